@@ -0,0 +1,55 @@
+use gameboy_core::emulator::Emulator;
+use serde::Deserialize;
+
+// <rom>.cheats.toml, a list of
+//
+// [[cheat]]
+// code = "01FF2DC6"
+// name = "infinite lives"
+// enabled = true
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct CheatFile {
+    cheat: Vec<CheatEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CheatEntry {
+    code: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default = "enabled_by_default")]
+    enabled: bool,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+pub fn path(rom_path: &str) -> String {
+    format!("{}.cheats.toml", rom_path)
+}
+
+// adds every cheat in the rom's cheat file, a rom without one has no cheats
+pub fn load(emulator: &mut Emulator, rom_path: &str) -> Result<(), String> {
+    let path = path(rom_path);
+
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(format!("{}: {}", path, e)),
+    };
+
+    let file: CheatFile = toml::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+
+    for entry in file.cheat {
+        let index = emulator.add_cheat(&entry.code).map_err(|e| {
+            let name = if entry.name.is_empty() { entry.code.clone() } else { entry.name.clone() };
+            format!("{}: {} {}", path, name, e)
+        })?;
+        emulator.set_cheat_enabled(index, entry.enabled);
+    }
+
+    Ok(())
+}
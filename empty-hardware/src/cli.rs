@@ -0,0 +1,254 @@
+use gameboy_core::emulator::{Speed, MAX_SPEED, MIN_SPEED};
+use minifb::Scale;
+use std::path::Path;
+
+pub const USAGE: &str = "\
+usage: empty-hardware [options] <rom>
+
+options:
+  --model dmg|cgb|auto      the game boy to emulate, auto picks by the rom header (default dmg)
+  --boot-rom <file>         run the boot rom first instead of starting at the cartridge entry point
+  --patch <file>            apply an ips, bps or ups patch, by default a patch with the rom's name is used
+  --symbols <file>          a .sym symbol file for the debuggers, by default the one with the rom's name is used
+  --strict-checksum         refuse roms with a bad header checksum
+  --speed <multiplier>      0.25 to 8 times the real speed, uncapped runs as fast as possible (default 1)
+  --no-audio                keep the apu muted
+  --scale 1|2|4|8|16|32     window scale (default 4)
+  --save-dir <dir>          where save states, screenshots and vram dumps go (default next to the rom)
+  --load-state <file>       load a save state before the first frame
+  --movie <file>            play back an input movie
+  --record-movie <file>     record an input movie from power on, it is written on exit
+  --trace <file>            log every instruction in the gameboy doctor format
+  --profile <prefix>        profile the cpu, written on exit as <prefix>.txt and <prefix>.callgrind
+  --cdl <file>              log how every rom byte is used, an existing log is added to and written back on exit
+  --printer                 plug a game boy printer into the link port, strips are saved as <rom>-print-<frame>.png
+  --gdb <port>              wait for gdb on localhost before the first instruction
+  --headless                run without a window, needs --frames
+  --frames <n>              frames to run headless
+  --screenshot <file>       the png the last headless frame is saved to
+  --dump-vram <prefix>      save the vram views after the last headless frame as <prefix>-<view>.png
+  --help                    show this";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Model {
+    Dmg,
+    Cgb,
+    Auto,
+}
+
+pub struct Headless {
+    pub frames: u64,
+    pub screenshot: Option<String>,
+    pub dump_vram: Option<String>,
+}
+
+pub struct Options {
+    pub rom: String,
+    pub boot_rom: Option<String>,
+    pub patch: Option<String>,
+    pub symbols: Option<String>,
+    pub model: Model,
+    pub strict_checksum: bool,
+    pub speed: Option<Speed>, // the real speed when not given, headless runs are always uncapped
+    pub audio: bool,
+    pub scale: Scale,
+    pub save_dir: Option<String>,
+    pub load_state: Option<String>,
+    pub movie: Option<String>,
+    pub record_movie: Option<String>,
+    pub trace: Option<String>,
+    pub profile: Option<String>, // the report files are <prefix>.txt and <prefix>.callgrind
+    pub cdl: Option<String>,
+    pub printer: bool,
+    pub gdb: Option<u16>, // port on localhost
+    pub headless: Option<Headless>,
+}
+
+impl Options {
+    // <save dir>/<rom file name><suffix>, or right next to the rom without a save dir
+    pub fn save_path(&self, suffix: &str) -> String {
+        match &self.save_dir {
+            Some(save_dir) => {
+                let name = Path::new(&self.rom)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+
+                Path::new(save_dir)
+                    .join(format!("{}{}", name, suffix))
+                    .to_string_lossy()
+                    .into_owned()
+            }
+            None => format!("{}{}", self.rom, suffix),
+        }
+    }
+}
+
+fn parse_model(model: &str) -> Result<Model, String> {
+    match model {
+        "dmg" => Ok(Model::Dmg),
+        "cgb" => Ok(Model::Cgb),
+        "auto" => Ok(Model::Auto),
+        _ => Err(format!("unknown model {}, expected dmg, cgb or auto", model)),
+    }
+}
+
+fn parse_speed(speed: &str) -> Result<Speed, String> {
+    if speed == "uncapped" {
+        return Ok(Speed::Uncapped);
+    }
+
+    match speed.parse::<f32>() {
+        Ok(multiplier) if (MIN_SPEED..=MAX_SPEED).contains(&multiplier) => Ok(Speed::Multiplier(multiplier)),
+        _ => Err(format!(
+            "bad speed {}, expected a multiplier from {} to {} or uncapped",
+            speed, MIN_SPEED, MAX_SPEED
+        )),
+    }
+}
+
+fn parse_scale(scale: &str) -> Result<Scale, String> {
+    match scale {
+        "1" => Ok(Scale::X1),
+        "2" => Ok(Scale::X2),
+        "4" => Ok(Scale::X4),
+        "8" => Ok(Scale::X8),
+        "16" => Ok(Scale::X16),
+        "32" => Ok(Scale::X32),
+        _ => Err(format!("bad scale {}, expected 1, 2, 4, 8, 16 or 32", scale)),
+    }
+}
+
+// args without the program name. Ok(None) means --help was asked for
+pub fn parse(args: &[String]) -> Result<Option<Options>, String> {
+    let mut rom = None;
+    let mut boot_rom = None;
+    let mut patch = None;
+    let mut symbols = None;
+    let mut model = Model::Dmg;
+    let mut strict_checksum = false;
+    let mut speed = None;
+    let mut audio = true;
+    let mut scale = None;
+    let mut save_dir = None;
+    let mut load_state = None;
+    let mut movie = None;
+    let mut record_movie = None;
+    let mut trace = None;
+    let mut profile = None;
+    let mut cdl = None;
+    let mut printer = false;
+    let mut gdb = None;
+    let mut headless = false;
+    let mut frames = None;
+    let mut screenshot = None;
+    let mut dump_vram = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().ok_or(format!("{} needs a value", arg));
+
+        match arg.as_str() {
+            "--help" | "-h" => return Ok(None),
+            "--model" => model = parse_model(&value()?)?,
+            "--boot-rom" => boot_rom = Some(value()?),
+            "--patch" => patch = Some(value()?),
+            "--symbols" => symbols = Some(value()?),
+            "--strict-checksum" => strict_checksum = true,
+            "--speed" => speed = Some(parse_speed(&value()?)?),
+            "--no-audio" => audio = false,
+            "--scale" => scale = Some(parse_scale(&value()?)?),
+            "--save-dir" => save_dir = Some(value()?),
+            "--load-state" => load_state = Some(value()?),
+            "--movie" => movie = Some(value()?),
+            "--record-movie" => record_movie = Some(value()?),
+            "--trace" => trace = Some(value()?),
+            "--profile" => profile = Some(value()?),
+            "--cdl" => cdl = Some(value()?),
+            "--printer" => printer = true,
+            "--gdb" => {
+                let port = value()?;
+                gdb = Some(port.parse::<u16>().map_err(|_| format!("bad port {}", port))?);
+            }
+            "--headless" => headless = true,
+            "--frames" => {
+                let count = value()?;
+                frames = Some(count.parse::<u64>().map_err(|_| format!("bad frame count {}", count))?);
+            }
+            "--screenshot" => screenshot = Some(value()?),
+            "--dump-vram" => dump_vram = Some(value()?),
+            flag if flag.starts_with("--") => return Err(format!("unknown option {}", flag)),
+            path => {
+                if rom.is_some() {
+                    return Err(format!("unexpected argument {}, only one rom can be given", path));
+                }
+                rom = Some(path.to_string());
+            }
+        }
+    }
+
+    let rom = rom.ok_or("no rom given")?;
+
+    if movie.is_some() && record_movie.is_some() {
+        return Err("--movie and --record-movie can't be used together".to_string());
+    }
+
+    // a movie starts from its own state, power on or the one it was recorded from
+    if movie.is_some() && load_state.is_some() {
+        return Err("--movie and --load-state can't be used together".to_string());
+    }
+
+    let headless = if headless {
+        if scale.is_some() {
+            return Err("--scale needs a window, it can't be used with --headless".to_string());
+        }
+
+        if gdb.is_some() {
+            return Err("--gdb needs a window, it can't be used with --headless".to_string());
+        }
+
+        if speed.is_some() {
+            return Err("headless runs are always uncapped, --speed can't be used with --headless".to_string());
+        }
+
+        Some(Headless {
+            frames: frames.ok_or("--headless needs --frames")?,
+            screenshot,
+            dump_vram,
+        })
+    } else {
+        let headless_only = [
+            ("--frames", frames.is_some()),
+            ("--screenshot", screenshot.is_some()),
+            ("--dump-vram", dump_vram.is_some()),
+        ];
+
+        if let Some((flag, _)) = headless_only.iter().find(|(_, given)| *given) {
+            return Err(format!("{} only works with --headless", flag));
+        }
+
+        None
+    };
+
+    Ok(Some(Options {
+        rom,
+        boot_rom,
+        patch,
+        symbols,
+        model,
+        strict_checksum,
+        speed,
+        audio,
+        scale: scale.unwrap_or(Scale::X4),
+        save_dir,
+        load_state,
+        movie,
+        record_movie,
+        trace,
+        profile,
+        cdl,
+        printer,
+        gdb,
+        headless,
+    }))
+}
@@ -0,0 +1,254 @@
+use crate::hardware::GameBoyHardware::Key as GameBoyKey;
+use crate::hardware::Layer;
+use minifb::Key;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+const CONFIG_ENV: &str = "GB_CONFIG";
+
+// the config file as it is written, key names are checked when it is turned into bindings
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub keyboard: Keyboard,
+    pub hotkeys: Hotkeys,
+    pub gamepad: Gamepad,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Keyboard {
+    pub right: String,
+    pub left: String,
+    pub up: String,
+    pub down: String,
+    pub a: String,
+    pub b: String,
+    pub select: String,
+    pub start: String,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Hotkeys {
+    pub rewind: String,
+    pub turbo: String,
+    pub pause: String,
+    pub save_state: String,
+    pub load_state: String,
+    pub reset: String,
+    pub screenshot: String,
+    pub viewer: String,
+    pub dump_vram: String,
+    pub quit: String,
+    pub toggle_background: String,
+    pub toggle_window: String,
+    pub toggle_sprites: String,
+    pub sprite_outlines: String,
+    pub window_area: String,
+    pub toggle_palettes: String,
+}
+
+// buttons use the evdev names (BTN_SOUTH, BTN_DPAD_UP, ...), the hat and the left stick always move the d-pad
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Gamepad {
+    pub enabled: bool,
+    pub device: Option<String>, // the first device that looks like a gamepad when not set
+    pub right: String,
+    pub left: String,
+    pub up: String,
+    pub down: String,
+    pub a: String,
+    pub b: String,
+    pub select: String,
+    pub start: String,
+    pub dead_zone: f32, // part of the stick range that is ignored
+}
+
+impl Default for Keyboard {
+    fn default() -> Keyboard {
+        Keyboard {
+            right: "Right".to_string(),
+            left: "Left".to_string(),
+            up: "Up".to_string(),
+            down: "Down".to_string(),
+            a: "Z".to_string(),
+            b: "X".to_string(),
+            select: "Space".to_string(),
+            start: "Enter".to_string(),
+        }
+    }
+}
+
+impl Default for Hotkeys {
+    fn default() -> Hotkeys {
+        Hotkeys {
+            rewind: "Backspace".to_string(),
+            turbo: "Tab".to_string(),
+            pause: "P".to_string(),
+            save_state: "F5".to_string(),
+            load_state: "F8".to_string(),
+            reset: "F1".to_string(),
+            screenshot: "F12".to_string(),
+            viewer: "F2".to_string(),
+            dump_vram: "F11".to_string(),
+            quit: "Escape".to_string(),
+            toggle_background: "Key1".to_string(),
+            toggle_window: "Key2".to_string(),
+            toggle_sprites: "Key3".to_string(),
+            sprite_outlines: "Key4".to_string(),
+            window_area: "Key5".to_string(),
+            toggle_palettes: "Key6".to_string(),
+        }
+    }
+}
+
+impl Default for Gamepad {
+    fn default() -> Gamepad {
+        Gamepad {
+            enabled: true,
+            device: None,
+            right: "BTN_DPAD_RIGHT".to_string(),
+            left: "BTN_DPAD_LEFT".to_string(),
+            up: "BTN_DPAD_UP".to_string(),
+            down: "BTN_DPAD_DOWN".to_string(),
+            a: "BTN_EAST".to_string(),
+            b: "BTN_SOUTH".to_string(),
+            select: "BTN_SELECT".to_string(),
+            start: "BTN_START".to_string(),
+            dead_zone: 0.5,
+        }
+    }
+}
+
+impl Gamepad {
+    // the game boy key every button name is bound to
+    pub fn buttons(&self) -> [(&str, GameBoyKey); 8] {
+        [
+            (&self.right, GameBoyKey::Right),
+            (&self.left, GameBoyKey::Left),
+            (&self.up, GameBoyKey::Up),
+            (&self.down, GameBoyKey::Down),
+            (&self.a, GameBoyKey::A),
+            (&self.b, GameBoyKey::B),
+            (&self.select, GameBoyKey::Select),
+            (&self.start, GameBoyKey::Start),
+        ]
+    }
+}
+
+// the keyboard side of the config with every key name resolved
+pub struct Bindings {
+    pub joypad: Vec<(Key, GameBoyKey)>,
+    pub rewind: Key,
+    pub turbo: Key,
+    pub pause: Key,
+    pub save_state: Key,
+    pub load_state: Key,
+    pub reset: Key,
+    pub screenshot: Key,
+    pub viewer: Key,
+    pub dump_vram: Key,
+    pub quit: Key,
+    pub layers: Vec<(Key, Layer)>,
+}
+
+impl Config {
+    // reads the file GB_CONFIG points at, or config.toml in the user config directory.
+    // a missing file means the default bindings
+    pub fn load() -> Result<Config, String> {
+        let path = match config_path() {
+            Some(path) => path,
+            None => return Ok(Config::default()),
+        };
+
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && std::env::var_os(CONFIG_ENV).is_none() => {
+                return Ok(Config::default())
+            }
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+
+        toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn bindings(&self) -> Result<Bindings, String> {
+        let keyboard = &self.keyboard;
+        let hotkeys = &self.hotkeys;
+
+        Ok(Bindings {
+            joypad: vec![
+                (parse_key(&keyboard.right)?, GameBoyKey::Right),
+                (parse_key(&keyboard.left)?, GameBoyKey::Left),
+                (parse_key(&keyboard.up)?, GameBoyKey::Up),
+                (parse_key(&keyboard.down)?, GameBoyKey::Down),
+                (parse_key(&keyboard.a)?, GameBoyKey::A),
+                (parse_key(&keyboard.b)?, GameBoyKey::B),
+                (parse_key(&keyboard.select)?, GameBoyKey::Select),
+                (parse_key(&keyboard.start)?, GameBoyKey::Start),
+            ],
+            rewind: parse_key(&hotkeys.rewind)?,
+            turbo: parse_key(&hotkeys.turbo)?,
+            pause: parse_key(&hotkeys.pause)?,
+            save_state: parse_key(&hotkeys.save_state)?,
+            load_state: parse_key(&hotkeys.load_state)?,
+            reset: parse_key(&hotkeys.reset)?,
+            screenshot: parse_key(&hotkeys.screenshot)?,
+            viewer: parse_key(&hotkeys.viewer)?,
+            dump_vram: parse_key(&hotkeys.dump_vram)?,
+            quit: parse_key(&hotkeys.quit)?,
+            layers: vec![
+                (parse_key(&hotkeys.toggle_background)?, Layer::Background),
+                (parse_key(&hotkeys.toggle_window)?, Layer::Window),
+                (parse_key(&hotkeys.toggle_sprites)?, Layer::Sprites),
+                (parse_key(&hotkeys.sprite_outlines)?, Layer::SpriteOutlines),
+                (parse_key(&hotkeys.window_area)?, Layer::WindowArea),
+                (parse_key(&hotkeys.toggle_palettes)?, Layer::Palettes),
+            ],
+        })
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os(CONFIG_ENV) {
+        return Some(PathBuf::from(path));
+    }
+
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => Path::new(&std::env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(config_dir.join("empty-hardware").join("config.toml"))
+}
+
+fn parse_key(name: &str) -> Result<Key, String> {
+    key_from_name(name).ok_or_else(|| format!("unknown key {}", name))
+}
+
+// keys are named after the minifb variants
+macro_rules! key_names {
+    ($($key:ident),*) => {
+        fn key_from_name(name: &str) -> Option<Key> {
+            match name {
+                $(stringify!($key) => Some(Key::$key),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+key_names!(
+    Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9,
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, F13, F14, F15,
+    Down, Left, Right, Up,
+    Apostrophe, Backquote, Backslash, Comma, Equal, LeftBracket, Minus, Period, RightBracket, Semicolon, Slash,
+    Backspace, Delete, End, Enter, Escape, Home, Insert, Menu, PageDown, PageUp, Pause, Space, Tab,
+    NumLock, CapsLock, ScrollLock,
+    LeftShift, RightShift, LeftCtrl, RightCtrl, LeftAlt, RightAlt, LeftSuper, RightSuper,
+    NumPad0, NumPad1, NumPad2, NumPad3, NumPad4, NumPad5, NumPad6, NumPad7, NumPad8, NumPad9,
+    NumPadDot, NumPadSlash, NumPadAsterisk, NumPadMinus, NumPadPlus, NumPadEnter
+);
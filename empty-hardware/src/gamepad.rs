@@ -0,0 +1,123 @@
+use crate::config::Gamepad;
+use std::sync::{atomic::AtomicU8, Arc};
+
+// reads the gamepad on its own thread, the game boy keys it holds down are kept in pressed (see hardware::key_bit)
+#[cfg(target_os = "linux")]
+pub fn start(config: &Gamepad, pressed: Arc<AtomicU8>) -> Result<(), String> {
+    use crate::hardware::{key_bit, GameBoyHardware::Key as GameBoyKey};
+    use evdev::{AbsoluteAxisType, Device, InputEventKind};
+    use std::str::FromStr;
+    use std::sync::atomic::Ordering;
+
+    if !config.enabled {
+        return Ok(());
+    }
+
+    let mut buttons = Vec::new();
+    for (name, key) in config.buttons().iter() {
+        let button = evdev::Key::from_str(name).map_err(|_| format!("unknown gamepad button {}", name))?;
+        buttons.push((button, key_bit(key)));
+    }
+
+    let mut device = match &config.device {
+        Some(path) => Device::open(path).map_err(|e| format!("failed to open gamepad {}: {}", path, e))?,
+        None => {
+            // no gamepad plugged in is not an error, the keyboard still works
+            let gamepad = evdev::enumerate()
+                .map(|(_, device)| device)
+                .find(|device| device.supported_keys().is_some_and(|keys| keys.contains(evdev::Key::BTN_SOUTH)));
+
+            match gamepad {
+                Some(device) => device,
+                None => return Ok(()),
+            }
+        }
+    };
+
+    // the stick range differs between gamepads, the dead zone is a part of it
+    let abs_state = device.get_abs_state().map_err(|e| format!("failed to read gamepad axes: {}", e))?;
+    let stick_range = |axis: AbsoluteAxisType| {
+        let info = abs_state[axis.0 as usize];
+        let center = (info.minimum + info.maximum) / 2;
+        let threshold = ((info.maximum - center) as f32 * config.dead_zone) as i32;
+        (center, threshold.max(1))
+    };
+    let stick_x = stick_range(AbsoluteAxisType::ABS_X);
+    let stick_y = stick_range(AbsoluteAxisType::ABS_Y);
+
+    let right = key_bit(&GameBoyKey::Right);
+    let left = key_bit(&GameBoyKey::Left);
+    let up = key_bit(&GameBoyKey::Up);
+    let down = key_bit(&GameBoyKey::Down);
+
+    // an axis past the dead zone on either side holds down one of the two directions
+    let axis = |value: i32, (center, threshold): (i32, i32), negative: u8, positive: u8| {
+        if value - center >= threshold {
+            positive
+        } else if center - value >= threshold {
+            negative
+        } else {
+            0
+        }
+    };
+
+    std::thread::spawn(move || {
+        let mut held_buttons = 0;
+        let mut hat = [0u8; 2];
+        let mut stick = [0u8; 2];
+
+        loop {
+            let events = match device.fetch_events() {
+                Ok(events) => events,
+                Err(e) => {
+                    // most likely unplugged, drop whatever it was holding down
+                    eprintln!("Lost the gamepad: {}", e);
+                    pressed.store(0, Ordering::Relaxed);
+                    return;
+                }
+            };
+
+            for event in events {
+                match event.kind() {
+                    InputEventKind::Key(key) => {
+                        for (button, bit) in buttons.iter() {
+                            if *button == key {
+                                if event.value() != 0 {
+                                    held_buttons |= bit;
+                                } else {
+                                    held_buttons &= !bit;
+                                }
+                            }
+                        }
+                    }
+                    InputEventKind::AbsAxis(AbsoluteAxisType::ABS_HAT0X) => {
+                        hat[0] = axis(event.value(), (0, 1), left, right);
+                    }
+                    InputEventKind::AbsAxis(AbsoluteAxisType::ABS_HAT0Y) => {
+                        hat[1] = axis(event.value(), (0, 1), up, down);
+                    }
+                    InputEventKind::AbsAxis(AbsoluteAxisType::ABS_X) => {
+                        stick[0] = axis(event.value(), stick_x, left, right);
+                    }
+                    InputEventKind::AbsAxis(AbsoluteAxisType::ABS_Y) => {
+                        stick[1] = axis(event.value(), stick_y, up, down);
+                    }
+                    _ => {}
+                }
+            }
+
+            pressed.store(held_buttons | hat[0] | hat[1] | stick[0] | stick[1], Ordering::Relaxed);
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn start(config: &Gamepad, _pressed: Arc<AtomicU8>) -> Result<(), String> {
+    if config.enabled && config.device.is_some() {
+        eprintln!("Gamepads are only supported on linux");
+    }
+
+    Ok(())
+}
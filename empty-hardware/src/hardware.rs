@@ -1,151 +1,323 @@
 use core::time::Duration;
-use minifb::{Scale, Window, WindowOptions};
-use crate::hardware::GameBoyHardware::Key;
-use std::collections::HashMap;
-use gameboy_core::hardware as GameBoyHardware;
+use std::time::Instant;
+use minifb::{KeyRepeat, Scale, Window, WindowOptions};
+use crate::config::Bindings;
+use crate::viewer::{ViewerWindows, Views};
+use crate::hardware::GameBoyHardware::{ButtonState, Key};
+pub use gameboy_core::hardware as GameBoyHardware;
 use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc, Mutex,
+    atomic::{AtomicBool, AtomicU8, Ordering},
+    mpsc::{self, Receiver, Sender, SyncSender},
+    Arc,
 };
 
-#[derive(Clone)]
+const PCM_SAMPLE_RATE: u32 = 44100;
+
+// the emulator side, it lives on the emulator thread and only talks to the gui through channels
 pub struct Hardware {
-    screen_buffer: Arc<Mutex<Vec<u32>>>,
-    key_state: Arc<Mutex<HashMap<Key, bool>>>,
+    screen_buffer: Vec<u32>,
+    frames: SyncSender<Vec<u32>>,
+    exit: Arc<AtomicBool>,
+    started: Instant,
+}
+
+// the window side, it has to stay on the main thread
+pub struct Gui {
+    screen_buffer: Vec<u32>,
+    frames: Receiver<Vec<u32>>,
+    keys: SyncSender<ButtonState>,
+    bindings: Bindings,
+    gamepad: Arc<AtomicU8>, // game boy keys the gamepad holds down, see key_bit
+    scale: Scale,
+    actions: Sender<Action>,
     exit: Arc<AtomicBool>,
+    rewinding: Arc<AtomicBool>,
+    turbo: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    viewer: Arc<AtomicBool>,
+    views: Receiver<Views>,
+    viewer_windows: ViewerWindows,
 }
 
-struct Gui {
-    window: Window,
-    screen_buffer: Arc<Mutex<Vec<u32>>>,
-    key_state: Arc<Mutex<HashMap<Key, bool>>>,
+// what the emulator thread needs to know about the gui on top of the hardware itself
+pub struct Controls {
+    frames: SyncSender<Vec<u32>>,
+    keys: Receiver<ButtonState>,
+    actions: Receiver<Action>,
     exit: Arc<AtomicBool>,
+    rewinding: Arc<AtomicBool>,
+    turbo: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    viewer: Arc<AtomicBool>,
+    views: SyncSender<Views>,
+}
+
+// hotkeys that have to be handled on the emulator thread
+#[derive(Clone)]
+pub enum Action {
+    SaveState,
+    LoadState,
+    Reset,
+    Screenshot,
+    DumpVram,
+    ToggleLayer(Layer),
+}
+
+// debug layers of the ppu that can be flipped from the keyboard
+#[derive(Clone, Copy)]
+pub enum Layer {
+    Background,
+    Window,
+    Sprites,
+    SpriteOutlines,
+    WindowArea,
+    Palettes,
+}
+
+pub fn key_bit(key: &Key) -> u8 {
+    match key {
+        Key::Right => 0x01,
+        Key::Left => 0x02,
+        Key::Up => 0x04,
+        Key::Down => 0x08,
+        Key::A => 0x10,
+        Key::B => 0x20,
+        Key::Select => 0x40,
+        Key::Start => 0x80,
+    }
+}
+
+pub fn new(bindings: Bindings, gamepad: Arc<AtomicU8>, scale: Scale) -> (Hardware, Gui, Controls) {
+    let screen_buffer = vec![0; GameBoyHardware::DISPLAY_WIDTH * GameBoyHardware::DISPLAY_HIGHT];
+
+    // a single slot is enough, if the gui is behind the frame is dropped instead of stalling the emulator
+    let (frames_sender, frames_receiver) = mpsc::sync_channel(1);
+    // the keys are sent every gui tick, a state that doesn't fit is replaced by the next one
+    let (keys_sender, keys_receiver) = mpsc::sync_channel(1);
+    let (actions_sender, actions_receiver) = mpsc::channel();
+    let (views_sender, views_receiver) = mpsc::sync_channel(1);
+
+    let exit = Arc::new(AtomicBool::new(false));
+    let rewinding = Arc::new(AtomicBool::new(false));
+    let turbo = Arc::new(AtomicBool::new(false));
+    let paused = Arc::new(AtomicBool::new(false));
+    let viewer = Arc::new(AtomicBool::new(false));
+
+    let controls = Controls {
+        frames: frames_sender.clone(),
+        keys: keys_receiver,
+        actions: actions_receiver,
+        exit: exit.clone(),
+        rewinding: rewinding.clone(),
+        turbo: turbo.clone(),
+        paused: paused.clone(),
+        viewer: viewer.clone(),
+        views: views_sender,
+    };
+
+    let hardware = Hardware {
+        screen_buffer: screen_buffer.clone(),
+        frames: frames_sender,
+        exit: exit.clone(),
+        started: Instant::now(),
+    };
+
+    let gui = Gui {
+        screen_buffer,
+        frames: frames_receiver,
+        keys: keys_sender,
+        bindings,
+        gamepad,
+        scale,
+        actions: actions_sender,
+        exit,
+        rewinding,
+        turbo,
+        paused,
+        viewer,
+        views: views_receiver,
+        viewer_windows: ViewerWindows::new(),
+    };
+
+    (hardware, gui, controls)
+}
+
+impl Controls {
+    pub fn exit(&self) -> bool {
+        self.exit.load(Ordering::Relaxed)
+    }
+
+    pub fn rewinding(&self) -> bool {
+        self.rewinding.load(Ordering::Relaxed)
+    }
+
+    pub fn turbo(&self) -> bool {
+        self.turbo.load(Ordering::Relaxed)
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    pub fn viewer(&self) -> bool {
+        self.viewer.load(Ordering::Relaxed)
+    }
+
+    // like frames, views are dropped while the gui still has older ones to show
+    pub fn show_views(&self, views: Views) {
+        let _ = self.views.try_send(views);
+    }
+
+    // the keys the gui saw last, None when nothing new arrived since the last call
+    pub fn keys(&self) -> Option<ButtonState> {
+        self.keys.try_iter().last()
+    }
+
+    pub fn next_action(&self) -> Option<Action> {
+        self.actions.try_recv().ok()
+    }
+
+    // frames don't reach the hardware while rewinding so they are sent from here
+    pub fn show_frame(&self, frame: &[u32]) {
+        let _ = self.frames.try_send(frame.to_vec());
+    }
 }
 
 
 impl Gui {
-    fn new (screen_buffer: Arc<Mutex<Vec<u32>>>, key_state: Arc<Mutex<HashMap<Key, bool>>>, exit: Arc<AtomicBool>) -> Self {
-        let window = Window::new("game boy", GameBoyHardware::DISPLAY_WIDTH, GameBoyHardware::DISPLAY_HIGHT, WindowOptions {
+    pub fn run(mut self) {
+        let mut window = Window::new("game boy", GameBoyHardware::DISPLAY_WIDTH, GameBoyHardware::DISPLAY_HIGHT, WindowOptions {
             resize: false,
-            scale: Scale::X4,
+            scale: self.scale,
             ..WindowOptions::default()
         }).unwrap();
 
-        Self {
-            window,
-            key_state,
-            screen_buffer,
-            exit,
+        while !self.exit.load(Ordering::Relaxed) {
+            std::thread::sleep(Duration::from_millis(10));
+            self.update_screen(&mut window);
+            self.update_viewer();
+            self.key_state(&window);
         }
     }
 
-    fn run(mut self) {
-        while !self.exit.load(Ordering::Relaxed) {
-            std::thread::sleep(Duration::from_millis(10));
-            self.update_screen();
-            self.key_state();
+    fn update_screen(&mut self, window: &mut Window) {
+        // only the newest frame is worth drawing
+        while let Ok(frame) = self.frames.try_recv() {
+            self.screen_buffer = frame;
         }
+
+        window.update_with_buffer(&self.screen_buffer).unwrap();
     }
 
-    fn update_screen(&mut self) {
-        let vram = self.screen_buffer.lock().unwrap().clone();
-        self.window.update_with_buffer(&vram).unwrap();
+    fn update_viewer(&mut self) {
+        if !self.viewer.load(Ordering::Relaxed) {
+            self.viewer_windows.close();
+            return;
+        }
+
+        let mut views = None;
+        while let Ok(newer) = self.views.try_recv() {
+            views = Some(newer);
+        }
+
+        // closing any of the viewer windows closes all of them
+        if !self.viewer_windows.update(views) {
+            self.viewer.store(false, Ordering::Relaxed);
+            self.viewer_windows.close();
+        }
     }
 
-    fn key_state(&mut self) {
-        if !self.window.is_open() {
+    fn key_state(&mut self, window: &Window) {
+        if !window.is_open() {
             self.exit.store(true, Ordering::Relaxed);
         }
 
-        for (_, v) in self.key_state.lock().unwrap().iter_mut() {
-            *v = false;
+        let bindings = &self.bindings;
+
+        if window.is_key_down(bindings.quit) {
+            self.exit.store(true, Ordering::Relaxed);
+            return;
         }
 
-        if let Some(keys) = self.window.get_keys() {
-            for k in keys {
-                let gbk = match k {
-                    minifb::Key::Right => Key::Right,
-                    minifb::Key::Left => Key::Left,
-                    minifb::Key::Up => Key::Up,
-                    minifb::Key::Down => Key::Down,
-                    minifb::Key::Z => Key::A,
-                    minifb::Key::X => Key::B,
-                    minifb::Key::Space => Key::Select,
-                    minifb::Key::Enter => Key::Start,
-                    minifb::Key::Escape => {
-                        self.exit.store(true, Ordering::Relaxed);
-                        return;
-                    }
-                    _ => continue,
-                };
-
-
-                match self.key_state.lock().unwrap().get_mut(&gbk) {
-                    Some(v) => *v = true,
-                    None => unreachable!(),
-                }
-            }
+        self.rewinding.store(window.is_key_down(bindings.rewind), Ordering::Relaxed);
+
+        if window.is_key_pressed(bindings.turbo, KeyRepeat::No) {
+            self.turbo.fetch_xor(true, Ordering::Relaxed);
         }
-    }
-}
 
+        if window.is_key_pressed(bindings.pause, KeyRepeat::No) {
+            self.paused.fetch_xor(true, Ordering::Relaxed);
+        }
 
-impl Hardware {
-    pub fn new() -> Self {
-        let screen_buffer = Arc::new(Mutex::new(vec![0; GameBoyHardware::DISPLAY_WIDTH * GameBoyHardware::DISPLAY_HIGHT]));
+        if window.is_key_pressed(bindings.viewer, KeyRepeat::No) {
+            self.viewer.fetch_xor(true, Ordering::Relaxed);
+        }
 
+        let actions = [
+            (bindings.save_state, Action::SaveState),
+            (bindings.load_state, Action::LoadState),
+            (bindings.reset, Action::Reset),
+            (bindings.screenshot, Action::Screenshot),
+            (bindings.dump_vram, Action::DumpVram),
+        ];
+        for (key, action) in actions.iter() {
+            if window.is_key_pressed(*key, KeyRepeat::No) {
+                let _ = self.actions.send(action.clone());
+            }
+        }
 
-        let mut key_state = HashMap::new();
-        key_state.insert(Key::Right, false);
-        key_state.insert(Key::Left, false);
-        key_state.insert(Key::Up, false);
-        key_state.insert(Key::Down, false);
-        key_state.insert(Key::A, false);
-        key_state.insert(Key::B, false);
-        key_state.insert(Key::Select, false);
-        key_state.insert(Key::Start, false);
-        let key_state = Arc::new(Mutex::new(key_state));
+        for (key, layer) in bindings.layers.iter() {
+            if window.is_key_pressed(*key, KeyRepeat::No) {
+                let _ = self.actions.send(Action::ToggleLayer(*layer));
+            }
+        }
 
-        let exit = Arc::new(AtomicBool::new(false));
+        let mut keys = self.gamepad.load(Ordering::Relaxed);
 
-        Self {
-            screen_buffer,
-            key_state,
-            exit,
+        for (key, gbk) in bindings.joypad.iter() {
+            if window.is_key_down(*key) {
+                keys |= key_bit(gbk);
+            }
         }
-    }
 
-    pub fn run(self) {
-        let bg = Gui::new(
-            self.screen_buffer.clone(),
-            self.key_state.clone(),
-            self.exit.clone(),
-        );
-        bg.run();
+        // key_bit numbers the keys the way ButtonState stores them
+        let _ = self.keys.try_send(ButtonState::from_bits(keys));
     }
 }
 
 impl GameBoyHardware::Hardware for Hardware {
     fn draw_line(&mut self, line: usize, buffer: &[u32]) {
-        let mut screen_buffer = self.screen_buffer.lock().unwrap();
+        let base = line * GameBoyHardware::DISPLAY_WIDTH;
+        self.screen_buffer[base..base + buffer.len()].copy_from_slice(buffer);
 
-        for i in 0..buffer.len() {
-            let base = line * GameBoyHardware::DISPLAY_WIDTH;
-            screen_buffer[base + i] = buffer[i];
+        if line == GameBoyHardware::DISPLAY_HIGHT - 1 {
+            let _ = self.frames.try_send(self.screen_buffer.clone());
         }
     }
 
-    fn joypad_pressed(&mut self, key: gameboy_core::hardware::Key) -> bool {
-        *self.key_state.lock().unwrap().get_mut(&key).expect("Key err")
+    // the emulator thread hands the keys over with set_input every frame, the hardware is never asked
+    fn joypad_pressed(&mut self, _key: gameboy_core::hardware::Key) -> bool {
+        false
+    }
+
+    // monotonic, a wall clock that is set back would stall the pacing
+    fn clock(&mut self) -> Duration {
+        self.started.elapsed()
     }
 
-    fn clock(&mut self) -> Duration { 
-        std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .expect("Couldn't get epoch")
+    // the window shows every frame, so the game runs at the speed it is set to
+    fn paced(&mut self) -> bool {
+        true
     }
 
+    // there is no audio output yet
+    fn pcm_sample_rate(&mut self) -> u32 {
+        PCM_SAMPLE_RATE
+    }
+
+    fn next_pcm_amplitude(&mut self, _amplitude: f32) {}
+
     fn run(&mut self) -> bool {
         !self.exit.load(Ordering::Relaxed)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,74 @@
+use crate::cli::{Headless, Options};
+use crate::printer;
+use crate::viewer;
+use gameboy_core::emulator::{Emulator, EmulatorConfig};
+use gameboy_core::hardware::{self as gameboy_hw, Key};
+use std::process;
+use std::time::{Duration, Instant};
+
+// nothing to show and nobody pressing keys
+struct HeadlessHardware {
+    started: Instant,
+}
+
+impl gameboy_hw::Hardware for HeadlessHardware {
+    fn draw_line(&mut self, _line: usize, _buffer: &[u32]) {}
+
+    fn joypad_pressed(&mut self, _key: Key) -> bool {
+        false
+    }
+
+    fn clock(&mut self) -> Duration {
+        self.started.elapsed()
+    }
+
+    fn pcm_sample_rate(&mut self) -> u32 {
+        44100
+    }
+
+    fn next_pcm_amplitude(&mut self, _amplitude: f32) {}
+
+    fn run(&mut self) -> bool {
+        true
+    }
+}
+
+// runs the given amount of frames as fast as possible, then saves the screenshot and vram views that were asked for
+pub fn run(options: &Options, headless: &Headless, rom: Vec<u8>, conf: EmulatorConfig) {
+    let mut emulator = match Emulator::new(rom, Box::new(HeadlessHardware { started: Instant::now() }), conf) {
+        Ok(emulator) => emulator,
+        Err(e) => {
+            eprintln!("Failed to load rom {}: {}", options.rom, e);
+            process::exit(1);
+        }
+    };
+    crate::prepare(&mut emulator, options);
+    let mut paper = options.printer.then(|| printer::connect(&mut emulator));
+
+    let mut lock_up_reported = false;
+    for _ in 0..headless.frames {
+        emulator.run_frame();
+        crate::report_lock_up(&emulator, &mut lock_up_reported);
+
+        if let Some(paper) = &mut paper {
+            paper.collect(&emulator, options);
+        }
+    }
+
+    crate::finish(&mut emulator, options);
+
+    if let Some(paper) = &mut paper {
+        paper.tear_off(&emulator, options);
+    }
+
+    if let Some(path) = &headless.screenshot {
+        if let Err(e) = viewer::save_png(path, &viewer::frame_image(&emulator)) {
+            eprintln!("Failed to save {}: {}", path, e);
+            process::exit(1);
+        }
+    }
+
+    if let Some(prefix) = &headless.dump_vram {
+        viewer::dump(&emulator, prefix);
+    }
+}
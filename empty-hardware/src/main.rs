@@ -1,37 +1,345 @@
+mod cheats;
+mod cli;
+mod config;
+mod gamepad;
 mod hardware;
+mod headless;
+mod printer;
+mod repl;
+mod rom;
+mod symbols;
+mod viewer;
 
-use gameboy_core::emulator;
+use cli::{Model, Options};
+use gameboy_core::emulator::{self, Emulator, EmulatorConfig, GameBoyMode, Speed};
+use gameboy_core::gdb;
 use gameboy_core::hardware as gameboy_hw;
-use hardware::Hardware;
+use gameboy_core::movie::Movie;
+use hardware::{Action, Layer};
 use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::net::TcpListener;
 use std::process;
+use std::sync::{atomic::AtomicU8, Arc};
+use std::time::Duration;
+
+const REWIND_BUDGET: usize = 64 * 1024 * 1024;
+const REWIND_STEP: Duration = Duration::from_millis(16);
+const PAUSE_STEP: Duration = Duration::from_millis(16);
+
+// every error before the emulator runs ends the program
+fn exit_on_error<T, E: std::fmt::Display>(result: Result<T, E>, message: &str) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => {
+            eprintln!("{}: {}", message, e);
+            process::exit(1);
+        }
+    }
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let args: Vec<String> = env::args().skip(1).collect();
 
-    if args.len() < 2 {
-        println!("You must supply the rom name!");
-        process::exit(1);
+    let options = match cli::parse(&args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            process::exit(1);
+        }
+    };
+
+    if let Some(save_dir) = &options.save_dir {
+        exit_on_error(std::fs::create_dir_all(save_dir), &format!("Failed to create {}", save_dir));
     }
 
-    start_gameboy(args[1].clone());
+    let rom = match rom::load(&options.rom, options.patch.as_deref(), options.strict_checksum) {
+        Ok(rom) => rom,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    // without a boot rom we start right at the cartridge entry point
+    let boot_rom = options
+        .boot_rom
+        .as_ref()
+        .map(|path| exit_on_error(std::fs::read(path), &format!("Failed to read boot rom {}", path)));
+
+    let game_boy_mode = match options.model {
+        Model::Dmg => GameBoyMode::Classic,
+        Model::Cgb => GameBoyMode::Color,
+        Model::Auto => GameBoyMode::detect(&rom),
+    };
+
+    let conf = EmulatorConfig {
+        allow_bad_checksum: !options.strict_checksum,
+        game_boy_mode,
+        boot_rom,
+        native_speed: options.headless.is_some() || options.speed == Some(Speed::Uncapped),
+        cpu_speed: gameboy_hw::PROCESSOR_CLOCK_SPEED,
+        rewind: match options.headless {
+            Some(_) => None,
+            None => Some(emulator::RewindConfig {
+                interval: 1,
+                budget: REWIND_BUDGET,
+            }),
+        },
+        block_opposite_directions: true,
+        audio: options.audio,
+    };
+
+    match &options.headless {
+        Some(headless) => headless::run(&options, headless, rom, conf),
+        None => start_gameboy(options, rom, conf),
+    }
 }
 
-fn start_gameboy(rom: String) {
-    let hardware = Hardware::new();
-    let hardware_clone = hardware.clone();
-
-    std::thread::spawn(move || {
-        let rom = std::fs::read(rom).unwrap();
-        let conf = emulator::EmulatorConfig {
-            allow_bad_checksum: true,
-            game_boy_mode: emulator::GameBoyMode::Classic,
-            native_speed: cfg!(debug_assertions), // run on native speed on debug mode
-            cpu_speed: gameboy_hw::PROCESSOR_CLOCK_SPEED,
+// everything the options ask for before the first frame
+pub fn prepare(emulator: &mut Emulator, options: &Options) {
+    if let Some(Speed::Multiplier(multiplier)) = options.speed {
+        emulator.set_speed(Speed::Multiplier(multiplier)).expect("the cli only takes multipliers in range");
+    }
+
+    if let Err(e) = cheats::load(emulator, &options.rom) {
+        eprintln!("Failed to load cheats from {}", e);
+        process::exit(1);
+    }
+
+    if let Err(e) = symbols::load(emulator, &options.rom, options.symbols.as_deref()) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+
+    if let Some(path) = &options.load_state {
+        let state = exit_on_error(std::fs::read(path), &format!("Failed to read state {}", path));
+        exit_on_error(emulator.load_state(&state), &format!("Failed to load state {}", path));
+    }
+
+    if let Some(path) = &options.movie {
+        let movie = exit_on_error(std::fs::read(path), &format!("Failed to read movie {}", path));
+        let movie = exit_on_error(Movie::from_bytes(&movie), &format!("Failed to read movie {}", path));
+        exit_on_error(emulator.play_movie(movie), &format!("Failed to play movie {}", path));
+    }
+
+    if options.record_movie.is_some() {
+        emulator.start_recording();
+    }
+
+    if let Some(path) = &options.trace {
+        let file = exit_on_error(File::create(path), &format!("Failed to create trace {}", path));
+        exit_on_error(emulator.set_trace(Some(Box::new(file))), "Failed to start the trace");
+    }
+
+    if options.profile.is_some() {
+        emulator.start_profiling();
+    }
+
+    if let Some(path) = &options.cdl {
+        let previous = match std::fs::read(path) {
+            Ok(log) => Some(log),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => {
+                eprintln!("Failed to read code/data log {}: {}", path, e);
+                process::exit(1);
+            }
         };
 
-        emulator::run(rom, Box::new(hardware_clone), conf)
+        exit_on_error(
+            emulator.start_code_data_log(previous.as_deref()),
+            &format!("Failed to continue code/data log {}", path),
+        );
+    }
+}
+
+// tells once that the game hung the cpu, the game keeps running with it locked up until a reset or a state load
+pub fn report_lock_up(emulator: &Emulator, reported: &mut bool) {
+    match emulator.lock_up() {
+        Some(lock_up) if !*reported => {
+            eprintln!(
+                "The cpu locked up on illegal opcode {:02x} at {}",
+                lock_up.opcode,
+                emulator.describe(lock_up.adder)
+            );
+            *reported = true;
+        }
+        Some(_) => {}
+        None => *reported = false,
+    }
+}
+
+// writes out what was recorded
+pub fn finish(emulator: &mut Emulator, options: &Options) {
+    if let (Some(path), Err(e)) = (&options.trace, emulator.set_trace(None)) {
+        eprintln!("Failed to write trace to {}: {}", path, e);
+    }
+
+    if let (Some(path), Some(movie)) = (&options.record_movie, emulator.stop_recording()) {
+        if let Err(e) = std::fs::write(path, movie.to_bytes()) {
+            eprintln!("Failed to save movie to {}: {}", path, e);
+        }
+    }
+
+    if let (Some(prefix), Some(profile)) = (&options.profile, emulator.stop_profiling()) {
+        let report = format!("{}.txt", prefix);
+        if let Err(e) = std::fs::write(&report, profile.report(emulator.symbols())) {
+            eprintln!("Failed to save profile to {}: {}", report, e);
+        }
+
+        let callgrind = format!("{}.callgrind", prefix);
+        let written = File::create(&callgrind).and_then(|file| {
+            let mut out = BufWriter::new(file);
+            profile.write_callgrind(emulator.symbols(), &mut out)?;
+            out.flush()
+        });
+        if let Err(e) = written {
+            eprintln!("Failed to save profile to {}: {}", callgrind, e);
+        }
+    }
+
+    if let (Some(path), Some(log)) = (&options.cdl, emulator.stop_code_data_log()) {
+        if let Err(e) = std::fs::write(path, log.to_bytes()) {
+            eprintln!("Failed to save code/data log to {}: {}", path, e);
+        }
+    }
+}
+
+fn start_gameboy(options: Options, rom: Vec<u8>, conf: EmulatorConfig) {
+    let config = exit_on_error(config::Config::load(), "Failed to read the config");
+    let bindings = exit_on_error(config.bindings(), "Bad key bindings");
+
+    let gamepad = Arc::new(AtomicU8::new(0));
+    exit_on_error(gamepad::start(&config.gamepad, gamepad.clone()), "Gamepad error");
+
+    let (hardware, gui, controls) = hardware::new(bindings, gamepad, options.scale);
+
+    // load the rom before opening the window so a bad rom is reported right away
+    let mut emulator = exit_on_error(
+        Emulator::new(rom, Box::new(hardware), conf),
+        &format!("Failed to load rom {}", options.rom),
+    );
+    prepare(&mut emulator, &options);
+    let mut paper = options.printer.then(|| printer::connect(&mut emulator));
+
+    // bound before the window opens so a port in use is reported right away
+    let gdb_listener = options.gdb.map(|port| {
+        let listener = exit_on_error(TcpListener::bind(("127.0.0.1", port)), &format!("Failed to listen on port {}", port));
+        println!("Waiting for gdb on 127.0.0.1:{}", port);
+        listener
     });
 
-    hardware.run();
+    let state_path = options.save_path(".state");
+    let commands = repl::start();
+
+    let emulator_thread = std::thread::spawn(move || {
+        // the game only runs when the debugger says so, once it detaches the game goes on as usual
+        if let Some(listener) = gdb_listener {
+            if let Err(e) = gdb::serve(&mut emulator, &listener, &|| controls.exit()) {
+                eprintln!("Gdb connection failed: {}", e);
+            }
+        }
+
+        let normal_speed = emulator.speed();
+        let mut repl = repl::Repl::new();
+        let mut lock_up_reported = false;
+
+        while !controls.exit() {
+            while let Ok(command) = commands.try_recv() {
+                repl.execute(&mut emulator, &command);
+            }
+
+            if let Some(keys) = controls.keys() {
+                emulator.set_input(keys);
+            }
+
+            while let Some(action) = controls.next_action() {
+                match action {
+                    Action::SaveState => {
+                        if let Err(e) = std::fs::write(&state_path, emulator.save_state()) {
+                            eprintln!("Failed to save state to {}: {}", state_path, e);
+                        }
+                    }
+                    Action::LoadState => {
+                        let loaded = std::fs::read(&state_path)
+                            .map_err(|e| e.to_string())
+                            .and_then(|state| emulator.load_state(&state).map_err(|e| e.to_string()));
+
+                        match loaded {
+                            Ok(()) => controls.show_frame(emulator.frame_buffer()),
+                            Err(e) => eprintln!("Failed to load state from {}: {}", state_path, e),
+                        }
+                    }
+                    Action::Reset => emulator.reset(),
+                    Action::ToggleLayer(layer) => {
+                        let mut layers = emulator.debug_layers();
+                        let toggle = match layer {
+                            Layer::Background => &mut layers.background,
+                            Layer::Window => &mut layers.window,
+                            Layer::Sprites => &mut layers.sprites,
+                            Layer::SpriteOutlines => &mut layers.sprite_outlines,
+                            Layer::WindowArea => &mut layers.window_area,
+                            Layer::Palettes => &mut layers.palettes,
+                        };
+                        *toggle = !*toggle;
+                        emulator.set_debug_layers(layers);
+                    }
+                    Action::Screenshot => {
+                        let path = options.save_path(&format!("-{}.png", emulator.frame_count()));
+                        if let Err(e) = viewer::save_png(&path, &viewer::frame_image(&emulator)) {
+                            eprintln!("Failed to save screenshot to {}: {}", path, e);
+                        }
+                    }
+                    Action::DumpVram => viewer::dump(&emulator, &options.save_path(&format!("-{}", emulator.frame_count()))),
+                }
+            }
+
+            let speed = if controls.turbo() {
+                Speed::Uncapped
+            } else {
+                normal_speed
+            };
+
+            if emulator.speed() != speed {
+                emulator.set_speed(speed).expect("the speed was taken once already");
+            }
+
+            if controls.rewinding() {
+                // step back a frame at a time at about the pace frames are shown
+                if emulator.rewind() {
+                    controls.show_frame(emulator.frame_buffer());
+                }
+                std::thread::sleep(REWIND_STEP);
+            } else if controls.paused() {
+                std::thread::sleep(PAUSE_STEP);
+            } else {
+                emulator.run_frame();
+            }
+
+            report_lock_up(&emulator, &mut lock_up_reported);
+
+            if let Some(paper) = &mut paper {
+                paper.collect(&emulator, &options);
+            }
+
+            if controls.viewer() {
+                controls.show_views(viewer::views(&emulator));
+            }
+        }
+
+        finish(&mut emulator, &options);
+
+        if let Some(paper) = &mut paper {
+            paper.tear_off(&emulator, &options);
+        }
+    });
+
+    gui.run();
+
+    emulator_thread.join().unwrap();
 }
@@ -0,0 +1,62 @@
+use crate::cli::Options;
+use crate::viewer;
+use gameboy_core::emulator::{DebugImage, Emulator};
+use gameboy_core::printer::{Printer, Printout};
+use std::sync::mpsc::{self, Receiver};
+
+// printouts that follow each other without a margin are one strip of paper, the strip is
+// saved as <rom>-print-<frame>.png once the paper is fed past it
+pub struct Paper {
+    printouts: Receiver<Printout>,
+    strip: Option<DebugImage>,
+}
+
+// plugs a printer into the link port
+pub fn connect(emulator: &mut Emulator) -> Paper {
+    let (sender, printouts) = mpsc::channel();
+    emulator.connect_serial(Box::new(Printer::new(sender)));
+
+    Paper {
+        printouts,
+        strip: None,
+    }
+}
+
+impl Paper {
+    // picks up what the game printed since the last call
+    pub fn collect(&mut self, emulator: &Emulator, options: &Options) {
+        while let Ok(printout) = self.printouts.try_recv() {
+            if printout.margin_before > 0 {
+                self.tear_off(emulator, options);
+            }
+
+            self.append(printout.image);
+
+            if printout.margin_after > 0 {
+                self.tear_off(emulator, options);
+            }
+        }
+    }
+
+    fn append(&mut self, image: DebugImage) {
+        match &mut self.strip {
+            Some(strip) => {
+                strip.pixels.extend(image.pixels);
+                strip.height += image.height;
+            }
+            None if image.height > 0 => self.strip = Some(image),
+            None => {}
+        }
+    }
+
+    // saves the strip printed so far
+    pub fn tear_off(&mut self, emulator: &Emulator, options: &Options) {
+        if let Some(strip) = self.strip.take() {
+            let path = options.save_path(&format!("-print-{}.png", emulator.frame_count()));
+            match viewer::save_png(&path, &strip) {
+                Ok(()) => println!("Printed {}", path),
+                Err(e) => eprintln!("Failed to save printout to {}: {}", path, e),
+            }
+        }
+    }
+}
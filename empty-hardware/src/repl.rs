@@ -0,0 +1,232 @@
+use gameboy_core::cheat_search::{CheatSearch, SearchFilter};
+use gameboy_core::emulator::Emulator;
+use gameboy_core::hardware::DISPLAY_HIGHT;
+use std::io::BufRead;
+use std::sync::mpsc::{self, Receiver};
+
+// lists longer than this are cut short, a search starts with thousands of candidates
+const MAX_LISTED: usize = 32;
+
+const HELP: &str = "\
+commands, numbers are hex, addresses can be labels from the symbol file:
+  peek <addr> [len]    dump memory
+  poke <addr> <value>  write a byte to ram, vram, oam or hram
+  sym <addr>           show the bank and label for addr as memory is mapped now
+  where                show the bank and label for pc, the calls that led there and where the cpu locked up
+  profile              show the hottest functions and instructions so far
+  profile start | stop start a new cpu profile or stop it
+  cdl                  show how much of the rom the code/data log has seen
+  scanline <ly> | off  tint a line (0 - 8f) to line up raster effects with it
+  search               start a cheat search with every ram address
+  search = | != | > | < compare with the last search step
+  search <value>       keep addresses holding value
+  search list          show the addresses that are left
+  cheat <code>         add a game genie or gameshark code
+  cheat list           show the cheats with their numbers
+  cheat on | off <n>   enable or disable a cheat
+  cheat remove <n>     drop a cheat
+  help";
+
+// reads commands from stdin on its own thread, they are run on the emulator thread between frames
+pub fn start() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            match line {
+                Ok(line) => {
+                    if sender.send(line).is_err() {
+                        return;
+                    }
+                }
+                Err(_) => return,
+            }
+        }
+    });
+
+    receiver
+}
+
+pub struct Repl {
+    search: Option<CheatSearch>,
+}
+
+impl Repl {
+    pub fn new() -> Repl {
+        Repl { search: None }
+    }
+
+    pub fn execute(&mut self, emulator: &mut Emulator, line: &str) {
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        let result = match words.as_slice() {
+            [] => Ok(()),
+            ["peek", addr] => parse_addr(emulator, addr).map(|addr| peek(emulator, addr, 1)),
+            ["peek", addr, len] => parse_addr(emulator, addr).and_then(|addr| {
+                peek(emulator, addr, parse(len)?);
+                Ok(())
+            }),
+            ["poke", addr, value] => parse_addr(emulator, addr).and_then(|addr| {
+                if emulator.poke(addr, parse_byte(value)?) {
+                    Ok(())
+                } else {
+                    Err(format!("can't poke {:04x}, only ram, vram, oam and hram can be written", addr))
+                }
+            }),
+            ["sym", addr] => parse_addr(emulator, addr).map(|addr| println!("{}", emulator.describe(addr))),
+            ["where"] => {
+                println!("pc {}", emulator.describe(emulator.registers().pc));
+                print!("{}", emulator.describe_backtrace());
+                if let Some(lock_up) = emulator.lock_up() {
+                    println!("locked up on illegal opcode {:02x} at {}", lock_up.opcode, emulator.describe(lock_up.adder));
+                }
+                Ok(())
+            }
+            ["profile"] => match emulator.profile() {
+                Some(profile) => {
+                    print!("{}", profile.report(emulator.symbols()));
+                    Ok(())
+                }
+                None => Err("not profiling, try profile start".to_string()),
+            },
+            ["profile", "start"] => {
+                emulator.start_profiling();
+                Ok(())
+            }
+            ["profile", "stop"] => emulator
+                .stop_profiling()
+                .map(|profile| print!("{}", profile.report(emulator.symbols())))
+                .ok_or("not profiling".to_string()),
+            ["cdl"] => match emulator.code_data_log() {
+                Some(log) => {
+                    let coverage = log.coverage();
+                    let size = log.flags().len();
+                    println!(
+                        "{:x} bytes: {:x} code, {:x} data, {:x} unused ({:.1}% seen)",
+                        size,
+                        coverage.code,
+                        coverage.data,
+                        coverage.unused,
+                        (size - coverage.unused) as f64 * 100.0 / size as f64
+                    );
+                    Ok(())
+                }
+                None => Err("no code/data log, start with --cdl <file>".to_string()),
+            },
+            ["scanline", "off"] => {
+                set_scanline(emulator, None);
+                Ok(())
+            }
+            ["scanline", line] => parse_byte(line).and_then(|line| {
+                if line as usize >= DISPLAY_HIGHT {
+                    return Err(format!("bad scanline {:x}, the screen has lines 0 to {:x}", line, DISPLAY_HIGHT - 1));
+                }
+                set_scanline(emulator, Some(line));
+                Ok(())
+            }),
+            ["search"] => {
+                let search = CheatSearch::new(emulator);
+                println!("{} candidates", search.candidates().len());
+                self.search = Some(search);
+                Ok(())
+            }
+            ["search", "list"] => self.list(emulator),
+            ["search", filter] => self.filter(emulator, filter),
+            ["cheat", "list"] => {
+                list_cheats(emulator);
+                Ok(())
+            }
+            ["cheat", "on", index] => parse(index).map(|index| emulator.set_cheat_enabled(index as usize, true)),
+            ["cheat", "off", index] => parse(index).map(|index| emulator.set_cheat_enabled(index as usize, false)),
+            ["cheat", "remove", index] => parse(index).map(|index| emulator.remove_cheat(index as usize)),
+            ["cheat", code] => emulator
+                .add_cheat(code)
+                .map(|index| println!("added cheat {:x}", index))
+                .map_err(|e| e.to_string()),
+            ["help"] => {
+                println!("{}", HELP);
+                Ok(())
+            }
+            _ => Err(format!("unknown command {}, try help", line.trim())),
+        };
+
+        if let Err(e) = result {
+            println!("{}", e);
+        }
+    }
+
+    fn filter(&mut self, emulator: &Emulator, filter: &str) -> Result<(), String> {
+        let filter = match filter {
+            "=" => SearchFilter::Equal,
+            "!=" => SearchFilter::Changed,
+            ">" => SearchFilter::Greater,
+            "<" => SearchFilter::Less,
+            value => SearchFilter::Value(parse_byte(value)?),
+        };
+
+        let search = self.search.as_mut().ok_or("no search running, start one with search")?;
+        search.filter(emulator, filter);
+        println!("{} candidates", search.candidates().len());
+
+        Ok(())
+    }
+
+    fn list(&self, emulator: &Emulator) -> Result<(), String> {
+        let search = self.search.as_ref().ok_or("no search running, start one with search")?;
+
+        for (addr, value) in search.candidates().iter().take(MAX_LISTED) {
+            match emulator.symbolize(*addr) {
+                Some(label) => println!("{:04x}: {:02x} {}", addr, value, label),
+                None => println!("{:04x}: {:02x}", addr, value),
+            }
+        }
+
+        if search.candidates().len() > MAX_LISTED {
+            println!("... {} more", search.candidates().len() - MAX_LISTED);
+        }
+
+        Ok(())
+    }
+}
+
+fn set_scanline(emulator: &mut Emulator, scanline: Option<u8>) {
+    let mut layers = emulator.debug_layers();
+    layers.scanline = scanline;
+    emulator.set_debug_layers(layers);
+}
+
+fn list_cheats(emulator: &Emulator) {
+    for (index, cheat) in emulator.cheats().iter().enumerate() {
+        let state = if cheat.enabled { "on" } else { "off" };
+        println!("{:x}: {} {}", index, cheat.code, state);
+    }
+}
+
+fn peek(emulator: &Emulator, addr: u16, len: u16) {
+    let bytes: Vec<u8> = (0..len).map(|offset| emulator.peek(addr.wrapping_add(offset))).collect();
+
+    for (row, chunk) in bytes.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+        println!("{:04x}: {}", addr.wrapping_add(row as u16 * 16), hex.join(" "));
+    }
+}
+
+fn parse(number: &str) -> Result<u16, String> {
+    let digits = number.trim_start_matches("0x").trim_start_matches('$');
+    u16::from_str_radix(digits, 16).map_err(|_| format!("bad number {}", number))
+}
+
+// a label from the symbol file or a number, a label in banked memory is used as is, whatever bank is mapped
+fn parse_addr(emulator: &Emulator, addr: &str) -> Result<u16, String> {
+    match emulator.symbols().lookup(addr) {
+        Some(symbol) => Ok(symbol.adder),
+        None => parse(addr),
+    }
+}
+
+fn parse_byte(number: &str) -> Result<u8, String> {
+    match parse(number)? {
+        value if value <= 0xFF => Ok(value as u8),
+        _ => Err(format!("{} doesn't fit in a byte", number)),
+    }
+}
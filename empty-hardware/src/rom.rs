@@ -0,0 +1,163 @@
+use flate2::read::GzDecoder;
+use gameboy_core::emulator::check_header;
+use gameboy_core::patch;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+
+const ROM_EXTENSIONS: [&str; 3] = ["gb", "gbc", "sgb"];
+const ARCHIVE_EXTENSIONS: [&str; 3] = ["zip", "gz", "7z"];
+
+// the start of the nintendo logo every cartridge header holds at 0x104
+const HEADER_LOGO: [u8; 8] = [0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B];
+const HEADER_LOGO_ADDER: usize = 0x104;
+
+// the largest cartridge is 8mb, anything bigger in an archive isn't a rom
+const MAX_ROM_SIZE: u64 = 8 * 1024 * 1024;
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const GZ_MAGIC: &[u8] = &[0x1F, 0x8B];
+const SEVEN_ZIP_MAGIC: &[u8] = &[b'7', b'z', 0xBC, 0xAF, 0x27, 0x1C];
+
+fn has_extension(name: &str, extensions: &[&str]) -> bool {
+    Path::new(name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extensions.iter().any(|known| extension.eq_ignore_ascii_case(known)))
+}
+
+fn has_header(data: &[u8]) -> bool {
+    data.get(HEADER_LOGO_ADDER..HEADER_LOGO_ADDER + HEADER_LOGO.len()) == Some(&HEADER_LOGO[..])
+}
+
+// the first file named like a rom, or else the first one with a cartridge header
+fn pick_rom(files: Vec<(String, Vec<u8>)>) -> Option<Vec<u8>> {
+    let by_name = files.iter().position(|(name, _)| has_extension(name, &ROM_EXTENSIONS));
+    let index = by_name.or_else(|| files.iter().position(|(_, data)| has_header(data)))?;
+
+    files.into_iter().nth(index).map(|(_, data)| data)
+}
+
+fn read_zip(archive: &[u8]) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut zip = zip::ZipArchive::new(Cursor::new(archive)).map_err(|e| e.to_string())?;
+    let mut files = Vec::new();
+
+    for index in 0..zip.len() {
+        let mut file = zip.by_index(index).map_err(|e| e.to_string())?;
+        if file.is_dir() || file.size() > MAX_ROM_SIZE {
+            continue;
+        }
+
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).map_err(|e| e.to_string())?;
+        files.push((file.name().to_string(), data));
+    }
+
+    Ok(files)
+}
+
+fn read_seven_zip(archive: &[u8]) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut reader = sevenz_rust::SevenZReader::new(Cursor::new(archive), archive.len() as u64, sevenz_rust::Password::empty())
+        .map_err(|e| e.to_string())?;
+    let mut files = Vec::new();
+
+    reader
+        .for_each_entries(|entry, data| {
+            // the entries of a solid archive share one stream, so every entry has to be read through
+            let mut bytes = Vec::new();
+            data.read_to_end(&mut bytes)?;
+
+            if !entry.is_directory && entry.size <= MAX_ROM_SIZE {
+                files.push((entry.name.clone(), bytes));
+            }
+
+            Ok(true)
+        })
+        .map_err(|e| e.to_string())?;
+
+    Ok(files)
+}
+
+// a gzip file holds a single file, its name is in the archive name. the size in the trailer
+// can't be trusted, so at most one byte more than a rom can hold is unpacked
+fn read_gz(archive: &[u8]) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+    GzDecoder::new(archive)
+        .take(MAX_ROM_SIZE + 1)
+        .read_to_end(&mut data)
+        .map_err(|e| e.to_string())?;
+
+    if data.len() as u64 > MAX_ROM_SIZE {
+        return Err(format!("it unpacks to more than the {}mb a rom can hold", MAX_ROM_SIZE / (1024 * 1024)));
+    }
+
+    Ok(data)
+}
+
+// roms can be given as they are or packed in a zip, gzip or 7z archive, archives are told apart by their magic
+fn read_rom(rom_path: &str) -> Result<Vec<u8>, String> {
+    let file = std::fs::read(rom_path).map_err(|e| format!("Failed to read rom {}: {}", rom_path, e))?;
+    let archive_error = |e: String| format!("Failed to read archive {}: {}", rom_path, e);
+    let no_rom = || format!("No game boy rom found in {}", rom_path);
+
+    if file.starts_with(ZIP_MAGIC) {
+        pick_rom(read_zip(&file).map_err(archive_error)?).ok_or_else(no_rom)
+    } else if file.starts_with(SEVEN_ZIP_MAGIC) {
+        pick_rom(read_seven_zip(&file).map_err(archive_error)?).ok_or_else(no_rom)
+    } else if file.starts_with(GZ_MAGIC) {
+        read_gz(&file).map_err(archive_error)
+    } else {
+        Ok(file)
+    }
+}
+
+// a patch with the rom's name is applied without being asked for, game.gb picks up game.ips
+const PATCH_EXTENSIONS: [&str; 3] = ["ips", "bps", "ups"];
+
+// the rom path files that go with the rom are named after, game.gb.gz and game.zip go with game.ips as well
+pub fn base_path(rom_path: &str) -> PathBuf {
+    let mut base = Path::new(rom_path).to_path_buf();
+    if has_extension(rom_path, &ARCHIVE_EXTENSIONS) {
+        base.set_extension("");
+    }
+
+    base
+}
+
+fn find_patch(rom_path: &str) -> Option<String> {
+    let base = base_path(rom_path);
+
+    PATCH_EXTENSIONS
+        .iter()
+        .map(|extension| base.with_extension(extension))
+        .find(|path| path.is_file())
+        .map(|path| path.to_string_lossy().into_owned())
+}
+
+// reads the rom (out of its archive if it is packed) and applies the given patch, or the one next to the rom when none is given.
+// a patch that changes the header has to fix the checksum as well or the boot rom locks up, so a
+// patched rom with a bad header is refused in strict mode and warned about otherwise
+pub fn load(rom_path: &str, patch_path: Option<&str>, strict_checksum: bool) -> Result<Vec<u8>, String> {
+    let rom = read_rom(rom_path)?;
+
+    let patch_path = match patch_path {
+        Some(path) => path.to_string(),
+        None => match find_patch(rom_path) {
+            Some(path) => path,
+            None => return Ok(rom),
+        },
+    };
+
+    let patch = std::fs::read(&patch_path).map_err(|e| format!("Failed to read patch {}: {}", patch_path, e))?;
+    let rom = patch::apply(&rom, &patch).map_err(|e| format!("Failed to patch {} with {}: {}", rom_path, patch_path, e))?;
+
+    println!("Applied patch {}", patch_path);
+
+    if let Err(e) = check_header(&rom, false) {
+        if strict_checksum {
+            return Err(format!("The rom patched with {} has a bad header, {}", patch_path, e));
+        }
+        eprintln!("Warning: the patched rom has a bad header, {}", e);
+    }
+
+    Ok(rom)
+}
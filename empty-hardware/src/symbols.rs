@@ -0,0 +1,25 @@
+use crate::rom;
+use gameboy_core::emulator::Emulator;
+use gameboy_core::symbols::SymbolTable;
+
+// loads the given symbol file, or the one next to the rom when none is given, game.gb picks up game.sym
+pub fn load(emulator: &mut Emulator, rom_path: &str, symbols_path: Option<&str>) -> Result<(), String> {
+    let path = match symbols_path {
+        Some(path) => path.to_string(),
+        None => {
+            let path = rom::base_path(rom_path).with_extension("sym");
+            if !path.is_file() {
+                return Ok(());
+            }
+            path.to_string_lossy().into_owned()
+        }
+    };
+
+    let text = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read symbols {}: {}", path, e))?;
+    let symbols = SymbolTable::parse(&text).map_err(|e| format!("Failed to load symbols {}: {}", path, e))?;
+
+    println!("Loaded {} symbols from {}", symbols.len(), path);
+    emulator.set_symbols(symbols);
+
+    Ok(())
+}
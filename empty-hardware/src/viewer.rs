@@ -0,0 +1,105 @@
+use gameboy_core::emulator::{DebugImage, Emulator};
+use gameboy_core::hardware as gameboy_hw;
+use minifb::{Scale, Window, WindowOptions};
+
+// every vram view with the name its window and file get
+pub type Views = Vec<(&'static str, DebugImage)>;
+
+pub fn views(emulator: &Emulator) -> Views {
+    vec![
+        ("tiles", emulator.vram_tiles()),
+        ("map0", emulator.tile_map(0)),
+        ("map1", emulator.tile_map(1)),
+        ("oam", emulator.oam_image()),
+        ("palettes", emulator.palettes()),
+    ]
+}
+
+pub fn frame_image(emulator: &Emulator) -> DebugImage {
+    DebugImage {
+        width: gameboy_hw::DISPLAY_WIDTH,
+        height: gameboy_hw::DISPLAY_HIGHT,
+        pixels: emulator.frame_buffer().to_vec(),
+    }
+}
+
+pub fn save_png(path: &str, image: &DebugImage) -> Result<(), png::EncodingError> {
+    let file = std::fs::File::create(path)?;
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), image.width as u32, image.height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    encoder.write_header()?.write_image_data(&image.to_rgba())
+}
+
+// writes every view next to each other as <prefix>-<view>.png
+pub fn dump(emulator: &Emulator, prefix: &str) {
+    for (name, image) in views(emulator) {
+        let path = format!("{}-{}.png", prefix, name);
+        if let Err(e) = save_png(&path, &image) {
+            eprintln!("Failed to save {}: {}", path, e);
+        }
+    }
+}
+
+// small images are scaled up more so every window ends up about the same size
+fn scale(image: &DebugImage) -> Scale {
+    if image.width * image.height > 128 * 128 {
+        Scale::X2
+    } else {
+        Scale::X4
+    }
+}
+
+// the secondary windows, they are opened with the first views that arrive
+pub struct ViewerWindows {
+    windows: Vec<Window>,
+}
+
+impl ViewerWindows {
+    pub fn new() -> ViewerWindows {
+        ViewerWindows { windows: Vec::new() }
+    }
+
+    pub fn close(&mut self) {
+        self.windows.clear();
+    }
+
+    // returns false once the user closed one of the windows
+    pub fn update(&mut self, views: Option<Views>) -> bool {
+        match views {
+            Some(views) => {
+                if self.windows.is_empty() {
+                    for (name, image) in views.iter() {
+                        let window = Window::new(name, image.width, image.height, WindowOptions {
+                            resize: false,
+                            scale: scale(image),
+                            ..WindowOptions::default()
+                        });
+
+                        match window {
+                            Ok(window) => self.windows.push(window),
+                            Err(e) => {
+                                eprintln!("Failed to open the {} viewer: {}", name, e);
+                                self.close();
+                                return false;
+                            }
+                        }
+                    }
+                }
+
+                for (window, (_, image)) in self.windows.iter_mut().zip(views.iter()) {
+                    let _ = window.update_with_buffer(&image.pixels);
+                }
+            }
+            None => {
+                // nothing new to show, keep the windows responsive
+                for window in self.windows.iter_mut() {
+                    window.update();
+                }
+            }
+        }
+
+        self.windows.iter().all(|window| window.is_open())
+    }
+}
@@ -0,0 +1,118 @@
+// frames per second benchmark, runs the emulator unthrottled for a fixed amount of frames.
+//
+// cargo bench --bench fps
+//
+// GB_BENCH_ROM can point at a rom to use instead of the built in reference rom,
+// GB_BENCH_FRAMES sets how many frames to run.
+
+use gameboy_core::emulator::{Emulator, EmulatorConfig, GameBoyMode};
+use gameboy_core::hardware::{Hardware, Key, PROCESSOR_CLOCK_SPEED};
+use std::time::{Duration, Instant};
+
+const DEFAULT_FRAMES: usize = 600;
+
+// a copy loop that hits rom, wram, vram, io and hram every iteration
+const REFERENCE_PROGRAM: [u8; 42] = [
+    0x31, 0xFE, 0xFF, // ld sp, $fffe
+    0x21, 0x00, 0x00, // loop: ld hl, $0000
+    0x11, 0x00, 0xC0, // ld de, $c000
+    0x06, 0x00, // ld b, 0
+    0x2A, 0x12, 0x13, 0x05, 0x20, 0xFA, // ld a, (hl+); ld (de), a; inc de; dec b; jr nz
+    0x21, 0x00, 0xC0, // ld hl, $c000
+    0x11, 0x00, 0x80, // ld de, $8000
+    0x06, 0x00, // ld b, 0
+    0x2A, 0x12, 0x13, 0x05, 0x20, 0xFA, // ld a, (hl+); ld (de), a; inc de; dec b; jr nz
+    0xF0, 0x44, // ldh a, ($44)
+    0xE0, 0x80, // ldh ($80), a
+    0x21, 0x80, 0xFF, // ld hl, $ff80
+    0x34, // inc (hl)
+    0xC3, 0x53, 0x01, // jp loop
+];
+
+const LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
+fn reference_rom() -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+
+    // nop; jp $0150
+    rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+    rom[0x104..0x134].copy_from_slice(&LOGO);
+    rom[0x134..0x13D].copy_from_slice(b"FPS BENCH");
+
+    let mut checksum = 0u8;
+    for byte in &rom[0x134..0x14D] {
+        checksum = checksum.wrapping_sub(*byte).wrapping_sub(1);
+    }
+    rom[0x14D] = checksum;
+
+    rom[0x150..0x150 + REFERENCE_PROGRAM.len()].copy_from_slice(&REFERENCE_PROGRAM);
+
+    rom
+}
+
+// frames are pulled with run_frame so the hardware has nothing to do
+struct BenchHardware;
+
+impl Hardware for BenchHardware {
+    fn draw_line(&mut self, _line: usize, _buffer: &[u32]) {}
+
+    fn joypad_pressed(&mut self, _key: Key) -> bool {
+        false
+    }
+
+    fn clock(&mut self) -> Duration {
+        Duration::from_secs(0)
+    }
+
+    fn pcm_sample_rate(&mut self) -> u32 {
+        44100
+    }
+
+    fn next_pcm_amplitude(&mut self, _amplitude: f32) {}
+
+    fn run(&mut self) -> bool {
+        true
+    }
+}
+
+fn main() {
+    let rom = match std::env::var("GB_BENCH_ROM") {
+        Ok(path) => std::fs::read(&path).expect("couldn't read GB_BENCH_ROM"),
+        Err(_) => reference_rom(),
+    };
+
+    let target_frames = std::env::var("GB_BENCH_FRAMES")
+        .ok()
+        .and_then(|frames| frames.parse().ok())
+        .unwrap_or(DEFAULT_FRAMES);
+
+    let config = EmulatorConfig {
+        game_boy_mode: GameBoyMode::Classic,
+        boot_rom: None,
+        allow_bad_checksum: true,
+        native_speed: true,
+        cpu_speed: PROCESSOR_CLOCK_SPEED,
+        rewind: None,
+        block_opposite_directions: false,
+        audio: false,
+    };
+
+    let mut emulator = Emulator::new(rom, Box::new(BenchHardware), config).expect("couldn't load rom");
+
+    let start = Instant::now();
+    for _ in 0..target_frames {
+        emulator.run_frame();
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "{} frames in {:.3}s, {:.1} fps",
+        target_frames,
+        elapsed.as_secs_f64(),
+        target_frames as f64 / elapsed.as_secs_f64()
+    );
+}
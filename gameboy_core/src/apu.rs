@@ -3,10 +3,9 @@ use crate::apu::tone::Tone;
 use crate::apu::wave::Wave;
 use crate::hardware::Hardware;
 use crate::mmu::IoDevice;
-use crate::mmu::{MemRead, MemWrite, Mmu};
+use crate::mmu::{MemRead, MemWrite};
 use crate::processor::TCycles;
-use std::cell::RefCell;
-use std::rc::Rc;
+use crate::state::{SaveState, StateReader, StateWriter};
 
 mod frame_sequencer;
 mod noise;
@@ -79,37 +78,41 @@ impl SoundDirectionRegister {
 }
 
 pub struct Apu {
-    soundDirection: SoundDirectionRegister,
-    chanelControl: ChanelControlRegister,
+    sound_direction: SoundDirectionRegister,
+    chanel_control: ChanelControlRegister,
     tone1: Tone,
     tone2: Tone,
     wave: Wave,
     noise: Noise,
 
     is_sound_enabled: bool,
+    muted: bool, // the channels keep running but no samples are handed to the hardware
 
     emulator_cycle_frequency: u32,
-    hardware: Rc<RefCell<Box<dyn Hardware>>>,
     clock: TCycles,
 }
 
 impl Apu {
-    pub fn new(hardware: Rc<RefCell<Box<dyn Hardware>>>, emulator_cycle_frequency: u32) -> Self {
+    pub fn new(emulator_cycle_frequency: u32) -> Self {
         Self {
-            soundDirection: SoundDirectionRegister::default(),
-            chanelControl: ChanelControlRegister::default(),
+            sound_direction: SoundDirectionRegister::default(),
+            chanel_control: ChanelControlRegister::default(),
             tone1: Tone::new(true),
             tone2: Tone::new(false),
             wave: Wave::new(),
             noise: Noise::new(),
             is_sound_enabled: false,
+            muted: false,
             emulator_cycle_frequency,
-            hardware,
             clock: 0,
         }
     }
 
-    pub fn cycle(&mut self, clocks: TCycles) {
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    pub fn cycle(&mut self, clocks: TCycles, hardware: &mut dyn Hardware) {
         if !self.is_sound_enabled {
             return;
         }
@@ -117,44 +120,49 @@ impl Apu {
         self.clock += clocks;
 
         let highest_volume = self
-            .chanelControl
+            .chanel_control
             .right_level
-            .max(self.chanelControl.left_level) as u16;
+            .max(self.chanel_control.left_level) as u16;
 
         let mut amplitude = 0;
 
         amplitude += self.tone1.cycle(clocks)
             * highest_volume
-            * (self.soundDirection.tone1_left || self.soundDirection.tone1_right) as u16;
+            * (self.sound_direction.tone1_left || self.sound_direction.tone1_right) as u16;
         amplitude += self.tone2.cycle(clocks)
             * highest_volume
-            * (self.soundDirection.tone2_left || self.soundDirection.tone2_right) as u16;
+            * (self.sound_direction.tone2_left || self.sound_direction.tone2_right) as u16;
         amplitude += self.wave.cycle(clocks)
             * highest_volume
-            * (self.soundDirection.wave_left || self.soundDirection.wave_right) as u16;
+            * (self.sound_direction.wave_left || self.sound_direction.wave_right) as u16;
         amplitude += self.noise.cycle(clocks)
             * highest_volume
-            * (self.soundDirection.noise_left || self.soundDirection.noise_right) as u16;
+            * (self.sound_direction.noise_left || self.sound_direction.noise_right) as u16;
 
 
-        let tcycles_to_output_rate = self.emulator_cycle_frequency / self.hardware.borrow_mut().pcm_sample_rate();
+        let tcycles_to_output_rate = self.emulator_cycle_frequency / hardware.pcm_sample_rate();
 
         if self.clock >= tcycles_to_output_rate {
             self.clock -= tcycles_to_output_rate;
-            self.hardware.borrow_mut().next_pcm_amplitude((amplitude as u64 / 840) as f32 / 100.0);
+
+            if self.muted {
+                return;
+            }
+
+            hardware.next_pcm_amplitude((amplitude as u64 / 840) as f32 / 100.0);
         }
     }
 }
 
 impl IoDevice for Apu {
-    fn read_byte(&mut self, _: &Mmu, addr: u16) -> MemRead {
+    fn peek_byte(&self, addr: u16) -> MemRead {
         match addr {
             0xFF10..=0xFF14 => MemRead::Read(self.tone1.read_byte(0xFF10, addr)),
             0xFF15..=0xFF19 => MemRead::Read(self.tone2.read_byte(0xFF15, addr)),
             0xFF1A..=0xFF1E => MemRead::Read(self.wave.read_byte(0xFF1A, addr)),
             0xFF1F..=0xFF23 => MemRead::Read(self.noise.read_byte(0xFF1F, addr)),
-            0xFF24 => MemRead::Read(self.chanelControl.read()),
-            0xFF25 => MemRead::Read(self.soundDirection.read()),
+            0xFF24 => MemRead::Read(self.chanel_control.read()),
+            0xFF25 => MemRead::Read(self.sound_direction.read()),
             0xFF26 => MemRead::Read(
                 (if self.is_sound_enabled { 0x80 } else { 0x0 })
                     | (if self.tone1.channel_enabled { 0x1 } else { 0x0 })
@@ -166,7 +174,7 @@ impl IoDevice for Apu {
             _ => MemRead::Ignore,
         }
     }
-    fn write_byte(&mut self, _: &Mmu, addr: u16, val: u8) -> MemWrite {
+    fn write_byte(&mut self, addr: u16, val: u8) -> MemWrite {
         match addr {
             0xFF10..=0xFF14 => {
                 self.tone1.write_byte(0xFF10, addr, val);
@@ -185,11 +193,11 @@ impl IoDevice for Apu {
                 MemWrite::Write
             }
             0xFF24 => {
-                self.chanelControl.write(val);
+                self.chanel_control.write(val);
                 MemWrite::Write
             }
             0xFF25 => {
-                self.soundDirection.write(val);
+                self.sound_direction.write(val);
                 MemWrite::Write
             }
             0xFF26 => {
@@ -205,3 +213,38 @@ impl IoDevice for Apu {
         }
     }
 }
+
+impl SaveState for Apu {
+    fn save_state(&self, state: &mut StateWriter) {
+        // the channel control register doesn't read back everything it was written so its fields are saved as is
+        state.write_bool(self.chanel_control.left_vin);
+        state.write_bool(self.chanel_control.right_vin);
+        state.write_u8(self.chanel_control.left_level);
+        state.write_u8(self.chanel_control.right_level);
+        state.write_u8(self.sound_direction.read());
+
+        self.tone1.save_state(state);
+        self.tone2.save_state(state);
+        self.wave.save_state(state);
+        self.noise.save_state(state);
+
+        state.write_bool(self.is_sound_enabled);
+        state.write_u32(self.clock);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) {
+        self.chanel_control.left_vin = state.read_bool();
+        self.chanel_control.right_vin = state.read_bool();
+        self.chanel_control.left_level = state.read_u8();
+        self.chanel_control.right_level = state.read_u8();
+        self.sound_direction.write(state.read_u8());
+
+        self.tone1.load_state(state);
+        self.tone2.load_state(state);
+        self.wave.load_state(state);
+        self.noise.load_state(state);
+
+        self.is_sound_enabled = state.read_bool();
+        self.clock = state.read_u32();
+    }
+}
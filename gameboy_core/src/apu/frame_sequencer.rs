@@ -1,5 +1,6 @@
 use crate::processor::T_CYCLE_FREQUENCY;
 use crate::processor::TCycles;
+use crate::state::{SaveState, StateReader, StateWriter};
 
 const FRAME_FREQUENCY: u32 = 512;
 const FRAME_TO_CYCLE_RATIO: u32 = T_CYCLE_FREQUENCY / FRAME_FREQUENCY;
@@ -37,4 +38,16 @@ impl FrameSequencer {
         self.clock = 0;
         self.current_cycle = 0;
     }
-}
\ No newline at end of file
+}
+
+impl SaveState for FrameSequencer {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u32(self.clock);
+        state.write_u32(self.current_cycle);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) {
+        self.clock = state.read_u32();
+        self.current_cycle = state.read_u32();
+    }
+}
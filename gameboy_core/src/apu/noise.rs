@@ -2,6 +2,7 @@ use crate::apu::frame_sequencer::FrameSequencer;
 use crate::apu::sound_length::SoundLength;
 use crate::apu::volume::Volume;
 use crate::processor::TCycles;
+use crate::state::{SaveState, StateReader, StateWriter};
 use crate::processor::T_CYCLE_FREQUENCY;
 
 const MAX_SOUND_LEN: u8 = 64;
@@ -55,7 +56,6 @@ impl Noise {
         match addr - base_addr {
             0x00 => {
                 // unmapped
-                return;
             },
             0x01 => {
                 self.sound_length.set_length(val & 0x1F);
@@ -69,12 +69,12 @@ impl Noise {
             },
             0x04 => {
                 self.sound_length.dec_sound_len = (val & 0x40) != 0;
-                if (val & 0x80) != 1 {
+                if (val & 0x80) != 0 {
                     self.enable_channel();
                 }
             }
             _ => unreachable!("Bad addr"),
-        };
+        }
     }
 
     fn get_t_cycle_ratio(&self) -> u32 {
@@ -116,18 +116,54 @@ impl Noise {
             self.clock -= self.get_t_cycle_ratio();
 
             self.last_lower_bit = self.shift_register & 0x1;
+            self.shift_lfsr();
+        }
+
+        self.last_lower_bit * self.volume.volume as u16
+    }
 
-            let xor_bit = (self.shift_register & 0x0) ^ ((self.shift_register & 0x1) >> 1);
+    // a 15 bit lfsr, or 7 bits in width mode. bits 0 and 1 are xored and the result is shifted in at the top
+    fn shift_lfsr(&mut self) {
+        let xor_bit = (self.shift_register & 0x1) ^ ((self.shift_register & 0x2) >> 1);
 
-            self.shift_register >>= 1;
+        self.shift_register >>= 1;
 
-            self.shift_register = (self.shift_register & !(1 << 15)) | (xor_bit << 15);
+        self.shift_register = (self.shift_register & !(1 << 14)) | (xor_bit << 14);
 
-            if self.width_mode {
-                self.shift_register = (self.shift_register & !(1 << 7)) | (xor_bit << 7)
-            }
+        if self.width_mode {
+            self.shift_register = (self.shift_register & !(1 << 6)) | (xor_bit << 6)
         }
+    }
+}
 
-        self.last_lower_bit * self.volume.volume as u16
+impl SaveState for Noise {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.channel_enabled);
+        state.write_u16(self.last_lower_bit);
+
+        state.write_u8(self.frequency);
+        state.write_bool(self.width_mode);
+        state.write_u8(self.frequency_divider);
+        state.write_u16(self.shift_register);
+        state.write_u32(self.clock);
+
+        self.volume.save_state(state);
+        self.sound_length.save_state(state);
+        self.frame_sequencer.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) {
+        self.channel_enabled = state.read_bool();
+        self.last_lower_bit = state.read_u16();
+
+        self.frequency = state.read_u8();
+        self.width_mode = state.read_bool();
+        self.frequency_divider = state.read_u8();
+        self.shift_register = state.read_u16();
+        self.clock = state.read_u32();
+
+        self.volume.load_state(state);
+        self.sound_length.load_state(state);
+        self.frame_sequencer.load_state(state);
     }
 }
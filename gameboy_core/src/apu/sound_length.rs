@@ -1,3 +1,5 @@
+use crate::state::{SaveState, StateReader, StateWriter};
+
 pub struct SoundLength {
     sound_len: u8,
     pub dec_sound_len: bool,
@@ -34,4 +36,16 @@ impl SoundLength {
     pub fn set_length(&mut self, val: u8) {
         self.sound_len = val % self.max_len;
     }
-}
\ No newline at end of file
+}
+
+impl SaveState for SoundLength {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.sound_len);
+        state.write_bool(self.dec_sound_len);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) {
+        self.sound_len = state.read_u8();
+        self.dec_sound_len = state.read_bool();
+    }
+}
@@ -2,6 +2,7 @@ use crate::apu::sound_length::SoundLength;
 use crate::apu::volume::Volume;
 use crate::apu::frame_sequencer::FrameSequencer;
 use crate::processor::{TCycles, T_CYCLE_FREQUENCY};
+use crate::state::{SaveState, StateReader, StateWriter};
 use crate::utils;
 
 const BASE_SWEEP_FREQUENCY: u32 = 128;
@@ -185,3 +186,45 @@ impl Tone {
         WAVE_PATTERN[self.selected_wave_pattern as usize][self.currant_wave_cycle] * self.volume.volume as u16
     }
 }
+
+impl SaveState for Tone {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u32(self.clock);
+        state.write_bool(self.channel_enabled);
+
+        state.write_u8(self.currant_sweep_cycle);
+        state.write_u8(self.sweep_time);
+        state.write_bool(self.sweep_increase);
+        state.write_u8(self.sweep_shift);
+
+        state.write_u8(self.selected_wave_pattern);
+        state.write_usize(self.currant_wave_cycle);
+        state.write_u16(self.frequency);
+
+        self.volume.save_state(state);
+        self.sound_length.save_state(state);
+        self.frame_sequencer.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) {
+        self.clock = state.read_u32();
+        self.channel_enabled = state.read_bool();
+
+        self.currant_sweep_cycle = state.read_u8();
+        self.sweep_time = state.read_u8();
+        self.sweep_increase = state.read_bool();
+        self.sweep_shift = state.read_u8();
+
+        self.selected_wave_pattern = state.read_u8();
+        if self.selected_wave_pattern as usize >= WAVE_TYPES {
+            state.reject("wave pattern out of range");
+            self.selected_wave_pattern = 0;
+        }
+        self.currant_wave_cycle = state.read_index(WAVE_STATES, "wave cycle out of range");
+        self.frequency = state.read_u16();
+
+        self.volume.load_state(state);
+        self.sound_length.load_state(state);
+        self.frame_sequencer.load_state(state);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::state::{SaveState, StateReader, StateWriter};
 
 pub struct Volume {
     pub initial_volume: u8,
@@ -51,4 +52,20 @@ impl Volume {
     pub fn reset(&mut self) {
         self.volume = self.initial_volume;
     }
-}
\ No newline at end of file
+}
+
+impl SaveState for Volume {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.initial_volume);
+        state.write_u8(self.volume);
+        state.write_bool(self.vol_envelope_increase);
+        state.write_u8(self.envelope_counter);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) {
+        self.initial_volume = state.read_u8();
+        self.volume = state.read_u8();
+        self.vol_envelope_increase = state.read_bool();
+        self.envelope_counter = state.read_u8();
+    }
+}
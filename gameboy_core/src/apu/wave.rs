@@ -3,6 +3,7 @@ use crate::processor::T_CYCLE_FREQUENCY;
 use crate::apu::frame_sequencer::FrameSequencer;
 use crate::apu::sound_length::SoundLength;
 use crate::processor::TCycles;
+use crate::state::{SaveState, StateReader, StateWriter};
 use crate::utils;
 
 const WAVE_PATTERN_RAM_SIZE: usize = 32;
@@ -68,7 +69,7 @@ impl Wave {
             0x04 => {
                 self.frequency = utils::build_u16(val & 0x3, utils::get_u16_low(self.frequency));
                 self.sound_length.dec_sound_len = (val & 0x40) != 0;
-                if (val & 0x80) != 1 {
+                if (val & 0x80) != 0 {
                     self.enable_channel();
                 }
             },
@@ -99,10 +100,8 @@ impl Wave {
 
         self.clock += clocks;
 
-        if self.frame_sequencer.cycle(clocks) {
-            if self.frame_sequencer.current_cycle % 2 == 0 {
-                self.sound_length.cycle(&mut self.channel_enabled);
-            }
+        if self.frame_sequencer.cycle(clocks) && self.frame_sequencer.current_cycle % 2 == 0 {
+            self.sound_length.cycle(&mut self.channel_enabled);
         }
 
         if self.clock >= self.get_t_cycle_ratio() {
@@ -128,4 +127,32 @@ impl Wave {
         }
         
     }
-}
\ No newline at end of file
+}
+
+impl SaveState for Wave {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.channel_enabled);
+        state.write_u16(self.frequency);
+        state.write_u8(self.volume);
+        state.write_usize(self.wave_index);
+        state.write_u32(self.clock);
+
+        self.sound_length.save_state(state);
+        self.frame_sequencer.save_state(state);
+
+        state.write_bytes(&self.wave_pattern_ram);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) {
+        self.channel_enabled = state.read_bool();
+        self.frequency = state.read_u16();
+        self.volume = state.read_u8();
+        self.wave_index = state.read_index(WAVE_PATTERN_RAM_SIZE, "wave index out of range");
+        self.clock = state.read_u32();
+
+        self.sound_length.load_state(state);
+        self.frame_sequencer.load_state(state);
+
+        state.read_bytes(&mut self.wave_pattern_ram);
+    }
+}
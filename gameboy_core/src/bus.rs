@@ -0,0 +1,104 @@
+use crate::apu::Apu;
+use crate::cartridge_controller::CartridgeController;
+use crate::ic::Ic;
+use crate::joypad::Joypad;
+use crate::mmu::IoDevice;
+use crate::ppu::dma::DmaManager;
+use crate::ppu::Ppu;
+use crate::ram::Ram;
+use crate::serial::Serial;
+use crate::state::{SaveState, StateReader, StateWriter};
+use crate::timer::Timer;
+
+// the bus components that can have io registers mapped to them. the components are matched statically
+// so the hot io path doesn't go through a vtable, anything else plugs in as a boxed device
+#[derive(Clone, Copy)]
+pub enum IoTarget {
+    Cartridge,
+    Ppu,
+    Dma,
+    Apu,
+    Ic,
+    Joypad,
+    Timer,
+    Serial,
+    Device(usize), // an index into Bus::devices
+}
+
+// the system bus owns every component that lives behind the address space,
+// the emulator hands out borrows of the parts it needs instead of sharing them
+pub struct Bus {
+    pub cartridge_controller: CartridgeController,
+    pub ppu: Ppu,
+    pub apu: Apu,
+    pub timer: Timer,
+    pub ic: Ic,
+    pub ram: Ram,
+    pub joypad: Joypad,
+    pub dma_manager: DmaManager,
+    pub serial: Serial,
+
+    // devices plugged in from outside, they aren't cycled and aren't part of a snapshot
+    pub devices: Vec<Box<dyn IoDevice + Send>>,
+}
+
+impl Bus {
+    pub fn io_device(&mut self, target: IoTarget) -> &mut dyn IoDevice {
+        match target {
+            IoTarget::Cartridge => &mut self.cartridge_controller,
+            IoTarget::Ppu => &mut self.ppu,
+            IoTarget::Dma => &mut self.dma_manager,
+            IoTarget::Apu => &mut self.apu,
+            IoTarget::Ic => &mut self.ic,
+            IoTarget::Joypad => &mut self.joypad,
+            IoTarget::Timer => &mut self.timer,
+            IoTarget::Serial => &mut self.serial,
+            IoTarget::Device(index) => self.devices[index].as_mut(),
+        }
+    }
+
+    pub fn io_device_ref(&self, target: IoTarget) -> &dyn IoDevice {
+        match target {
+            IoTarget::Cartridge => &self.cartridge_controller,
+            IoTarget::Ppu => &self.ppu,
+            IoTarget::Dma => &self.dma_manager,
+            IoTarget::Apu => &self.apu,
+            IoTarget::Ic => &self.ic,
+            IoTarget::Joypad => &self.joypad,
+            IoTarget::Timer => &self.timer,
+            IoTarget::Serial => &self.serial,
+            IoTarget::Device(index) => self.devices[index].as_ref(),
+        }
+    }
+
+    pub fn add_device(&mut self, device: Box<dyn IoDevice + Send>) -> IoTarget {
+        self.devices.push(device);
+        IoTarget::Device(self.devices.len() - 1)
+    }
+}
+
+impl SaveState for Bus {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.cartridge_controller.save_state(state);
+        self.ppu.save_state(state);
+        self.apu.save_state(state);
+        self.timer.save_state(state);
+        self.ic.save_state(state);
+        self.ram.save_state(state);
+        self.joypad.save_state(state);
+        self.dma_manager.save_state(state);
+        self.serial.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) {
+        self.cartridge_controller.load_state(state);
+        self.ppu.load_state(state);
+        self.apu.load_state(state);
+        self.timer.load_state(state);
+        self.ic.load_state(state);
+        self.ram.load_state(state);
+        self.joypad.load_state(state);
+        self.dma_manager.load_state(state);
+        self.serial.load_state(state);
+    }
+}
@@ -2,111 +2,238 @@ mod rom_only;
 mod mbc1;
 
 use crate::cartridge_controller::mbc1::Mbc1;
+use crate::cheats::RomPatch;
 use crate::mmu::MemWrite;
 use crate::mmu::MemRead;
-use crate::mmu::Mmu;
 use crate::cartridge_controller::rom_only::RomOnly;
-use crate::emulator::GameBoyMode;
+use crate::emulator::{EmulatorError, GameBoyMode};
 use crate::mmu::IoDevice;
+use crate::state::{SaveState, StateReader, StateWriter};
 
 const CARTRIDGE_TYPE_ADDER: usize = 0x0147;
+const ROM_SIZE_ADDER: usize = 0x0148;
+const HEADER_CHECKSUM_ADDER: usize = 0x014D;
 
+// every rom must at least hold a full cartridge header
+const HEADER_END: usize = 0x0150;
 
+const COLOR_BOOT_ROM_SIZE: usize = 0x900;
+const CLASSIC_BOOT_ROM_SIZE: usize = 0x100;
 
-const COLOR_BOOT_ROM: &[u8] = {
-    include_bytes!("boot_roms\\cgb.bin")
-};
 
-const CLASSIC_BOOT_ROM: &[u8] = {
-    include_bytes!("boot_roms\\dmg.bin")
-};
+// cartridges save their banking registers and ram, the rom itself is never part of a snapshot
+pub trait Cartridge: SaveState + Send {
+    // a read by the cpu, cartridges where reading changes state override this
+    fn read_byte(&mut self, adder: u16) -> MemRead {
+        self.peek_byte(adder)
+    }
 
+    // a read without side effects, for debuggers
+    fn peek_byte(&self, adder: u16) -> MemRead;
 
+    fn write_byte(&mut self, adder: u16, value: u8) -> MemWrite;
 
-pub trait Cartridge {
-    fn read_byte(&mut self, mmu: &Mmu, adder: u16) -> MemRead;
+    // a write straight into cartridge ram without touching the mbc, for debuggers
+    fn poke_byte(&mut self, _adder: u16, _value: u8) -> MemWrite {
+        MemWrite::Ignore
+    }
 
-    fn write_byte(&mut self, mmu: &Mmu, adder: u16, value: u8) -> MemWrite;
+    // the rom bank mapped at 0x4000 - 0x7FFF, for debuggers
+    fn rom_bank(&self) -> usize {
+        1
+    }
 }
 
 pub struct CartridgeController {
     cartridge: Box<dyn Cartridge>,
-    use_boot_rom: bool,
+    boot_rom: Option<Vec<u8>>, // unmapped once the boot rom writes to 0xff50
     game_boy_mode: GameBoyMode,
+    header_checksum: u8,
+    rom_size: usize,
+    rom_patches: Vec<RomPatch>, // enabled game genie codes, the rom itself is never changed
 }
 
 fn calc_checksum(rom: &[u8]) -> u8 {
     let mut sum = 0u8;
 
-    for i in 0x134..0x14D {
-        sum = sum.wrapping_sub(rom[i]).wrapping_sub(1);
+    for byte in &rom[0x134..HEADER_CHECKSUM_ADDER] {
+        sum = sum.wrapping_sub(*byte).wrapping_sub(1);
     }
 
     sum
 }
 
-impl CartridgeController {
-    pub fn new(rom: Vec<u8>, game_boy_mode: GameBoyMode, allow_bad_checksum: bool) -> CartridgeController {
+// rom size in bytes as declared by the header size code
+fn header_rom_size(size_code: u8) -> Option<usize> {
+    match size_code {
+        0x00 ..= 0x08 => Some(0x8000 << size_code),
+        0x52 => Some(72 * 0x4000),
+        0x53 => Some(80 * 0x4000),
+        0x54 => Some(96 * 0x4000),
+        _ => None,
+    }
+}
+
+// the checks every rom has to pass before it is loaded. a bad checksum can be allowed, the size always
+// has to be the one the header gives: a smaller rom would have the mbc read past its end and the bytes
+// past a bigger one (overdumps, roms padded by flash cart tools) aren't on the cartridge
+pub fn check_header(rom: &[u8], allow_bad_checksum: bool) -> Result<(), EmulatorError> {
+    if rom.len() < HEADER_END {
+        return Err(EmulatorError::TruncatedRom(rom.len()));
+    }
+
+    let checksum = calc_checksum(rom);
+    if checksum != rom[HEADER_CHECKSUM_ADDER] && !allow_bad_checksum {
+        return Err(EmulatorError::ChecksumMismatch {
+            expected: rom[HEADER_CHECKSUM_ADDER],
+            actual: checksum,
+        });
+    }
+
+    let size_code = rom[ROM_SIZE_ADDER];
+    if header_rom_size(size_code) != Some(rom.len()) {
+        return Err(EmulatorError::SizeMismatch {
+            size_code,
+            actual: rom.len(),
+        });
+    }
 
+    Ok(())
+}
 
-        if !calc_checksum(&rom) == rom[0x14D] && !allow_bad_checksum {
-            panic!("bad checksum");
+impl CartridgeController {
+    pub fn new(rom: Vec<u8>, boot_rom: Option<Vec<u8>>, game_boy_mode: GameBoyMode, allow_bad_checksum: bool) -> Result<CartridgeController, EmulatorError> {
+        if let Some(boot_rom) = &boot_rom {
+            let expected = match game_boy_mode {
+                GameBoyMode::Color => COLOR_BOOT_ROM_SIZE,
+                GameBoyMode::Classic => CLASSIC_BOOT_ROM_SIZE,
+            };
+
+            if boot_rom.len() != expected {
+                return Err(EmulatorError::BootRomSizeMismatch {
+                    expected,
+                    actual: boot_rom.len(),
+                });
+            }
         }
 
+        check_header(&rom, allow_bad_checksum)?;
+
+        let header_checksum = rom[HEADER_CHECKSUM_ADDER];
+        let rom_size = rom.len();
         let cartridge_type = rom[CARTRIDGE_TYPE_ADDER];
 
         let cartridge: Box<dyn Cartridge> = if RomOnly::probe_cartridge(cartridge_type) {
-            Box::new(RomOnly::new(rom.clone()))
+            Box::new(RomOnly::new(rom))
         } else if Mbc1::probe_cartridge(cartridge_type) {
-            Box::new(Mbc1::new(rom.clone()))
+            Box::new(Mbc1::new(rom))
         } else {
-            unimplemented!("unimplemented cartridge type")
+            return Err(EmulatorError::UnsupportedMapper(cartridge_type));
         };
 
-        CartridgeController {
-            cartridge: cartridge,
-            use_boot_rom: true,
-            game_boy_mode: game_boy_mode,
-        }
+        Ok(CartridgeController {
+            cartridge,
+            boot_rom,
+            game_boy_mode,
+            header_checksum,
+            rom_size,
+            rom_patches: Vec::new(),
+        })
     }
 
-    fn in_boot_rom(&self, addr: u16) -> bool {
-        match self.game_boy_mode {
-            GameBoyMode::Color => {
-                assert_eq!(0x900, COLOR_BOOT_ROM.len());
+    // the checksum byte as it is written in the header, used to tell roms apart
+    pub fn header_checksum(&self) -> u8 {
+        self.header_checksum
+    }
+
+    pub fn rom_bank(&self) -> usize {
+        self.cartridge.rom_bank()
+    }
 
-                addr < 0x100 || (addr >= 0x200 && addr < 0x900)
-            },
-            GameBoyMode::Classic => {
-                assert_eq!(0x100, CLASSIC_BOOT_ROM.len());
+    pub fn rom_size(&self) -> usize {
+        self.rom_size
+    }
+
+    // where in the rom file addr reads from right now, None while the boot rom covers it
+    pub fn rom_offset(&self, addr: u16) -> Option<usize> {
+        match addr {
+            _ if self.boot_rom.is_some() && self.in_boot_rom(addr) => None,
+            0x0000 ..= 0x3FFF => Some(addr as usize),
+            0x4000 ..= 0x7FFF => Some(self.rom_bank() * 0x4000 + (addr as usize - 0x4000)),
+            _ => None,
+        }
+    }
+
+    pub fn set_rom_patches(&mut self, rom_patches: Vec<RomPatch>) {
+        self.rom_patches = rom_patches;
+    }
 
-                addr < 0x100
+    fn patch(&self, addr: u16, read: MemRead) -> MemRead {
+        match read {
+            MemRead::Read(data) if addr < 0x8000 && !self.rom_patches.is_empty() => {
+                match self.rom_patches.iter().find(|patch| patch.matches(addr, data)) {
+                    Some(patch) => MemRead::Read(patch.value),
+                    None => read,
+                }
             }
+            _ => read,
         }
     }
 
+    // only cartridge ram can be poked, the rom and the mbc registers are left alone
+    pub fn poke_byte(&mut self, addr: u16, value: u8) -> MemWrite {
+        self.cartridge.poke_byte(addr, value)
+    }
 
+    fn in_boot_rom(&self, addr: u16) -> bool {
+        match self.game_boy_mode {
+            // the color boot rom leaves a hole for the cartridge header
+            GameBoyMode::Color => addr < 0x100 || (0x200..0x900).contains(&addr),
+            GameBoyMode::Classic => addr < 0x100,
+        }
+    }
 }
 
 impl IoDevice for CartridgeController {
-    fn read_byte(&mut self, mmu: &Mmu, addr: u16) -> MemRead { 
-        if self.use_boot_rom && self.in_boot_rom(addr) {
-            match self.game_boy_mode {
-                GameBoyMode::Classic => MemRead::Read(CLASSIC_BOOT_ROM[addr as usize]),
-                GameBoyMode::Color => MemRead::Read(COLOR_BOOT_ROM[addr as usize]),
+    fn read_byte(&mut self, addr: u16) -> MemRead {
+        match &self.boot_rom {
+            Some(boot_rom) if self.in_boot_rom(addr) => MemRead::Read(boot_rom[addr as usize]),
+            _ => {
+                let read = self.cartridge.read_byte(addr);
+                self.patch(addr, read)
             }
-        } else {
-            self.cartridge.read_byte(mmu, addr)
         }
     }
-    fn write_byte(&mut self, mmu: &Mmu, addr: u16, val: u8) -> MemWrite {
-        if self.use_boot_rom && addr < 0x100 {
-            unreachable!("Writing to boot ROM")
+    fn peek_byte(&self, addr: u16) -> MemRead {
+        match &self.boot_rom {
+            Some(boot_rom) if self.in_boot_rom(addr) => MemRead::Read(boot_rom[addr as usize]),
+            _ => self.patch(addr, self.cartridge.peek_byte(addr)),
+        }
+    }
+    fn write_byte(&mut self, addr: u16, val: u8) -> MemWrite {
+        if self.boot_rom.is_some() && addr < 0x100 {
+            // the boot rom is read only, writes are dropped
+            MemWrite::Write
         } else if addr == 0xff50 {
-            self.use_boot_rom = false;
+            self.boot_rom = None;
             MemWrite::Write
         } else {
-            self.cartridge.write_byte(mmu, addr, val)
+            self.cartridge.write_byte(addr, val)
         }
     }
-}
\ No newline at end of file
+}
+
+impl SaveState for CartridgeController {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.boot_rom.is_some());
+        self.cartridge.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) {
+        // a snapshot taken after the boot rom finished unmaps it, there is no way to map it back
+        if !state.read_bool() {
+            self.boot_rom = None;
+        }
+        self.cartridge.load_state(state);
+    }
+}
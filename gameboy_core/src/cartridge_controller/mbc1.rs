@@ -1,7 +1,7 @@
 use crate::cartridge_controller::MemWrite;
 use crate::cartridge_controller::MemRead;
-use crate::cartridge_controller::Mmu;
 use crate::cartridge_controller::Cartridge;
+use crate::state::{SaveState, StateReader, StateWriter};
 
 
 const MBC1_TYPE: u8 = 0x1;
@@ -37,28 +37,19 @@ impl Mbc1 {
 
 
 impl Cartridge for Mbc1 {
-    fn read_byte(&mut self, _mmu: &Mmu, addr: u16) -> MemRead { 
+    fn peek_byte(&self, addr: u16) -> MemRead {
         match addr {
             0x0 ..= 0x3FFF => MemRead::Read(self.rom[addr as usize]),
             0x4000 ..= 0x7FFF => {
-                let rom_bank = self.rom_bank.max(1);
-
-                let rom_bank = if rom_bank == 0x20 || rom_bank == 0x40 || rom_bank == 0x60 {
-                    rom_bank + 1
-                } else {
-                    rom_bank
-                };
-
-                let base = rom_bank * 0x4000;
+                let base = self.rom_bank() * 0x4000;
 
                 let offset = addr as usize - 0x4000;
 
-                let addr = (base + offset) & (self.rom.len() - 1);
-                MemRead::Read(self.rom[addr])
+                MemRead::Read(self.rom[base + offset])
             }
             0xA000 ..= 0xBFFF => {
                 if self.ram_enable {
-                    let base = self.ram_bank as usize * 0x2000;
+                    let base = self.ram_bank * 0x2000;
                     let offset = addr as usize - 0xa000;
                     let addr = (base + offset) & (self.rom.len() - 1);
                     MemRead::Read(self.ram[addr])
@@ -71,7 +62,20 @@ impl Cartridge for Mbc1 {
         }
     }
 
-    fn write_byte(&mut self, _mmu: &Mmu, addr: u16, val: u8) -> MemWrite {
+    fn rom_bank(&self) -> usize {
+        let rom_bank = self.rom_bank.max(1);
+
+        let rom_bank = if rom_bank == 0x20 || rom_bank == 0x40 || rom_bank == 0x60 {
+            rom_bank + 1
+        } else {
+            rom_bank
+        };
+
+        // banks past the end of the rom wrap around
+        rom_bank & (self.rom.len() / 0x4000 - 1)
+    }
+
+    fn write_byte(&mut self, addr: u16, val: u8) -> MemWrite {
         match addr {
             0x0 ..= 0x1FFF => {
                 self.ram_enable = val & 0xA == 0xA;
@@ -97,7 +101,7 @@ impl Cartridge for Mbc1 {
             }, 
             0xa000 ..= 0xbfff => {
                 if self.ram_enable {
-                    let base = self.ram_bank as usize * 0x2000;
+                    let base = self.ram_bank * 0x2000;
                     let offset = addr as usize - 0xa000;
                     self.ram[base + offset] = val;
                     MemWrite::Write
@@ -110,4 +114,36 @@ impl Cartridge for Mbc1 {
             _ => MemWrite::Ignore,
         }
     }
-}
\ No newline at end of file
+
+    // the ram bank that is mapped, even while the ram is disabled
+    fn poke_byte(&mut self, addr: u16, val: u8) -> MemWrite {
+        match addr {
+            0xa000 ..= 0xbfff => {
+                let base = self.ram_bank * 0x2000;
+                let offset = addr as usize - 0xa000;
+                self.ram[base + offset] = val;
+                MemWrite::Write
+            }
+            _ => MemWrite::Ignore,
+        }
+    }
+}
+
+impl SaveState for Mbc1 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.ram);
+        state.write_usize(self.rom_bank);
+        state.write_usize(self.ram_bank);
+        state.write_bool(self.ram_enable);
+        state.write_bool(self.ram_select);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) {
+        state.read_bytes(&mut self.ram);
+        // the bank registers are 7 and 2 bits wide
+        self.rom_bank = state.read_index(0x80, "rom bank out of range");
+        self.ram_bank = state.read_index(self.ram.len() / 0x2000, "ram bank out of range");
+        self.ram_enable = state.read_bool();
+        self.ram_select = state.read_bool();
+    }
+}
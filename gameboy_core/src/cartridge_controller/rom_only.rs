@@ -1,7 +1,7 @@
 use crate::cartridge_controller::MemWrite;
-use crate::cartridge_controller::Mmu;
 use crate::cartridge_controller::MemRead;
 use crate::cartridge_controller::Cartridge;
+use crate::state::{SaveState, StateReader, StateWriter};
 
 
 const ROM_ONLY_TYPE: u8 = 0x0;
@@ -22,20 +22,26 @@ impl RomOnly {
 }
 
 impl Cartridge for RomOnly {
-    fn read_byte(&mut self, _: &Mmu, addr: u16) -> MemRead {
+    fn peek_byte(&self, addr: u16) -> MemRead {
         if addr <= 0x7fff {
             MemRead::Read(self.rom[addr as usize])
         } else {
             MemRead::Ignore
         }
     }
-    fn write_byte(&mut self, _: &Mmu, addr: u16, val: u8) -> MemWrite {
+    fn write_byte(&mut self, addr: u16, _val: u8) -> MemWrite {
+        // there is no mbc to talk to, so writes to rom are simply dropped
         if addr <= 0x7fff {
             MemWrite::Write
-        } else if addr >= 0xa000 && addr <= 0xbfff {
-            MemWrite::Ignore
         } else {
-            unreachable!("Write to ROM: {:02x} {:02x}", addr, val);
+            MemWrite::Ignore
         }
     }
 }
+
+// there are no registers or ram on a rom only cartridge
+impl SaveState for RomOnly {
+    fn save_state(&self, _state: &mut StateWriter) {}
+
+    fn load_state(&mut self, _state: &mut StateReader) {}
+}
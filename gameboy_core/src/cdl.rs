@@ -0,0 +1,75 @@
+use crate::emulator::EmulatorError;
+
+// a code/data log, one byte of flags for every rom byte telling how the game used it. the file is
+// just those bytes without a header, in a layout of our own
+pub const OPCODE: u8 = 0x01; // fetched as the first byte of an instruction
+pub const OPERAND: u8 = 0x02; // fetched as an immediate of an instruction
+pub const DATA: u8 = 0x04; // read by an instruction
+pub const DMA: u8 = 0x08; // copied by oam, hblank or general purpose dma
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Coverage {
+    pub code: usize, // bytes fetched as opcodes or operands
+    pub data: usize, // bytes read as data or by dma
+    pub unused: usize, // bytes never touched
+}
+
+#[derive(Clone, Debug)]
+pub struct CodeDataLog {
+    flags: Vec<u8>, // indexed by rom offset
+}
+
+impl CodeDataLog {
+    pub fn new(rom_size: usize) -> CodeDataLog {
+        CodeDataLog {
+            flags: vec![0; rom_size],
+        }
+    }
+
+    // picks up a log written earlier, the new flags are added to it
+    pub fn from_bytes(bytes: &[u8], rom_size: usize) -> Result<CodeDataLog, EmulatorError> {
+        if bytes.len() != rom_size {
+            return Err(EmulatorError::InvalidCodeDataLog("it doesn't have a byte for every rom byte"));
+        }
+
+        if bytes.iter().any(|flags| flags & !(OPCODE | OPERAND | DATA | DMA) != 0) {
+            return Err(EmulatorError::InvalidCodeDataLog("unknown flags"));
+        }
+
+        Ok(CodeDataLog { flags: bytes.to_vec() })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.flags.clone()
+    }
+
+    pub fn flags(&self) -> &[u8] {
+        &self.flags
+    }
+
+    pub fn mark(&mut self, offset: usize, access: u8) {
+        if let Some(flags) = self.flags.get_mut(offset) {
+            *flags |= access;
+        }
+    }
+
+    pub fn coverage(&self) -> Coverage {
+        let mut coverage = Coverage::default();
+
+        for flags in self.flags.iter() {
+            if flags & (OPCODE | OPERAND) != 0 {
+                coverage.code += 1;
+            }
+
+            if flags & (DATA | DMA) != 0 {
+                coverage.data += 1;
+            }
+
+            if *flags == 0 {
+                coverage.unused += 1;
+            }
+        }
+
+        coverage
+    }
+}
@@ -0,0 +1,58 @@
+use crate::emulator::Emulator;
+
+// the memory a game keeps its variables in: cartridge ram, wram and hram
+const SEARCH_RANGES: [(u16, u16); 3] = [(0xA000, 0xBFFF), (0xC000, 0xDFFF), (0xFF80, 0xFFFE)];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SearchFilter {
+    // compared to the value at the last snapshot
+    Equal,
+    Changed,
+    Greater,
+    Less,
+
+    // the value is exactly this now
+    Value(u8),
+}
+
+// finds where a game keeps a variable by narrowing down addresses over a few snapshots:
+// start a search, play until the variable changes, filter, repeat until a few addresses are left
+pub struct CheatSearch {
+    candidates: Vec<(u16, u8)>, // address and its value at the last snapshot
+}
+
+impl CheatSearch {
+    // every searched address is a candidate at first
+    pub fn new(emulator: &Emulator) -> CheatSearch {
+        let candidates = SEARCH_RANGES
+            .iter()
+            .flat_map(|(start, end)| *start..=*end)
+            .map(|addr| (addr, emulator.peek(addr)))
+            .collect();
+
+        CheatSearch { candidates }
+    }
+
+    // drops every candidate that doesn't match and takes a new snapshot of the rest
+    pub fn filter(&mut self, emulator: &Emulator, filter: SearchFilter) {
+        self.candidates.retain_mut(|(addr, previous)| {
+            let value = emulator.peek(*addr);
+
+            let keep = match filter {
+                SearchFilter::Equal => value == *previous,
+                SearchFilter::Changed => value != *previous,
+                SearchFilter::Greater => value > *previous,
+                SearchFilter::Less => value < *previous,
+                SearchFilter::Value(expected) => value == expected,
+            };
+
+            *previous = value;
+            keep
+        });
+    }
+
+    // the addresses that are left with their values at the last snapshot
+    pub fn candidates(&self) -> &[(u16, u8)] {
+        &self.candidates
+    }
+}
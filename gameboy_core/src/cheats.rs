@@ -0,0 +1,108 @@
+use crate::emulator::EmulatorError;
+
+// gameshark codes only write the memory a game keeps its variables in: cartridge ram, wram and hram
+const GAME_SHARK_RANGES: [(u16, u16); 3] = [(0xA000, 0xBFFF), (0xC000, 0xDFFF), (0xFF80, 0xFFFE)];
+
+// a game genie code, it replaces a rom byte as the cartridge is read. with a compare byte the
+// replacement only happens when the rom holds that byte, which keeps it to the right bank
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RomPatch {
+    pub adder: u16,
+    pub value: u8,
+    pub compare: Option<u8>,
+}
+
+impl RomPatch {
+    pub fn matches(&self, adder: u16, original: u8) -> bool {
+        self.adder == adder
+            && match self.compare {
+                Some(compare) => compare == original,
+                None => true,
+            }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CheatCode {
+    GameGenie(RomPatch),
+    // a gameshark code, written to memory every v-blank. wram_bank picks the 0xd000 - 0xdfff bank
+    // on the color game boy, otherwise the selected bank is written
+    GameShark { adder: u16, value: u8, wram_bank: Option<usize> },
+}
+
+fn hex_digits(code: &str) -> Result<Vec<u8>, EmulatorError> {
+    code.chars()
+        .filter(|c| *c != '-')
+        .map(|c| {
+            c.to_digit(16)
+                .map(|digit| digit as u8)
+                .ok_or(EmulatorError::InvalidCheat("codes can only hold hex digits and dashes"))
+        })
+        .collect()
+}
+
+impl CheatCode {
+    // game genie codes are ABC-DEF or ABC-DEF-GHI (the dashes are optional), gameshark codes are 8 digits
+    pub fn parse(code: &str) -> Result<CheatCode, EmulatorError> {
+        let digits = hex_digits(code.trim())?;
+        let byte = |high: usize, low: usize| digits[high] << 4 | digits[low];
+
+        match digits.len() {
+            6 | 9 => {
+                // the address is FCDE with F xored by 0xf
+                let adder = ((digits[5] ^ 0xF) as u16) << 12
+                    | (digits[2] as u16) << 8
+                    | (digits[3] as u16) << 4
+                    | digits[4] as u16;
+
+                if adder >= 0x8000 {
+                    return Err(EmulatorError::InvalidCheat("game genie codes can only patch the rom"));
+                }
+
+                // the code holds the compare byte xored with 0xba and rotated left by 2 as GI, so it is
+                // rotated back right before the xor. H is unused
+                let compare = if digits.len() == 9 {
+                    Some(byte(6, 8).rotate_right(2) ^ 0xBA)
+                } else {
+                    None
+                };
+
+                Ok(CheatCode::GameGenie(RomPatch {
+                    adder,
+                    value: byte(0, 1),
+                    compare,
+                }))
+            }
+            8 if !code.contains('-') => {
+                // ABCDEFGH, AB is the code type, CD the value and GHEF the address
+                let wram_bank = match byte(0, 1) {
+                    0x00 | 0x01 => None,
+                    code_type @ 0x80 ..= 0x87 | code_type @ 0x90 ..= 0x97 => Some((code_type & 0x7).max(1) as usize),
+                    _ => return Err(EmulatorError::InvalidCheat("unknown gameshark code type")),
+                };
+
+                let adder = (byte(6, 7) as u16) << 8 | byte(4, 5) as u16;
+
+                if !GAME_SHARK_RANGES.iter().any(|(start, end)| (*start..=*end).contains(&adder)) {
+                    return Err(EmulatorError::InvalidCheat("gameshark codes can only write cartridge ram, wram or hram"));
+                }
+
+                Ok(CheatCode::GameShark {
+                    adder,
+                    value: byte(2, 3),
+                    wram_bank,
+                })
+            }
+            _ => Err(EmulatorError::InvalidCheat(
+                "codes are 6 or 9 digit game genie codes or 8 digit gameshark codes",
+            )),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Cheat {
+    pub code: String, // as it was given
+    pub cheat: CheatCode,
+    pub enabled: bool,
+}
@@ -1,17 +1,31 @@
 use crate::apu::Apu;
+use crate::bus::{Bus, IoTarget};
 use crate::frequency_controller::FrequencyController;
+pub use crate::frequency_controller::{MAX_SPEED, MIN_SPEED};
 use crate::ppu::dma::DmaManager;
-use crate::ram::Ram;
 use crate::joypad::Joypad;
+use crate::cdl::CodeDataLog;
 use crate::cartridge_controller::CartridgeController;
+use crate::cheats::{Cheat, CheatCode};
 use crate::timer::Timer;
 use crate::ppu::Ppu;
 use crate::ic::Ic;
-use std::rc::Rc;
-use std::cell::RefCell;
-use crate::hardware::Hardware;
+use crate::ram::Ram;
+use crate::serial::{Serial, SerialTransport};
+use crate::hardware::{ButtonState, Hardware};
 use crate::processor::Processor;
+pub use crate::processor::Registers;
+pub use crate::cartridge_controller::check_header;
 use crate::mmu::Mmu;
+pub use crate::mmu::{IoDevice, MemRead, MemWrite};
+use crate::movie::{Movie, MovieSession, MovieStart};
+use crate::post_boot;
+use crate::profiler::Profile;
+use crate::rewind::RewindBuffer;
+use crate::state::{SaveState, StateReader, StateWriter};
+use crate::symbols::SymbolTable;
+use std::fmt;
+use std::io::{self, Write};
 
 
 #[derive(PartialEq, Copy, Clone)]
@@ -20,132 +34,899 @@ pub enum GameBoyMode {
     Color,
 }
 
+impl GameBoyMode {
+    // the cgb flag in the header, set for color only games and for games that also run on the classic
+    pub fn detect(rom: &[u8]) -> GameBoyMode {
+        match rom.get(0x143) {
+            Some(flag) if flag & 0x80 != 0 => GameBoyMode::Color,
+            _ => GameBoyMode::Classic,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct EmulatorConfig {
     pub game_boy_mode: GameBoyMode,
-    pub allow_bad_checksum: bool,
-    pub native_speed: bool,
+    pub boot_rom: Option<Vec<u8>>, // when missing we start right at the cartridge entry point
+    pub allow_bad_checksum: bool, // the rom size still has to match the header
+    pub native_speed: bool, // start uncapped instead of at the real hardware speed
     pub cpu_speed: u64, // nano sec per cycle
+    pub rewind: Option<RewindConfig>, // rewinding is off when missing
+    pub block_opposite_directions: bool, // left+right and up+down read as neither, like on a real d-pad
+    pub audio: bool, // when off the apu stays muted at every speed
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Speed {
+    // the real hardware speed times the multiplier, it is kept between MIN_SPEED and MAX_SPEED.
+    // the emulator only keeps to it on hardware that is paced
+    Multiplier(f32),
+
+    // as fast as the host can go
+    Uncapped,
+}
+
+// what the ppu draws, for finding out which layer a rendering bug comes from.
+// turning a layer off only changes the picture, the emulated machine doesn't notice
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DebugLayers {
+    pub background: bool,
+    pub window: bool, // the background shows through where the window is turned off
+    pub sprites: bool,
+    pub sprite_outlines: bool, // boxes around every sprite on screen, even hidden ones
+    pub window_area: bool, // tints the part of the screen the window covers
+    pub palettes: bool, // when off the color numbers are drawn as a fixed gray ramp, past the game's palettes
+    pub scanline: Option<u8>, // tints this line, to line up a raster effect with the line it happens on
+}
+
+impl Default for DebugLayers {
+    fn default() -> DebugLayers {
+        DebugLayers {
+            background: true,
+            window: true,
+            sprites: true,
+            sprite_outlines: false,
+            window_area: false,
+            palettes: true,
+            scanline: None,
+        }
+    }
+}
+
+// an image made by the vram viewer, pixels are in the same format as the frame buffer
+#[derive(Clone, PartialEq, Debug)]
+pub struct DebugImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+impl DebugImage {
+    pub fn new(width: usize, height: usize, color: u32) -> DebugImage {
+        DebugImage {
+            width,
+            height,
+            pixels: vec![color; width * height],
+        }
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: u32) {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x] = color;
+        }
+    }
+
+    // 8 bits per channel in rgba order, the way image files want it
+    pub fn to_rgba(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|pixel| {
+                let [a, r, g, b] = pixel.to_be_bytes();
+                vec![r, g, b, a]
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Access, // reads and writes
+}
+
+// stops the debugger when the cpu touches any byte from adder to adder + len - 1
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Watchpoint {
+    pub adder: u16,
+    pub len: u16,
+    pub kind: WatchKind,
+}
+
+impl Watchpoint {
+    pub fn matches(&self, adder: u16, write: bool) -> bool {
+        let kind_matches = match self.kind {
+            WatchKind::Read => !write,
+            WatchKind::Write => write,
+            WatchKind::Access => true,
+        };
+
+        kind_matches && adder.wrapping_sub(self.adder) < self.len
+    }
+}
+
+// the watchpoint that was hit and the address that was touched
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct WatchHit {
+    pub watchpoint: Watchpoint,
+    pub adder: u16,
+}
+
+// the cpu ran an opcode that doesn't exist at adder and stopped for good, the rest of the system keeps running.
+// only a reset or loading a state brings it back
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LockUp {
+    pub adder: u16,
+    pub opcode: u8,
+}
+
+// a call the cpu hasn't returned from yet. the banks are the ones that were mapped when the call was made
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CallFrame {
+    pub call_site: u16, // the call or rst, or the instruction an interrupt came in before
+    pub call_site_bank: usize,
+    pub target: u16,
+    pub target_bank: usize,
+    pub interrupt: bool,
+    pub return_slot: u16, // where the return address was pushed
+}
+
+// why a debugger step stopped
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DebugEvent {
+    Watch(WatchHit),
+    LockUp(LockUp), // only reported by the step that locked the cpu up
+}
+
+// a decoded oam entry, x and y are screen coordinates so they go negative for sprites partly off screen
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct OamEntry {
+    pub x: i32,
+    pub y: i32,
+    pub tile_index: u16,
+    pub palette: u8, // obp0/obp1 on the classic game boy, one of the 8 object palettes on the color
+    pub vram_bank: usize,
+    pub x_flip: bool,
+    pub y_flip: bool,
+    pub behind_background: bool,
+}
+
+#[derive(Clone)]
+pub struct RewindConfig {
+    pub interval: u32, // frames between snapshots, a rewind step goes back this many frames
+    pub budget: usize, // bytes the snapshots may take up, the oldest ones are dropped first
+}
+
+// every save state starts with this so we never try to load random data
+const STATE_MAGIC: &[u8; 4] = b"GBST";
+const STATE_VERSION: u8 = 6;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EmulatorError {
+    // The rom is too short to hold a cartridge header, holds the rom length.
+    TruncatedRom(usize),
+
+    // The cartridge type in the header isn't supported, holds the type code.
+    UnsupportedMapper(u8),
+
+    // The header checksum doesn't match the one calculated from the header.
+    ChecksumMismatch { expected: u8, actual: u8 },
+
+    // The rom length doesn't match the rom size code in the header.
+    SizeMismatch { size_code: u8, actual: usize },
+
+    // The boot rom doesn't have the size the selected game boy mode expects.
+    BootRomSizeMismatch { expected: usize, actual: usize },
+
+    // The save state can't be loaded, holds the reason.
+    InvalidState(&'static str),
+
+    // The movie can't be read or played, holds the reason.
+    InvalidMovie(&'static str),
+
+    // The rom patch can't be applied, holds the reason.
+    InvalidPatch(&'static str),
+
+    // The cheat code can't be parsed, holds the reason.
+    InvalidCheat(&'static str),
+
+    // The symbol file can't be parsed, holds the reason.
+    InvalidSymbols(&'static str),
+
+    // The code/data log doesn't fit the rom, holds the reason.
+    InvalidCodeDataLog(&'static str),
+
+    // The speed multiplier isn't a finite number, holds the multiplier.
+    InvalidSpeed(f32),
+
+    // The movie was recorded on a rom with a different header checksum.
+    MovieMismatch { expected: u8, actual: u8 },
+}
+
+impl fmt::Display for EmulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EmulatorError::TruncatedRom(len) => write!(f, "rom is truncated, only {} bytes long", len),
+            EmulatorError::UnsupportedMapper(code) => write!(f, "unsupported cartridge type {:#04x}", code),
+            EmulatorError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "bad header checksum, header says {:#04x} but calculated {:#04x}",
+                expected, actual
+            ),
+            EmulatorError::SizeMismatch { size_code, actual } => write!(
+                f,
+                "rom is {} bytes long which doesn't match header rom size code {:#04x}",
+                actual, size_code
+            ),
+            EmulatorError::BootRomSizeMismatch { expected, actual } => write!(
+                f,
+                "boot rom is {} bytes long but this game boy mode expects {} bytes",
+                actual, expected
+            ),
+            EmulatorError::InvalidState(reason) => write!(f, "invalid save state, {}", reason),
+            EmulatorError::InvalidMovie(reason) => write!(f, "invalid movie, {}", reason),
+            EmulatorError::InvalidPatch(reason) => write!(f, "can't apply patch, {}", reason),
+            EmulatorError::InvalidCheat(reason) => write!(f, "invalid cheat code, {}", reason),
+            EmulatorError::InvalidSymbols(reason) => write!(f, "invalid symbol file, {}", reason),
+            EmulatorError::InvalidCodeDataLog(reason) => write!(f, "invalid code/data log, {}", reason),
+            EmulatorError::InvalidSpeed(multiplier) => write!(f, "speed multiplier {} isn't a finite number", multiplier),
+            EmulatorError::MovieMismatch { expected, actual } => write!(
+                f,
+                "movie was recorded on a rom with header checksum {:#04x} but this rom has {:#04x}",
+                expected, actual
+            ),
+        }
+    }
 }
 
+impl std::error::Error for EmulatorError {}
+
 pub struct Emulator {
-    hw: Rc<RefCell<Box<dyn Hardware>>>,
+    hw: Box<dyn Hardware>,
     processor: Processor,
     mmu: Mmu,
-    ic: Rc<RefCell<Ic>>,
-    ppu: Rc<RefCell<Ppu>>,
-    dma_manager: Rc<RefCell<DmaManager>>,
-    timer: Rc<RefCell<Timer>>,
-    cartridge_controller: Rc<RefCell<CartridgeController>>,
-    joypad: Rc<RefCell<Joypad>>,
-    apt: Rc<RefCell<Apu>>,
     fc: FrequencyController,
+    cycles: u64, // t cycles run since power on
+    rewind: Option<RewindBuffer>,
+    movie: Option<MovieSession>,
+    input: Option<ButtonState>, // set through set_input, the hardware is asked when missing
+    power_on: Vec<u8>, // snapshot taken before the first instruction, reset goes back to it
+    audio: bool,
+    cheats: Vec<Cheat>, // not part of save states, they stay on through loads, rewinds and resets
+    symbols: SymbolTable,
 }
 
 
 
 impl Emulator {
-    pub fn new(rom: Vec<u8>, hw: Box<dyn Hardware>, emulator_config: EmulatorConfig) -> Emulator {
-
-        let hw = Rc::new(RefCell::new(hw));
-        let ic = Rc::new(RefCell::new(Ic::new()));
-        let irq = ic.borrow().get_requester();
-        let cartridge_controller = Rc::new(RefCell::new(CartridgeController::new(rom, emulator_config.game_boy_mode, emulator_config.allow_bad_checksum)));
-        let joypad = Rc::new(RefCell::new(Joypad::new(Rc::clone(&hw), irq.clone())));
-        let ram = Rc::new(RefCell::new(Ram::new()));
-        let dma_manager = Rc::new(RefCell::new(DmaManager::new()));
+    pub fn new(rom: Vec<u8>, hw: Box<dyn Hardware>, emulator_config: EmulatorConfig) -> Result<Emulator, EmulatorError> {
+        let skip_boot_rom = emulator_config.boot_rom.is_none();
+        let cartridge_controller = CartridgeController::new(
+            rom,
+            emulator_config.boot_rom,
+            emulator_config.game_boy_mode,
+            emulator_config.allow_bad_checksum,
+        )?;
+
+        let processor = if skip_boot_rom {
+            Processor::post_boot(emulator_config.game_boy_mode)
+        } else {
+            Processor::new()
+        };
 
-        let processor = Processor::new();
-        let mut mmu = Mmu::new();
-        let ppu = Rc::new(RefCell::new(Ppu::new(Rc::clone(&hw), irq.clone(), emulator_config.game_boy_mode)));
-        let timer = Rc::new(RefCell::new(Timer::new(irq.clone())));
+        let bus = Bus {
+            cartridge_controller,
+            ppu: Ppu::new(emulator_config.game_boy_mode),
+            apu: Apu::new((1000000000 / emulator_config.cpu_speed) as u32),
+            timer: Timer::new(),
+            ic: Ic::new(),
+            ram: Ram::new(),
+            joypad: Joypad::new(emulator_config.block_opposite_directions),
+            dma_manager: DmaManager::new(),
+            serial: Serial::new(emulator_config.game_boy_mode),
+            devices: Vec::new(),
+        };
+        let mut mmu = Mmu::new(bus);
 
-        let apt = Rc::new(RefCell::new(Apu::new(Rc::clone(&hw), (1000000000 / emulator_config.cpu_speed) as u32)));
+        let fc = FrequencyController::new(emulator_config.cpu_speed, emulator_config.native_speed);
+        mmu.bus.apu.set_muted(!emulator_config.audio || fc.speed() != Speed::Multiplier(1.0));
 
-        let fc = FrequencyController::new(Rc::clone(&hw) ,emulator_config.cpu_speed, emulator_config.native_speed);
 
+        // rom, vram, oam, wram and hram are wired into the mmu directly, only io registers are mapped here
+        mmu.register_device((0xff50, 0xff50), IoTarget::Cartridge);
 
-        mmu.register_device((0x0000, 0x7fff), Rc::clone(&cartridge_controller));
-        mmu.register_device((0xff50, 0xff50), Rc::clone(&cartridge_controller));
-        mmu.register_device((0xa000, 0xbfff), Rc::clone(&cartridge_controller));
+        mmu.register_device((0xff40, 0xff4f), IoTarget::Ppu);
+        mmu.register_device((0xff68, 0xff6b), IoTarget::Ppu);
 
-        mmu.register_device((0x8000, 0x9fff), Rc::clone(&ppu));
-        mmu.register_device((0xff40, 0xff55), Rc::clone(&ppu));
-        mmu.register_device((0xff68, 0xff6b), Rc::clone(&ppu));
-        mmu.register_device((0xFE00, 0xFE9F), Rc::clone(&ppu));
+        mmu.register_device((0xFF46, 0xFF46), IoTarget::Dma);
+        mmu.register_device((0xFF51, 0xFF55), IoTarget::Dma);
 
-        mmu.register_device((0xFF46, 0xFF46), Rc::clone(&dma_manager));
-        mmu.register_device((0xFF51, 0xFF55), Rc::clone(&dma_manager));
+        mmu.register_device((0xFF10, 0xFF3F), IoTarget::Apu);
 
-        mmu.register_device((0xFF10, 0xFF3F), Rc::clone(&apt));
+        mmu.register_device((0xff0f, 0xff0f), IoTarget::Ic);
+        mmu.register_device((0xffff, 0xffff), IoTarget::Ic);
 
-        mmu.register_device((0xff0f, 0xff0f), Rc::clone(&ic));
-        mmu.register_device((0xffff, 0xffff), Rc::clone(&ic));
+        mmu.register_device((0xff00, 0xff00), IoTarget::Joypad);
 
-        mmu.register_device((0xff00, 0xff00), Rc::clone(&joypad));
+        mmu.register_device((0xff04, 0xff07), IoTarget::Timer);
 
-        mmu.register_device((0xff04, 0xff07), Rc::clone(&timer));
+        mmu.register_device((0xff01, 0xff02), IoTarget::Serial);
 
-        mmu.register_device((0xC000, 0xCFFF), Rc::clone(&ram));
-        mmu.register_device((0xD000, 0xDFFF), Rc::clone(&ram));
-        mmu.register_device((0xE000, 0xFDFF), Rc::clone(&ram));
-        mmu.register_device((0xFF70, 0xFF70), Rc::clone(&ram));
-        mmu.register_device((0xFF80, 0xFFFE), Rc::clone(&ram));
+        if skip_boot_rom {
+            post_boot::skip_boot_rom(&mut mmu, emulator_config.game_boy_mode);
+        }
 
-        Emulator {
+        let mut emulator = Emulator {
             hw,
-            ic,
             processor,
             mmu,
-            ppu,
-            timer,
-            cartridge_controller,
-            dma_manager,
-            joypad,
             fc,
-            apt,
-        }
+            cycles: 0,
+            rewind: emulator_config.rewind.map(|rewind| RewindBuffer::new(rewind.interval, rewind.budget)),
+            movie: None,
+            input: None,
+            power_on: Vec::new(),
+            audio: emulator_config.audio,
+            cheats: Vec::new(),
+            symbols: SymbolTable::default(),
+        };
+        emulator.power_on = emulator.save_state();
+
+        Ok(emulator)
     }
 
-    fn cycle(&mut self) {
-        
-        let cycle_start = { self.hw.borrow_mut().clock() };
+    // runs a single instruction and returns the t cycles it took
+    fn cycle(&mut self) -> u32 {
+
+        let frame = self.frame_count();
 
         let mut clock = self.processor.cycle(&mut self.mmu);
 
-        clock += self.processor.check_interrupt(&mut self.mmu, &self.ic);
+        clock += self.processor.check_interrupt(&mut self.mmu);
     
-        let (ppu_mode, ppu_clock) = {
-            self.ppu.borrow_mut().get_status()
-        };
+        let (ppu_mode, ppu_clock) = self.mmu.bus.ppu.get_status();
 
-        let (new_ppu_clock, dma_in_progress) = self.dma_manager.borrow_mut().cycle(
+        let (new_ppu_clock, dma_in_progress, transfer) = self.mmu.bus.dma_manager.cycle(
             ppu_mode,
-            &mut self.mmu,
             ppu_clock + clock
         );
 
+        if let Some(transfer) = transfer {
+            transfer.run(&mut self.mmu);
+        }
+
+        let bus = &mut self.mmu.bus;
 
         if new_ppu_clock != ppu_clock {
-            self.ppu.borrow_mut().set_clock(new_ppu_clock);
+            bus.ppu.set_clock(new_ppu_clock);
         }
 
-        // this is a temp fix for the borrow system TODO: find a nicer way to make the dma and ppu talk
+        // the ppu is stalled while the dma is still copying
         if !dma_in_progress {
-            self.ppu.borrow_mut().cycle(&mut self.mmu, 0);
+            bus.ppu.cycle(0, &mut bus.ic, self.hw.as_mut());
         }
 
         
-        self.timer.borrow_mut().cycle(clock);
-        self.joypad.borrow_mut().poll();
+        bus.timer.cycle(clock, &mut bus.ic);
+
+        bus.serial.cycle(clock, &mut bus.ic);
+
+
+        self.fc.add_cycles(clock);
+
+        self.cycles += clock as u64;
+
+        if self.frame_count() != frame {
+            self.frame_finished();
+        }
+
+        clock
+    }
+
+    fn frame_finished(&mut self) {
+        self.fc.pace(self.hw.as_mut());
+
+        self.apply_ram_cheats();
+
+        self.latch_input();
+
+        let snapshot_due = match &mut self.rewind {
+            Some(rewind) => rewind.frame(),
+            None => false,
+        };
+
+        if snapshot_due {
+            let state = self.save_state();
+            if let Some(rewind) = &mut self.rewind {
+                rewind.push(state);
+            }
+        }
+    }
+
+    // input is latched once a frame, the joypad never asks the hardware in the middle of one
+    fn latch_input(&mut self) {
+        let live = match self.input {
+            Some(buttons) => buttons,
+            None => ButtonState::read(self.hw.as_mut()),
+        };
+
+        let buttons = match &mut self.movie {
+            Some(movie) => match movie.next_frame(live) {
+                Some(buttons) => buttons,
+                None => {
+                    // playback is over, input comes from the hardware again
+                    self.movie = None;
+                    live
+                }
+            },
+            None => live,
+        };
+
+        let bus = &mut self.mmu.bus;
+        bus.joypad.set_input(buttons, &mut bus.ic);
+    }
+
+    // from now on the hardware isn't asked about keys anymore, the given state is used until it is set again.
+    // it applies right away, unless a movie is being recorded or played, then it waits for the next frame
+    // so the movie stays in sync
+    pub fn set_input(&mut self, buttons: ButtonState) {
+        self.input = Some(buttons);
+
+        if self.movie.is_none() {
+            let bus = &mut self.mmu.bus;
+            bus.joypad.set_input(buttons, &mut bus.ic);
+        }
+    }
+
+    pub fn registers(&self) -> Registers {
+        self.processor.registers()
+    }
+
+    pub fn set_registers(&mut self, registers: Registers) {
+        self.processor.set_registers(registers);
+    }
+
+    // runs a single instruction (and the interrupt dispatch after it) for debuggers,
+    // returns the first watchpoint it hit or the lock up it ran into
+    pub fn step(&mut self) -> Option<DebugEvent> {
+        let locked_up = self.processor.lock_up().is_some();
+
+        self.mmu.watch_hit = None;
+        self.cycle();
+
+        if let Some(hit) = self.mmu.watch_hit.take() {
+            return Some(DebugEvent::Watch(hit));
+        }
+
+        match self.processor.lock_up() {
+            Some(lock_up) if !locked_up => Some(DebugEvent::LockUp(lock_up)),
+            _ => None,
+        }
+    }
+
+    pub fn lock_up(&self) -> Option<LockUp> {
+        self.processor.lock_up()
+    }
+
+    // the calls the cpu is in, the innermost one last. it follows the calls, rsts, interrupts and
+    // returns the cpu runs, code that leaves a call by jumping or moves the stack by hand can leave
+    // frames behind until the next return above them. a loaded state starts with an empty stack
+    pub fn backtrace(&self) -> &[CallFrame] {
+        self.processor.backtrace()
+    }
+
+    // logs every instruction to out in the gameboy doctor format until it is set to None,
+    // returns the first error the trace it replaces ran into
+    pub fn set_trace(&mut self, out: Option<Box<dyn Write + Send>>) -> io::Result<()> {
+        self.processor.set_trace(out)
+    }
+
+    // counts every instruction the cpu runs from now on, per bank and address
+    pub fn start_profiling(&mut self) {
+        self.processor.start_profiling();
+    }
+
+    pub fn stop_profiling(&mut self) -> Option<Profile> {
+        self.processor.stop_profiling()
+    }
+
+    // what was collected so far, None when not profiling
+    pub fn profile(&self) -> Option<&Profile> {
+        self.processor.profile()
+    }
+
+    // plugs a device into the link port instead of nothing, a printer or another game boy
+    pub fn connect_serial(&mut self, transport: Box<dyn SerialTransport>) {
+        self.mmu.bus.serial.connect(transport);
+    }
+
+    // maps io registers to a device of your own, it takes them over from whatever had them.
+    // 0xff70 and hram always go to the ram
+    pub fn map_io_device(&mut self, range: (u16, u16), device: Box<dyn IoDevice + Send>) {
+        let target = self.mmu.bus.add_device(device);
+        self.mmu.register_device(range, target);
+    }
+
+    // logs how every rom byte is read from now on, the flags are added to a log from an earlier session
+    pub fn start_code_data_log(&mut self, previous: Option<&[u8]>) -> Result<(), EmulatorError> {
+        let rom_size = self.mmu.bus.cartridge_controller.rom_size();
+
+        let log = match previous {
+            Some(bytes) => CodeDataLog::from_bytes(bytes, rom_size)?,
+            None => CodeDataLog::new(rom_size),
+        };
+
+        self.mmu.cdl = Some(log);
+        Ok(())
+    }
+
+    pub fn stop_code_data_log(&mut self) -> Option<CodeDataLog> {
+        self.mmu.cdl.take()
+    }
+
+    pub fn code_data_log(&self) -> Option<&CodeDataLog> {
+        self.mmu.cdl.as_ref()
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.mmu.watchpoints.push(watchpoint);
+    }
+
+    pub fn remove_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.mmu.watchpoints.retain(|other| *other != watchpoint);
+    }
+
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
 
+    pub fn set_symbols(&mut self, symbols: SymbolTable) {
+        self.symbols = symbols;
+    }
 
-        self.fc.add_delay(cycle_start, clock);
+    // the bank mapped at adder right now, numbered the way symbol files number them
+    pub fn bank(&self, adder: u16) -> usize {
+        self.mmu.bank(adder)
+    }
+
+    // the label for adder as the memory is mapped right now, banked rom resolves through the cartridge's current bank
+    pub fn symbolize(&self, adder: u16) -> Option<String> {
+        self.symbols.symbolize(self.bank(adder), adder)
+    }
+
+    // bank:adder followed by its label when there is one, 01:4123 Label+3
+    pub fn describe(&self, adder: u16) -> String {
+        self.describe_in(self.bank(adder), adder)
+    }
+
+    fn describe_in(&self, bank: usize, adder: u16) -> String {
+        let location = format!("{:02x}:{:04x}", bank, adder);
+
+        match self.symbols.symbolize(bank, adder) {
+            Some(label) => format!("{} {}", location, label),
+            None => location,
+        }
+    }
+
+    // a line for every frame of the backtrace, innermost first
+    pub fn describe_backtrace(&self) -> String {
+        self.backtrace()
+            .iter()
+            .rev()
+            .map(|frame| {
+                let kind = if frame.interrupt { "interrupted at" } else { "called from" };
+                format!("{} {}\n", kind, self.describe_in(frame.call_site_bank, frame.call_site))
+            })
+            .collect()
+    }
+
+    pub fn debug_layers(&self) -> DebugLayers {
+        self.mmu.bus.ppu.debug_layers
+    }
+
+    // applies from the next line the ppu draws
+    pub fn set_debug_layers(&mut self, layers: DebugLayers) {
+        self.mmu.bus.ppu.debug_layers = layers;
+    }
+
+    // reads memory the way the cpu sees it right now without touching anything, io registers included
+    pub fn peek(&self, addr: u16) -> u8 {
+        self.mmu.peek_byte(addr)
+    }
+
+    // writes straight into ram, vram, oam or hram without any side effect. the rom, the mbc registers
+    // and the io registers can't be poked, false when nothing was written
+    pub fn poke(&mut self, addr: u16, value: u8) -> bool {
+        self.mmu.poke_byte(addr, value)
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    // adds an enabled cheat and returns its index
+    pub fn add_cheat(&mut self, code: &str) -> Result<usize, EmulatorError> {
+        let cheat = CheatCode::parse(code)?;
+
+        self.cheats.push(Cheat {
+            code: code.trim().to_string(),
+            cheat,
+            enabled: true,
+        });
+        self.update_rom_patches();
+
+        Ok(self.cheats.len() - 1)
+    }
+
+    pub fn remove_cheat(&mut self, index: usize) {
+        if index < self.cheats.len() {
+            self.cheats.remove(index);
+            self.update_rom_patches();
+        }
+    }
+
+    pub fn set_cheat_enabled(&mut self, index: usize, enabled: bool) {
+        if let Some(cheat) = self.cheats.get_mut(index) {
+            cheat.enabled = enabled;
+            self.update_rom_patches();
+        }
+    }
+
+    // game genie codes are applied by the cartridge controller as the rom is read
+    fn update_rom_patches(&mut self) {
+        let rom_patches = self
+            .cheats
+            .iter()
+            .filter(|cheat| cheat.enabled)
+            .filter_map(|cheat| match cheat.cheat {
+                CheatCode::GameGenie(patch) => Some(patch),
+                CheatCode::GameShark { .. } => None,
+            })
+            .collect();
+
+        self.mmu.bus.cartridge_controller.set_rom_patches(rom_patches);
+    }
+
+    // gameshark codes are written once a frame like the real one does on v-blank
+    fn apply_ram_cheats(&mut self) {
+        for cheat in self.cheats.iter().filter(|cheat| cheat.enabled) {
+            if let CheatCode::GameShark { adder, value, wram_bank } = cheat.cheat {
+                match wram_bank {
+                    Some(bank) if (0xD000..0xE000).contains(&adder) => {
+                        self.mmu.bus.ram.write_wram_bank(bank, adder, value)
+                    }
+                    // the address was checked to be ram when the code was parsed
+                    _ => {
+                        self.mmu.poke_byte(adder, value);
+                    }
+                }
+            }
+        }
+    }
+
+    // every tile in vram, 16 tiles a row and the second bank to the right on the color game boy.
+    // tiles are colored with the first background palette
+    pub fn vram_tiles(&self) -> DebugImage {
+        self.mmu.bus.ppu.tile_data_image()
+    }
+
+    // one of the two 32x32 tile maps (0 at 0x9800, 1 at 0x9c00) with the scroll viewport outlined
+    pub fn tile_map(&self, map: usize) -> DebugImage {
+        self.mmu.bus.ppu.tile_map_image(map)
+    }
+
+    pub fn oam_entries(&self) -> Vec<OamEntry> {
+        self.mmu.bus.ppu.oam_entries()
+    }
+
+    // the 40 sprites in oam order, 8 a row
+    pub fn oam_image(&self) -> DebugImage {
+        self.mmu.bus.ppu.oam_image()
+    }
+
+    // a row of 4 colors for every palette, background palettes first
+    pub fn palettes(&self) -> DebugImage {
+        self.mmu.bus.ppu.palette_image()
+    }
+
+    pub fn speed(&self) -> Speed {
+        self.fc.speed()
+    }
+
+    // audio is only played at the real speed, at any other speed it is muted. a multiplier that isn't
+    // a finite number is refused and the speed stays as it was
+    pub fn set_speed(&mut self, speed: Speed) -> Result<(), EmulatorError> {
+        self.fc.set_speed(speed)?;
+        self.mmu.bus.apu.set_muted(!self.audio || self.fc.speed() != Speed::Multiplier(1.0));
+
+        Ok(())
+    }
+
+    // records the input of every frame from now on, a recording started before the first
+    // instruction ran replays from power on, otherwise it replays from a save state taken now
+    pub fn start_recording(&mut self) {
+        let start = if self.cycles == 0 {
+            MovieStart::PowerOn
+        } else {
+            MovieStart::SaveState(self.save_state())
+        };
+
+        self.movie = Some(MovieSession::Recording(Movie {
+            header_checksum: self.mmu.bus.cartridge_controller.header_checksum(),
+            start,
+            frames: Vec::new(),
+        }));
+        self.latch_input();
+    }
+
+    // returns the recording, or None if nothing was being recorded
+    pub fn stop_recording(&mut self) -> Option<Movie> {
+        match self.movie.take() {
+            Some(MovieSession::Recording(movie)) => Some(movie),
+            other => {
+                self.movie = other;
+                None
+            }
+        }
+    }
+
+    // the hardware input is ignored until the movie runs out of frames
+    pub fn play_movie(&mut self, movie: Movie) -> Result<(), EmulatorError> {
+        let header_checksum = self.mmu.bus.cartridge_controller.header_checksum();
+        if movie.header_checksum != header_checksum {
+            return Err(EmulatorError::MovieMismatch {
+                expected: movie.header_checksum,
+                actual: header_checksum,
+            });
+        }
+
+        match &movie.start {
+            MovieStart::PowerOn if self.cycles != 0 => {
+                return Err(EmulatorError::InvalidMovie("recorded from power on but the emulator already ran"))
+            }
+            MovieStart::PowerOn => {}
+            MovieStart::SaveState(state) => self.load_state(state)?,
+        }
+
+        self.movie = Some(MovieSession::Playing { movie, frame: 0 });
+        self.latch_input();
+
+        Ok(())
+    }
+
+    pub fn playing_movie(&self) -> bool {
+        matches!(self.movie, Some(MovieSession::Playing { .. }))
+    }
+
+    // snapshot of the whole machine, it can only be loaded back into an emulator running the same rom
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = StateWriter::new();
+
+        state.write_bytes(STATE_MAGIC);
+        state.write_u8(STATE_VERSION);
+        state.write_u8(self.mmu.bus.cartridge_controller.header_checksum());
+
+        state.write_u64(self.cycles);
+        self.processor.save_state(&mut state);
+        self.mmu.bus.save_state(&mut state);
+
+        state.finish()
+    }
+
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), EmulatorError> {
+        if state.len() < STATE_MAGIC.len() + 2 || &state[..STATE_MAGIC.len()] != STATE_MAGIC {
+            return Err(EmulatorError::InvalidState("not a save state"));
+        }
+
+        let mut reader = StateReader::new(&state[STATE_MAGIC.len()..]);
+
+        if reader.read_u8() != STATE_VERSION {
+            return Err(EmulatorError::InvalidState("saved by an incompatible version"));
+        }
+
+        if reader.read_u8() != self.mmu.bus.cartridge_controller.header_checksum() {
+            return Err(EmulatorError::InvalidState("saved while running a different rom"));
+        }
+
+        // every field has a fixed size for a given rom so any other length means the state is damaged
+        if state.len() != self.save_state().len() {
+            return Err(EmulatorError::InvalidState("unexpected length"));
+        }
+
+        let previous = self.save_state();
+
+        self.cycles = reader.read_u64();
+        self.processor.load_state(&mut reader);
+        self.mmu.bus.load_state(&mut reader);
+
+        // a damaged field is only noticed halfway through, the machine goes back to where it was
+        if let Some(reason) = reader.invalid() {
+            self.load_state(&previous).expect("a state saved a moment ago is always valid");
+            return Err(EmulatorError::InvalidState(reason));
+        }
+
+        Ok(())
+    }
+
+    // puts the machine back to how it was right after it was created
+    pub fn reset(&mut self) {
+        let power_on = std::mem::take(&mut self.power_on);
+        self.load_state(&power_on).expect("power on snapshot is always valid");
+        self.power_on = power_on;
+    }
+
+    // steps back to the previous rewind snapshot, returns false when there is nothing left to rewind
+    pub fn rewind(&mut self) -> bool {
+        let state = match self.rewind.as_mut().and_then(|rewind| rewind.pop()) {
+            Some(state) => state.to_vec(),
+            None => return false,
+        };
+
+        self.load_state(&state).is_ok()
+    }
+
+    // runs until the ppu enters the next vblank and returns the finished frame, row by row 160x144 pixels
+    pub fn run_frame(&mut self) -> &[u32] {
+        let frame = self.frame_count();
+
+        while self.frame_count() == frame {
+            self.cycle();
+        }
+
+        self.frame_buffer()
+    }
+
+    // runs at least the given amount of t cycles, instructions aren't split so it returns how many actually ran
+    pub fn run_cycles(&mut self, cycles: u32) -> u32 {
+        let mut ran = 0;
+
+        while ran < cycles {
+            ran += self.cycle();
+        }
+
+        ran
+    }
+
+    // runs instructions until the predicate holds, it is checked before every instruction.
+    // returns how many t cycles ran
+    pub fn run_until<F>(&mut self, mut predicate: F) -> u64 where F: FnMut(&Emulator) -> bool {
+        let start = self.cycles;
+
+        while !predicate(self) {
+            self.cycle();
+        }
+
+        self.cycles - start
+    }
+
+    // the last frame the ppu finished, row by row 160x144 pixels
+    pub fn frame_buffer(&self) -> &[u32] {
+        self.mmu.bus.ppu.frame_buffer()
+    }
+
+    // how many frames were finished since power on
+    pub fn frame_count(&self) -> u64 {
+        self.mmu.bus.ppu.frame_count()
+    }
+
+    // how many t cycles ran since power on
+    pub fn cycles(&self) -> u64 {
+        self.cycles
     }
 
     // cycle as long as hw allows
     pub fn poll(&mut self) -> bool {
-        if !self.hw.borrow_mut().run() {
+        if !self.hw.run() {
             return false;
         }
 
@@ -157,7 +938,9 @@ impl Emulator {
 
 
 
-pub fn run(rom: Vec<u8>, hw: Box<dyn Hardware>, emulator_config: EmulatorConfig) {
-    let mut emulator = Emulator::new(rom, hw, emulator_config);
+pub fn run(rom: Vec<u8>, hw: Box<dyn Hardware>, emulator_config: EmulatorConfig) -> Result<(), EmulatorError> {
+    let mut emulator = Emulator::new(rom, hw, emulator_config)?;
     while emulator.poll() {}
+
+    Ok(())
 }
\ No newline at end of file
@@ -1,37 +1,82 @@
+use crate::emulator::{EmulatorError, Speed};
 use crate::processor::TCycles;
 use std::time::Duration;
 use crate::hardware::Hardware;
-use std::cell::RefCell;
-use std::rc::Rc;
 
+pub const MIN_SPEED: f32 = 0.25;
+pub const MAX_SPEED: f32 = 8.0;
 
+// if we are this far behind the host is too slow to keep up, so we don't try to catch up with it
+const MAX_LAG: Duration = Duration::from_millis(100);
+
+
+// paces the emulator by sleeping once a frame until the host clock catches up with the emulated time,
+// only for hardware that asks for it
 pub struct FrequencyController {
-    hardware: Rc<RefCell<Box<dyn Hardware>>>,
-    
     target_freq: u64, // nano sec per cycle
 
-    native_speed: bool,
+    speed: Speed,
+
+    start: Option<Duration>, // host clock when pacing last (re)started
+    cycles: u64, // cycles run since start
 }
 
 impl FrequencyController {
-    pub fn new(hardware: Rc<RefCell<Box<dyn Hardware>>>, target_freq: u64, native_speed: bool) -> Self {
+    pub fn new(target_freq: u64, native_speed: bool) -> Self {
+        let speed = if native_speed {
+            Speed::Uncapped
+        } else {
+            Speed::Multiplier(1.0)
+        };
+
         Self {
-            hardware,
             target_freq,
-            native_speed,
+            speed,
+            start: None,
+            cycles: 0,
         }
     }
 
-    pub fn add_delay(&mut self, cycle_start: Duration, cpu_cycles: TCycles) {
-        if self.native_speed {
-            return;
-        }
+    pub fn speed(&self) -> Speed {
+        self.speed
+    }
 
-        let target_time = Duration::from_nanos(self.target_freq * cpu_cycles as u64);
-        let mut cycle_end = self.hardware.borrow_mut().clock();
+    pub fn set_speed(&mut self, speed: Speed) -> Result<(), EmulatorError> {
+        self.speed = match speed {
+            // clamp lets nan through
+            Speed::Multiplier(multiplier) if !multiplier.is_finite() => return Err(EmulatorError::InvalidSpeed(multiplier)),
+            Speed::Multiplier(multiplier) => Speed::Multiplier(multiplier.clamp(MIN_SPEED, MAX_SPEED)),
+            Speed::Uncapped => Speed::Uncapped,
+        };
 
-        while (cycle_end - cycle_start) < target_time {
-            cycle_end = self.hardware.borrow_mut().clock();
+        // the time already run was paced for the old speed
+        self.start = None;
+        self.cycles = 0;
+
+        Ok(())
+    }
+
+    pub fn add_cycles(&mut self, cpu_cycles: TCycles) {
+        self.cycles += cpu_cycles as u64;
+    }
+
+    pub fn pace(&mut self, hardware: &mut dyn Hardware) {
+        let multiplier = match self.speed {
+            Speed::Multiplier(multiplier) if hardware.paced() => multiplier as f64,
+            _ => return,
+        };
+
+        let now = hardware.clock();
+        let start = *self.start.get_or_insert(now);
+
+        let target_time = Duration::from_nanos((self.target_freq as f64 * self.cycles as f64 / multiplier) as u64);
+        let elapsed = now.checked_sub(start).unwrap_or_default();
+
+        if target_time > elapsed {
+            std::thread::sleep(target_time - elapsed);
+        } else if elapsed - target_time > MAX_LAG {
+            self.start = Some(now);
+            self.cycles = 0;
         }
     }
-}
\ No newline at end of file
+}
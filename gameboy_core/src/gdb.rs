@@ -0,0 +1,588 @@
+use crate::emulator::{DebugEvent, Emulator, Registers, WatchHit, WatchKind, Watchpoint};
+use crate::symbols::Symbol;
+use std::collections::HashSet;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+// a gdb remote serial protocol stub, gdb (or anything that speaks rsp) drives the emulator one
+// connection at a time. breakpoints never touch memory, the pc is checked after every instruction
+
+// gdb has no sm83 target so the registers are described to it, g packets send them in this order
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.gnu.gdb.sm83.core">
+    <reg name="a" bitsize="8" regnum="0"/>
+    <reg name="f" bitsize="8"/>
+    <reg name="b" bitsize="8"/>
+    <reg name="c" bitsize="8"/>
+    <reg name="d" bitsize="8"/>
+    <reg name="e" bitsize="8"/>
+    <reg name="h" bitsize="8"/>
+    <reg name="l" bitsize="8"/>
+    <reg name="sp" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+const REGISTER_COUNT: usize = 10;
+const PACKET_SIZE: usize = 0x4000;
+
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+
+// ctrl-c from the debugger while running
+const INTERRUPT: u8 = 0x03;
+
+// instructions run between checks for a ctrl-c
+const INTERRUPT_CHECK_INTERVAL: u32 = 4096;
+
+// how long a wait for the debugger goes before the quit check is asked again
+const QUIT_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+// gdb knows nothing about banks or the game's symbols, monitor commands fill the gap
+const MONITOR_HELP: &str = "\
+monitor commands, addresses are hex or a label from the symbol file:
+  break <addr>   break at addr, a label in banked rom only breaks while its bank is mapped
+  delete <addr>  remove a breakpoint set with monitor break
+  sym <addr>     show the bank and label for addr
+  where          show the bank and label for pc, the calls that led there and where the cpu locked up
+  help
+";
+
+enum Stop {
+    Step,
+    Breakpoint,
+    Watch(WatchHit),
+    LockUp, // an illegal opcode hung the cpu
+    Interrupted,
+}
+
+// waits for a debugger on the listener and serves it until it detaches, kills or disconnects.
+// quit is asked every now and then while waiting, once it says yes the session ends like a detach
+pub fn serve(emulator: &mut Emulator, listener: &TcpListener, quit: &dyn Fn() -> bool) -> io::Result<()> {
+    let stream = match accept(listener, quit)? {
+        Some(stream) => stream,
+        None => return Ok(()),
+    };
+    stream.set_nonblocking(false)?;
+    stream.set_nodelay(true)?;
+    stream.set_read_timeout(Some(QUIT_CHECK_INTERVAL))?;
+
+    let mut session = Session {
+        stream,
+        emulator,
+        breakpoints: HashSet::new(),
+        banked_breakpoints: HashSet::new(),
+        watchpoints: Vec::new(),
+        ack: true,
+        pending: Vec::new(),
+        quit,
+    };
+
+    let result = session.run();
+    session.clear_watchpoints();
+
+    result
+}
+
+// None when told to quit before a debugger showed up
+fn accept(listener: &TcpListener, quit: &dyn Fn() -> bool) -> io::Result<Option<TcpStream>> {
+    listener.set_nonblocking(true)?;
+
+    let stream = loop {
+        match listener.accept() {
+            Ok((stream, _)) => break Some(stream),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                if quit() {
+                    break None;
+                }
+                std::thread::sleep(QUIT_CHECK_INTERVAL);
+            }
+            Err(e) => {
+                listener.set_nonblocking(false)?;
+                return Err(e);
+            }
+        }
+    };
+
+    listener.set_nonblocking(false)?;
+    Ok(stream)
+}
+
+struct Session<'a> {
+    stream: TcpStream,
+    emulator: &'a mut Emulator,
+    breakpoints: HashSet<u16>,
+    banked_breakpoints: HashSet<(usize, u16)>, // bank, adder
+    watchpoints: Vec<Watchpoint>, // to remove them from the emulator when the debugger goes away
+    ack: bool, // until the debugger asks for no ack mode
+    pending: Vec<u8>, // bytes read while checking for a ctrl-c
+    quit: &'a dyn Fn() -> bool,
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn hex(text: &str) -> Option<u16> {
+    u16::from_str_radix(text, 16).ok()
+}
+
+fn hex_bytes(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 2 != 0 {
+        return None;
+    }
+
+    (0..text.len())
+        .step_by(2)
+        .map(|index| text.get(index..index + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn register_bytes(registers: &Registers) -> Vec<u8> {
+    let mut bytes = vec![
+        registers.a,
+        registers.f,
+        registers.b,
+        registers.c,
+        registers.d,
+        registers.e,
+        registers.h,
+        registers.l,
+    ];
+    bytes.extend_from_slice(&registers.sp.to_le_bytes());
+    bytes.extend_from_slice(&registers.pc.to_le_bytes());
+
+    bytes
+}
+
+// writes one register from its little endian bytes, returns false for a bad register or size
+fn set_register(registers: &mut Registers, number: usize, bytes: &[u8]) -> bool {
+    let byte = |target: &mut u8| match bytes {
+        [value] => {
+            *target = *value;
+            true
+        }
+        _ => false,
+    };
+
+    match number {
+        0 => byte(&mut registers.a),
+        1 => byte(&mut registers.f),
+        2 => byte(&mut registers.b),
+        3 => byte(&mut registers.c),
+        4 => byte(&mut registers.d),
+        5 => byte(&mut registers.e),
+        6 => byte(&mut registers.h),
+        7 => byte(&mut registers.l),
+        8 | 9 => match bytes {
+            [low, high] => {
+                let value = u16::from_le_bytes([*low, *high]);
+                if number == 8 {
+                    registers.sp = value;
+                } else {
+                    registers.pc = value;
+                }
+                true
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+// the register sizes in g packet order
+fn register_size(number: usize) -> usize {
+    if number < 8 {
+        1
+    } else {
+        2
+    }
+}
+
+// an address as hex or a label, labels keep their bank
+fn parse_location(emulator: &Emulator, text: &str) -> Result<Symbol, String> {
+    if let Some(symbol) = emulator.symbols().lookup(text) {
+        return Ok(symbol.clone());
+    }
+
+    match hex(text.trim_start_matches("0x").trim_start_matches('$')) {
+        Some(adder) => Ok(Symbol {
+            bank: emulator.bank(adder),
+            adder,
+            name: String::new(),
+        }),
+        None => Err(format!("no symbol named {}", text)),
+    }
+}
+
+fn watch_kind(packet_type: &str) -> Option<WatchKind> {
+    match packet_type {
+        "2" => Some(WatchKind::Write),
+        "3" => Some(WatchKind::Read),
+        "4" => Some(WatchKind::Access),
+        _ => None,
+    }
+}
+
+impl<'a> Session<'a> {
+    fn run(&mut self) -> io::Result<()> {
+        while let Some(packet) = self.read_packet()? {
+            let reply = match packet.as_str() {
+                "k" => return Ok(()),
+                "D" => {
+                    self.send("OK")?;
+                    return Ok(());
+                }
+                "QStartNoAckMode" => {
+                    self.send("OK")?;
+                    self.ack = false;
+                    continue;
+                }
+                "c" | "s" => self.resume(packet == "s")?,
+                _ if packet.starts_with('c') || packet.starts_with('s') => {
+                    match hex(&packet[1..]) {
+                        Some(addr) => {
+                            let mut registers = self.emulator.registers();
+                            registers.pc = addr;
+                            self.emulator.set_registers(registers);
+                            self.resume(packet.starts_with('s'))?
+                        }
+                        None => "E01".to_string(),
+                    }
+                }
+                _ => self.handle(&packet).unwrap_or_else(|| "E01".to_string()),
+            };
+
+            self.send(&reply)?;
+        }
+
+        Ok(())
+    }
+
+    // every packet that doesn't run the emulator, None is a malformed packet
+    fn handle(&mut self, packet: &str) -> Option<String> {
+        let reply = if packet.starts_with("qSupported") {
+            format!(
+                "PacketSize={:x};qXfer:features:read+;swbreak+;hwbreak+;QStartNoAckMode+",
+                PACKET_SIZE
+            )
+        } else if let Some(request) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let (offset, len) = request.split_once(',')?;
+            let offset = usize::from_str_radix(offset, 16).ok()?.min(TARGET_XML.len());
+            let len = usize::from_str_radix(len, 16).ok()?;
+            let end = offset.saturating_add(len).min(TARGET_XML.len());
+
+            let more = if end < TARGET_XML.len() { "m" } else { "l" };
+            format!("{}{}", more, &TARGET_XML[offset..end])
+        } else if packet == "?" {
+            format!("S{:02x}", SIGTRAP)
+        } else if packet == "qAttached" {
+            "1".to_string()
+        } else if packet.starts_with('H') {
+            "OK".to_string()
+        } else if packet == "g" {
+            to_hex(&register_bytes(&self.emulator.registers()))
+        } else if let Some(values) = packet.strip_prefix('G') {
+            let bytes = hex_bytes(values)?;
+            let mut registers = self.emulator.registers();
+            let mut offset = 0;
+
+            for number in 0..REGISTER_COUNT {
+                let size = register_size(number);
+                set_register(&mut registers, number, bytes.get(offset..offset + size)?);
+                offset += size;
+            }
+
+            self.emulator.set_registers(registers);
+            "OK".to_string()
+        } else if let Some(number) = packet.strip_prefix('p') {
+            let number = usize::from_str_radix(number, 16).ok()?;
+            let bytes = register_bytes(&self.emulator.registers());
+            let offset: usize = (0..number.min(REGISTER_COUNT)).map(register_size).sum();
+
+            match bytes.get(offset..offset + register_size(number)) {
+                Some(register) if number < REGISTER_COUNT => to_hex(register),
+                _ => return None,
+            }
+        } else if let Some(assignment) = packet.strip_prefix('P') {
+            let (number, value) = assignment.split_once('=')?;
+            let mut registers = self.emulator.registers();
+
+            if !set_register(&mut registers, usize::from_str_radix(number, 16).ok()?, &hex_bytes(value)?) {
+                return None;
+            }
+
+            self.emulator.set_registers(registers);
+            "OK".to_string()
+        } else if let Some(range) = packet.strip_prefix('m') {
+            let (addr, len) = range.split_once(',')?;
+            let addr = hex(addr)?;
+            let len = usize::from_str_radix(len, 16).ok()?.min(PACKET_SIZE / 2);
+
+            let bytes: Vec<u8> = (0..len)
+                .map(|offset| self.emulator.peek(addr.wrapping_add(offset as u16)))
+                .collect();
+            to_hex(&bytes)
+        } else if let Some(write) = packet.strip_prefix('M') {
+            let (range, data) = write.split_once(':')?;
+            let (addr, len) = range.split_once(',')?;
+            let addr = hex(addr)?;
+            let bytes = hex_bytes(data)?;
+
+            if bytes.len() != usize::from_str_radix(len, 16).ok()? {
+                return None;
+            }
+
+            // the rom and io registers can't be written, gdb is told when any byte didn't make it
+            let mut written = true;
+            for (offset, byte) in bytes.iter().enumerate() {
+                written &= self.emulator.poke(addr.wrapping_add(offset as u16), *byte);
+            }
+            if written { "OK".to_string() } else { "E01".to_string() }
+        } else if let Some(command) = packet.strip_prefix("qRcmd,") {
+            let command = String::from_utf8(hex_bytes(command)?).ok()?;
+            let output = self.monitor(&command);
+
+            // console output goes in an O packet ahead of the reply
+            if !output.is_empty() {
+                self.send(&format!("O{}", to_hex(output.as_bytes()))).ok()?;
+            }
+            "OK".to_string()
+        } else if packet.starts_with('Z') || packet.starts_with('z') {
+            let insert = packet.starts_with('Z');
+            let mut fields = packet[1..].split(',');
+            let packet_type = fields.next()?;
+            let addr = hex(fields.next()?)?;
+            let len = hex(fields.next()?)?;
+
+            match packet_type {
+                // software and hardware breakpoints are the same thing here
+                "0" | "1" => {
+                    if insert {
+                        self.breakpoints.insert(addr);
+                    } else {
+                        self.breakpoints.remove(&addr);
+                    }
+                }
+                _ => {
+                    let watchpoint = Watchpoint {
+                        adder: addr,
+                        len: len.max(1),
+                        kind: watch_kind(packet_type)?,
+                    };
+
+                    if insert {
+                        self.emulator.add_watchpoint(watchpoint);
+                        self.watchpoints.push(watchpoint);
+                    } else {
+                        self.emulator.remove_watchpoint(watchpoint);
+                        self.watchpoints.retain(|other| *other != watchpoint);
+                    }
+                }
+            }
+            "OK".to_string()
+        } else {
+            // an empty reply tells the debugger the packet isn't supported
+            String::new()
+        };
+
+        Some(reply)
+    }
+
+    fn monitor(&mut self, command: &str) -> String {
+        let words: Vec<&str> = command.split_whitespace().collect();
+
+        let result = match words.as_slice() {
+            ["break", location] => parse_location(self.emulator, location).map(|symbol| {
+                // only rom is banked by the cartridge, anywhere else the address is enough
+                if (0x4000..0x8000).contains(&symbol.adder) {
+                    self.banked_breakpoints.insert((symbol.bank, symbol.adder));
+                    format!("breakpoint at {:02x}:{:04x}\n", symbol.bank, symbol.adder)
+                } else {
+                    self.breakpoints.insert(symbol.adder);
+                    format!("breakpoint at {:04x}\n", symbol.adder)
+                }
+            }),
+            ["delete", location] => parse_location(self.emulator, location).map(|symbol| {
+                self.banked_breakpoints.remove(&(symbol.bank, symbol.adder));
+                self.breakpoints.remove(&symbol.adder);
+                String::new()
+            }),
+            ["sym", location] => parse_location(self.emulator, location).map(|symbol| {
+                match self.emulator.symbols().symbolize(symbol.bank, symbol.adder) {
+                    Some(label) => format!("{:02x}:{:04x} {}\n", symbol.bank, symbol.adder, label),
+                    None => format!("{:02x}:{:04x}\n", symbol.bank, symbol.adder),
+                }
+            }),
+            ["where"] => {
+                let mut output = format!("pc {}\n", self.emulator.describe(self.emulator.registers().pc));
+                output += &self.emulator.describe_backtrace();
+                if let Some(lock_up) = self.emulator.lock_up() {
+                    output += &format!("locked up on illegal opcode {:02x} at {}\n", lock_up.opcode, self.emulator.describe(lock_up.adder));
+                }
+                Ok(output)
+            }
+            _ => Ok(MONITOR_HELP.to_string()),
+        };
+
+        match result {
+            Ok(output) => output,
+            Err(e) => format!("{}\n", e),
+        }
+    }
+
+    fn at_breakpoint(&self) -> bool {
+        let pc = self.emulator.registers().pc;
+
+        self.breakpoints.contains(&pc)
+            || (!self.banked_breakpoints.is_empty() && self.banked_breakpoints.contains(&(self.emulator.bank(pc), pc)))
+    }
+
+    // runs until a breakpoint, a watchpoint or a ctrl-c, or a single instruction when stepping
+    fn resume(&mut self, step: bool) -> io::Result<String> {
+        let mut instructions: u32 = 0;
+
+        let stop = loop {
+            match self.emulator.step() {
+                Some(DebugEvent::Watch(hit)) => break Stop::Watch(hit),
+                Some(DebugEvent::LockUp(_)) => break Stop::LockUp,
+                None => {}
+            }
+
+            if step {
+                break Stop::Step;
+            }
+
+            if self.at_breakpoint() {
+                break Stop::Breakpoint;
+            }
+
+            instructions += 1;
+            // a quit stops the game like a ctrl-c, the next read then ends the session
+            if instructions % INTERRUPT_CHECK_INTERVAL == 0 && (self.interrupted()? || (self.quit)()) {
+                break Stop::Interrupted;
+            }
+        };
+
+        let reply = match stop {
+            Stop::Step => format!("S{:02x}", SIGTRAP),
+            Stop::Breakpoint => format!("T{:02x}swbreak:;", SIGTRAP),
+            Stop::Watch(hit) => {
+                let kind = match hit.watchpoint.kind {
+                    WatchKind::Write => "watch",
+                    WatchKind::Read => "rwatch",
+                    WatchKind::Access => "awatch",
+                };
+                format!("T{:02x}{}:{:04x};", SIGTRAP, kind, hit.adder)
+            }
+            Stop::LockUp => format!("S{:02x}", SIGILL),
+            Stop::Interrupted => format!("S{:02x}", SIGINT),
+        };
+
+        Ok(reply)
+    }
+
+    // looks for a ctrl-c without blocking, anything else that arrived is kept for read_packet
+    fn interrupted(&mut self) -> io::Result<bool> {
+        let mut buffer = [0u8; 64];
+        self.stream.set_nonblocking(true)?;
+        let read = self.stream.read(&mut buffer);
+        self.stream.set_nonblocking(false)?;
+
+        match read {
+            Ok(0) => Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(len) => {
+                let interrupted = buffer[..len].contains(&INTERRUPT);
+                self.pending.extend(buffer[..len].iter().filter(|byte| **byte != INTERRUPT));
+                Ok(interrupted)
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn next_byte(&mut self) -> io::Result<Option<u8>> {
+        if !self.pending.is_empty() {
+            return Ok(Some(self.pending.remove(0)));
+        }
+
+        // quitting while waiting on the debugger counts as it hanging up
+        let mut byte = [0u8];
+        loop {
+            match self.stream.read(&mut byte) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(byte[0])),
+                Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                    if (self.quit)() {
+                        return Ok(None);
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // $<data>#<checksum>, acks and stray ctrl-cs between packets are skipped. None once the debugger hangs up
+    fn read_packet(&mut self) -> io::Result<Option<String>> {
+        loop {
+            match self.next_byte()? {
+                None => return Ok(None),
+                Some(b'$') => {}
+                Some(_) => continue,
+            }
+
+            let mut data = Vec::new();
+            loop {
+                match self.next_byte()? {
+                    None => return Ok(None),
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                }
+            }
+
+            let mut sum = [0u8; 2];
+            for digit in sum.iter_mut() {
+                match self.next_byte()? {
+                    None => return Ok(None),
+                    Some(byte) => *digit = byte,
+                }
+            }
+
+            let valid = std::str::from_utf8(&sum)
+                .ok()
+                .and_then(|sum| u8::from_str_radix(sum, 16).ok())
+                .is_some_and(|sum| sum == checksum(&data));
+
+            if self.ack {
+                self.stream.write_all(if valid { b"+" } else { b"-" })?;
+            }
+
+            if valid {
+                return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+            }
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${}#{:02x}", data, checksum(data.as_bytes()));
+        self.stream.write_all(packet.as_bytes())?;
+
+        // the ack is read back with the next packet, there is nothing to do about a nack anyway
+        Ok(())
+    }
+
+    fn clear_watchpoints(&mut self) {
+        for watchpoint in self.watchpoints.drain(..) {
+            self.emulator.remove_watchpoint(watchpoint);
+        }
+    }
+}
@@ -19,13 +19,95 @@ pub enum Key {
     Start,
 }
 
-pub trait Hardware {
+// the state of every key at once, the emulator latches it instead of asking the hardware key by key
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct ButtonState {
+    pub right: bool,
+    pub left: bool,
+    pub up: bool,
+    pub down: bool,
+    pub a: bool,
+    pub b: bool,
+    pub select: bool,
+    pub start: bool,
+}
+
+impl ButtonState {
+    pub fn pressed(&self, key: Key) -> bool {
+        match key {
+            Key::Right => self.right,
+            Key::Left => self.left,
+            Key::Up => self.up,
+            Key::Down => self.down,
+            Key::A => self.a,
+            Key::B => self.b,
+            Key::Select => self.select,
+            Key::Start => self.start,
+        }
+    }
+
+    pub fn set(&mut self, key: Key, pressed: bool) {
+        match key {
+            Key::Right => self.right = pressed,
+            Key::Left => self.left = pressed,
+            Key::Up => self.up = pressed,
+            Key::Down => self.down = pressed,
+            Key::A => self.a = pressed,
+            Key::B => self.b = pressed,
+            Key::Select => self.select = pressed,
+            Key::Start => self.start = pressed,
+        }
+    }
+
+    // a set bit is a pressed key, the low nibble is right, left, up, down and the high one a, b, select, start.
+    // the nibbles line up with the p1 register
+    pub fn to_bits(&self) -> u8 {
+        let keys = [self.right, self.left, self.up, self.down, self.a, self.b, self.select, self.start];
+
+        keys.iter().enumerate().fold(0, |bits, (bit, pressed)| bits | ((*pressed as u8) << bit))
+    }
+
+    pub fn from_bits(bits: u8) -> ButtonState {
+        let pressed = |bit: u8| bits & (1 << bit) != 0;
+
+        ButtonState {
+            right: pressed(0),
+            left: pressed(1),
+            up: pressed(2),
+            down: pressed(3),
+            a: pressed(4),
+            b: pressed(5),
+            select: pressed(6),
+            start: pressed(7),
+        }
+    }
+
+    // asks the hardware about every key
+    pub fn read(hw: &mut dyn Hardware) -> ButtonState {
+        let mut buttons = ButtonState::default();
+
+        for key in [Key::Right, Key::Left, Key::Up, Key::Down, Key::A, Key::B, Key::Select, Key::Start].iter() {
+            buttons.set(key.clone(), hw.joypad_pressed(key.clone()));
+        }
+
+        buttons
+    }
+}
+
+// the emulator can be moved to a worker thread so the hardware it drives has to be sendable as well
+pub trait Hardware: Send {
     fn draw_line(&mut self, line: usize, buffer: &[u32]);
 
     fn joypad_pressed(&mut self, key: Key) -> bool;
 
     fn clock(&mut self) -> Duration;
 
+    // a host that shows the game as it runs returns true, the emulator then sleeps once a frame to keep to
+    // the set speed. otherwise run_frame and friends run as fast as they can whatever the speed is
+    fn paced(&mut self) -> bool {
+        false
+    }
+
     // returns the pcm sample rate on hz
     fn pcm_sample_rate(&mut self) -> u32;
 
@@ -1,6 +1,5 @@
 use crate::mmu::*;
-use core::cell::RefCell;
-use std::rc::Rc;
+use crate::state::{SaveState, StateReader, StateWriter};
 
 
 
@@ -41,72 +40,52 @@ impl Interrupts {
 }
 
 
-// interrupt request
-
-#[derive(Clone)]
-pub struct Irq {
-    request: Rc<RefCell<Interrupts>>
+// interrupt controller I know its a bad name...
+// components raise their interrupt request lines directly on it while they cycle
+#[derive(Default)]
+pub struct Ic {
+    enabled: Interrupts,
+    line: Interrupts,
 }
 
-impl Irq {
-    fn new(request: Rc<RefCell<Interrupts>>) -> Irq {
-        Irq {
-            request
-        }
-    }
 
-    pub fn v_blank(&self, v: bool) {
-        self.request.borrow_mut().v_blank = v;
+impl Ic {
+    pub fn new() -> Ic {
+        Ic::default()
     }
 
-    pub fn lcd_stat(&self, v: bool) {
-        self.request.borrow_mut().lcd_stat = v;
+    pub fn v_blank(&mut self, v: bool) {
+        self.line.v_blank = v;
     }
 
-    pub fn timer(&self, v: bool) {
-        self.request.borrow_mut().timer = v;
+    pub fn lcd_stat(&mut self, v: bool) {
+        self.line.lcd_stat = v;
     }
 
-    pub fn serial(&self, v: bool) {
-        self.request.borrow_mut().serial = v;
+    pub fn timer(&mut self, v: bool) {
+        self.line.timer = v;
     }
 
-    pub fn joypad(&self, v: bool) {
-        self.request.borrow_mut().joypad = v;
+    pub fn serial(&mut self, v: bool) {
+        self.line.serial = v;
     }
-}
-
-// interrupt controller I know its a bad name...
-pub struct Ic {
-    enabled: Rc<RefCell<Interrupts>>,
-    line: Rc<RefCell<Interrupts>>
-}
 
-
-impl Ic {
-    pub fn new() -> Ic {
-        Ic {
-            enabled: Rc::new(RefCell::new(Interrupts::default())),
-            line: Rc::new(RefCell::new(Interrupts::default())),
-        }
-    }
-
-    pub fn get_requester(&self) -> Irq {
-        Irq::new(self.line.clone())
+    pub fn joypad(&mut self, v: bool) {
+        self.line.joypad = v;
     }
 
-    pub fn peek(&self) -> Option<u8> {
+    pub fn peek(&mut self) -> Option<u8> {
         self.read(false)
     }
 
-    pub fn consume(&self) -> Option<u8> {
+    pub fn consume(&mut self) -> Option<u8> {
         self.read(true)
     }
 
 
-    fn read(&self, consume: bool) -> Option<u8> {
-        let enabled = self.enabled.borrow();
-        let mut line = self.line.borrow_mut();
+    fn read(&mut self, consume: bool) -> Option<u8> {
+        let enabled = &self.enabled;
+        let line = &mut self.line;
 
         if enabled.v_blank && line.v_blank {
             line.v_blank = !consume;
@@ -130,25 +109,37 @@ impl Ic {
 }
 
 impl IoDevice for Ic {
-    fn read_byte(&mut self, _: &Mmu, adder: u16) -> MemRead {
+    fn peek_byte(&self, adder: u16) -> MemRead {
         if adder == 0xFFFF {
-            MemRead::Read(self.enabled.borrow().get())
+            MemRead::Read(self.enabled.get())
         } else if adder == 0xFF0F {
-            MemRead::Read(self.line.borrow().get())
+            MemRead::Read(self.line.get())
         } else {
             MemRead::Ignore
         }
     }
 
-    fn write_byte(&mut self, _: &Mmu, adder: u16, val: u8) -> MemWrite { 
+    fn write_byte(&mut self, adder: u16, val: u8) -> MemWrite {
         if adder == 0xFFFF {
-            self.enabled.borrow_mut().set(val);
+            self.enabled.set(val);
             MemWrite::Write
         } else if adder == 0xFF0F {
-            self.line.borrow_mut().set(val);
+            self.line.set(val);
             MemWrite::Write
         } else {
             MemWrite::Ignore
         }
     }
-}
\ No newline at end of file
+}
+
+impl SaveState for Ic {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.enabled.get());
+        state.write_u8(self.line.get());
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) {
+        self.enabled.set(state.read_u8());
+        self.line.set(state.read_u8());
+    }
+}
@@ -1,81 +1,97 @@
 use crate::mmu::MemWrite;
 use crate::mmu::MemRead;
-use crate::mmu::Mmu;
 use crate::mmu::IoDevice;
-use crate::ic::Irq;
-use crate::hardware::{ Hardware, Key};
-use std::cell::RefCell;
-use std::rc::Rc;
+use crate::ic::Ic;
+use crate::state::{SaveState, StateReader, StateWriter};
+use crate::hardware::ButtonState;
+
+const SELECT_DIRECTIONS: u8 = 0x10;
+const SELECT_BUTTONS: u8 = 0x20;
 
 pub struct Joypad {
-    hw: Rc<RefCell<Box<dyn Hardware>>>,
-    irq: Irq,
-    select: u8,
-    pressed: u8,
+    select: u8, // bits 4 and 5 as last written, a cleared bit selects the group
+    keys: u8, // latched input, see ButtonState::to_bits
+    block_opposite_directions: bool,
 }
 
 impl Joypad {
-    pub fn new(hw: Rc<RefCell<Box<dyn Hardware>>>, irq: Irq) -> Joypad {
+    pub fn new(block_opposite_directions: bool) -> Joypad {
+        // both groups start selected, p1 reads 0xcf until the game picks one
         Joypad {
-            hw,
-            irq,
-            select: 0xff,
-            pressed: 0x0f,
+            select: 0,
+            keys: 0,
+            block_opposite_directions,
         }
     }
 
-    pub fn poll(&mut self) {
-        let pressed = self.check();
+    // latches new input, the joypad interrupt fires when a selected key goes down
+    pub fn set_input(&mut self, buttons: ButtonState, ic: &mut Ic) {
+        let mut buttons = buttons;
 
-        for i in 0..4 {
-            let bit = 1 << i;
-            if self.pressed & bit != 0 && pressed & bit == 0 {
-                self.irq.joypad(true);
-                break;
+        // a physical d-pad can't press both sides at once, some games break when it happens
+        if self.block_opposite_directions {
+            if buttons.left && buttons.right {
+                buttons.left = false;
+                buttons.right = false;
+            }
+            if buttons.up && buttons.down {
+                buttons.up = false;
+                buttons.down = false;
             }
         }
 
-        self.pressed = pressed;
-    }
+        let before = self.lines();
+        self.keys = buttons.to_bits();
 
-    fn check(&self) -> u8 {
-        let p = |key| self.hw.borrow_mut().joypad_pressed(key);
+        // the lines are active low, so a press is a bit going from 1 to 0
+        if before & !self.lines() != 0 {
+            ic.joypad(true);
+        }
+    }
 
-        let mut value = 0;
+    // the low nibble of p1, every selected group pulls the lines of its pressed keys low
+    fn lines(&self) -> u8 {
+        let mut pressed = 0;
 
-        if self.select & 0x10 == 0 {
-            value |= if p(Key::Right) { 0x00 } else { 0x01 };
-            value |= if p(Key::Left) { 0x00 } else { 0x02 };
-            value |= if p(Key::Up) { 0x00 } else { 0x04 };
-            value |= if p(Key::Down) { 0x00 } else { 0x08 };
-        } else if self.select & 0x20 == 0 {
-            value |= if p(Key::A) { 0x00 } else { 0x01 };
-            value |= if p(Key::B) { 0x00 } else { 0x02 };
-            value |= if p(Key::Select) { 0x00 } else { 0x04 };
-            value |= if p(Key::Start) { 0x0 } else { 0x08 };
-        } else {
-            value = 0x0f;
+        if self.select & SELECT_DIRECTIONS == 0 {
+            pressed |= self.keys & 0x0f;
+        }
+        if self.select & SELECT_BUTTONS == 0 {
+            pressed |= self.keys >> 4;
         }
 
-        value
+        !pressed & 0x0f
     }
 }
 
 impl IoDevice for Joypad {
-    fn read_byte(&mut self, _: &Mmu, addr: u16) -> MemRead {
+    fn peek_byte(&self, addr: u16) -> MemRead {
         if addr == 0xff00 {
-            MemRead::Read(self.check())
+            // the top two bits aren't connected and always read as set
+            MemRead::Read(0xc0 | self.select | self.lines())
         } else {
             MemRead::Ignore
         }
     }
-    fn write_byte(&mut self, _: &Mmu, addr: u16, value: u8) -> MemWrite {
+    fn write_byte(&mut self, addr: u16, value: u8) -> MemWrite {
         if addr == 0xff00 {
-            self.select = value & 0xf0;
+            self.select = value & (SELECT_DIRECTIONS | SELECT_BUTTONS);
             MemWrite::Write
         } else {
             MemWrite::Ignore
         }
-        
+
+    }
+}
+
+impl SaveState for Joypad {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.select);
+        state.write_u8(self.keys);
     }
-}
\ No newline at end of file
+
+    fn load_state(&mut self, state: &mut StateReader) {
+        self.select = state.read_u8() & (SELECT_DIRECTIONS | SELECT_BUTTONS);
+        self.keys = state.read_u8();
+    }
+}
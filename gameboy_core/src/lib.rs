@@ -1,9 +1,23 @@
+// the crate checks for multiples with % like it always has, is_multiple_of would need rust 1.87
+#![allow(clippy::manual_is_multiple_of)]
+
 pub mod hardware;
 pub mod emulator;
+pub mod movie;
+pub mod cheat_search;
+pub mod cheats;
+pub mod patch;
+pub mod gdb;
+pub mod symbols;
+pub mod profiler;
+pub mod cdl;
+pub mod serial;
+pub mod printer;
 
 mod utils;
 mod processor;
 mod mmu;
+mod bus;
 mod ic;
 mod ram;
 mod ppu;
@@ -11,4 +25,9 @@ mod timer;
 mod cartridge_controller;
 mod joypad;
 mod frequency_controller;
-mod apu;
\ No newline at end of file
+// the apu only keeps its registers for now, nothing drives it from the emulator loop yet
+#[allow(dead_code)]
+mod apu;
+mod post_boot;
+mod state;
+mod rewind;
\ No newline at end of file
@@ -1,13 +1,22 @@
-use std::cell::RefCell;
-use std::rc::Rc;
-use std::collections::HashMap;
+use crate::bus::{Bus, IoTarget};
+use crate::cdl::{self, CodeDataLog};
+use crate::emulator::{WatchHit, Watchpoint};
 use crate::utils;
 
+const PAGE_COUNT: usize = 0x100;
+const IO_PAGE_ADDER: u16 = 0xFF00;
+
 // io devices that can be mapped to memory
 pub trait IoDevice {
-    fn read_byte(&mut self, mmu: &Mmu, addr: u16) -> MemRead;
+    // a read by the cpu, devices where reading changes state override this
+    fn read_byte(&mut self, addr: u16) -> MemRead {
+        self.peek_byte(addr)
+    }
+
+    // a read without side effects, for debuggers
+    fn peek_byte(&self, addr: u16) -> MemRead;
 
-    fn write_byte(&mut self, mmu: &Mmu, addr: u16, value: u8) -> MemWrite;
+    fn write_byte(&mut self, addr: u16, value: u8) -> MemWrite;
 }
 
 
@@ -28,50 +37,283 @@ pub enum MemWrite {
     Ignore,
 }
 
+// what lives behind every 256 byte page of the address space
+#[derive(Clone, Copy)]
+enum Region {
+    Rom,
+    Vram,
+    CartridgeRam,
+    Wram,
+    Echo,
+    Oam,
+    Io,
+    // a single device behind the whole address space, see Mmu::flat
+    #[cfg_attr(not(test), allow(dead_code))]
+    Flat,
+}
 
+impl Region {
+    fn of_page(page: usize) -> Region {
+        match page {
+            0x00 ..= 0x7F => Region::Rom,
+            0x80 ..= 0x9F => Region::Vram,
+            0xA0 ..= 0xBF => Region::CartridgeRam,
+            0xC0 ..= 0xDF => Region::Wram,
+            0xE0 ..= 0xFD => Region::Echo,
+            0xFE => Region::Oam,
+            _ => Region::Io,
+        }
+    }
+}
+
+
+// memory is accessed through a page table, plain memory regions go straight to the owning component
+// and only the io register page is dispatched to the registered io devices.
 pub struct Mmu {
-    memory_mapped_devices: HashMap<u16, Vec<Rc<RefCell<dyn IoDevice>>>>
+    page_table: Vec<Region>,
+
+    pub bus: Bus,
+
+    io_map: Vec<Option<IoTarget>>, // the bus component for every address in the io page
+
+    pub watchpoints: Vec<Watchpoint>,
+    pub watch_hit: Option<WatchHit>, // the first watchpoint hit since the debugger cleared it
+
+    pub cdl: Option<CodeDataLog>, // how every rom byte was read, while logging
+
+    flat: Option<Box<dyn IoDevice + Send>>,
 }
 
 
 impl Mmu {
 
-    pub fn new() -> Mmu {
+    pub fn new(bus: Bus) -> Mmu {
         Mmu {
-            memory_mapped_devices: HashMap::new(),
+            page_table: (0..PAGE_COUNT).map(Region::of_page).collect(),
+            bus,
+            io_map: vec![None; PAGE_COUNT],
+            watchpoints: Vec::new(),
+            watch_hit: None,
+            cdl: None,
+            flat: None,
         }
     }
 
-    pub fn register_device<T>(&mut self, range: (u16, u16), io_device: Rc<RefCell<T>>) where T: IoDevice + 'static {
-        for i in range.0 ..= range.1 {
-            if self.memory_mapped_devices.contains_key(&i) {
-                match self.memory_mapped_devices.get_mut(&i) {
-                    Some(v) => v.push(io_device.clone()),
-                    None => unreachable!(),
-                }
-            } else {
-                self.memory_mapped_devices.insert(i, vec![io_device.clone()]);
+    // every address goes to memory, the bus is only there for the interrupt controller.
+    // cpu tests use it to run instructions against a plain 64kb of memory
+    #[cfg(test)]
+    pub fn flat(bus: Bus, memory: Box<dyn IoDevice + Send>) -> Mmu {
+        Mmu {
+            page_table: vec![Region::Flat; PAGE_COUNT],
+            flat: Some(memory),
+            ..Mmu::new(bus)
+        }
+    }
+
+    // map io registers to a device, if a register was already mapped the new device takes it over
+    pub fn register_device(&mut self, range: (u16, u16), target: IoTarget) {
+        assert!(range.0 >= IO_PAGE_ADDER, "only io registers can be mapped to io devices");
+
+        for addr in range.0 ..= range.1 {
+            self.io_map[(addr - IO_PAGE_ADDER) as usize] = Some(target);
+        }
+    }
+
+    #[cold]
+    fn check_watchpoints(&mut self, addr: u16, write: bool) {
+        if self.watch_hit.is_none() {
+            if let Some(watchpoint) = self.watchpoints.iter().find(|watchpoint| watchpoint.matches(addr, write)) {
+                self.watch_hit = Some(WatchHit {
+                    watchpoint: *watchpoint,
+                    adder: addr,
+                });
             }
         }
     }
 
-    pub fn read_byte(&self, addr: u16) -> u8 {
+    #[cold]
+    fn log_rom(&mut self, addr: u16, access: u8) {
+        if let (Some(cdl), Some(offset)) = (&mut self.cdl, self.bus.cartridge_controller.rom_offset(addr)) {
+            cdl.mark(offset, access);
+        }
+    }
+
+    // reads like read_byte but a rom byte is logged as access instead of data, for instruction fetches and dma
+    #[inline(always)]
+    pub fn read_as(&mut self, addr: u16, access: u8) -> u8 {
+        if self.cdl.is_none() {
+            return self.read_byte(addr);
+        }
 
+        self.logged_read(addr, access)
+    }
 
+    #[cold]
+    fn logged_read(&mut self, addr: u16, access: u8) -> u8 {
+        let cdl = self.cdl.take();
+        let value = self.read_byte(addr);
+        self.cdl = cdl;
+
+        self.log_rom(addr, access);
+
+        value
+    }
+
+    pub fn read_byte(&mut self, addr: u16) -> u8 {
+        if !self.watchpoints.is_empty() {
+            self.check_watchpoints(addr, false);
+        }
+
+        match self.page_table[(addr >> 8) as usize] {
+            Region::Rom | Region::CartridgeRam => {
+                if self.cdl.is_some() {
+                    self.log_rom(addr, cdl::DATA);
+                }
+
+                match self.bus.cartridge_controller.read_byte(addr) {
+                    MemRead::Read(data) => data,
+                    MemRead::Ignore => 0,
+                }
+            },
+            Region::Vram => self.bus.ppu.read_vram(addr),
+            Region::Wram => self.bus.ram.read_wram(addr),
+            Region::Echo => self.bus.ram.read_wram(addr - 0x2000),
+            Region::Oam => {
+                if addr <= 0xFE9F {
+                    self.bus.ppu.read_oma(addr)
+                } else {
+                    0
+                }
+            },
+            Region::Io => self.read_io(addr),
+            Region::Flat => match self.flat.as_mut().map(|memory| memory.read_byte(addr)) {
+                Some(MemRead::Read(data)) => data,
+                _ => 0,
+            },
+        }
+    }
 
-        match self.memory_mapped_devices.get(&addr) {
-            Some(devices) => {
-                for device in devices {
-                    match device.borrow_mut().read_byte(self, addr) {
-                        MemRead::Read(data) => return data,
-                        MemRead::Ignore => ()
+    // reads like the cpu would but never changes any state, for debuggers
+    pub fn peek_byte(&self, addr: u16) -> u8 {
+        match self.page_table[(addr >> 8) as usize] {
+            Region::Rom | Region::CartridgeRam => {
+                match self.bus.cartridge_controller.peek_byte(addr) {
+                    MemRead::Read(data) => data,
+                    MemRead::Ignore => 0,
+                }
+            },
+            Region::Vram => self.bus.ppu.read_vram(addr),
+            Region::Wram => self.bus.ram.read_wram(addr),
+            Region::Echo => self.bus.ram.read_wram(addr - 0x2000),
+            Region::Oam => {
+                if addr <= 0xFE9F {
+                    self.bus.ppu.read_oma(addr)
+                } else {
+                    0
+                }
+            },
+            Region::Io => match addr {
+                0xFF70 => self.bus.ram.read_bank_select(),
+                0xFF80 ..= 0xFFFE => self.bus.ram.read_hram(addr),
+                _ => {
+                    if let Some(target) = self.io_map[(addr - IO_PAGE_ADDER) as usize] {
+                        if let MemRead::Read(data) = self.bus.io_device_ref(target).peek_byte(addr) {
+                            return data;
+                        }
                     }
+
+                    Mmu::unmapped_io(addr)
+                }
+            },
+            Region::Flat => match self.flat.as_ref().map(|memory| memory.peek_byte(addr)) {
+                Some(MemRead::Read(data)) => data,
+                _ => 0,
+            },
+        }
+    }
+
+    // writes straight into the memory behind an address without any side effect, for debuggers.
+    // the rom, the mbc registers and the io registers are left alone, false when nothing was written
+    pub fn poke_byte(&mut self, addr: u16, value: u8) -> bool {
+        match self.page_table[(addr >> 8) as usize] {
+            Region::Rom | Region::CartridgeRam => {
+                matches!(self.bus.cartridge_controller.poke_byte(addr, value), MemWrite::Write)
+            },
+            Region::Vram => {
+                self.bus.ppu.write_vram(addr, value);
+                true
+            },
+            Region::Wram => {
+                self.bus.ram.write_wram(addr, value);
+                true
+            },
+            Region::Echo => {
+                self.bus.ram.write_wram(addr - 0x2000, value);
+                true
+            },
+            Region::Oam if addr <= 0xFE9F => {
+                self.bus.ppu.write_oma(addr, value);
+                true
+            },
+            Region::Oam => false,
+            Region::Io => match addr {
+                0xFF80 ..= 0xFFFE => {
+                    self.bus.ram.write_hram(addr, value);
+                    true
+                },
+                _ => false,
+            },
+            Region::Flat => match self.flat.as_mut() {
+                Some(memory) => matches!(memory.write_byte(addr, value), MemWrite::Write),
+                None => false,
+            },
+        }
+    }
+
+    pub fn write_byte(&mut self, addr: u16, value: u8) {
+        if !self.watchpoints.is_empty() {
+            self.check_watchpoints(addr, true);
+        }
+
+        match self.page_table[(addr >> 8) as usize] {
+            Region::Rom | Region::CartridgeRam => {
+                self.bus.cartridge_controller.write_byte(addr, value);
+            },
+            Region::Vram => self.bus.ppu.write_vram(addr, value),
+            Region::Wram => self.bus.ram.write_wram(addr, value),
+            Region::Echo => self.bus.ram.write_wram(addr - 0x2000, value),
+            Region::Oam => {
+                if addr <= 0xFE9F {
+                    self.bus.ppu.write_oma(addr, value);
                 }
             },
-            None => ()
+            Region::Io => self.write_io(addr, value),
+            Region::Flat => {
+                if let Some(memory) = self.flat.as_mut() {
+                    memory.write_byte(addr, value);
+                }
+            }
         }
+    }
 
-        // TODO move this to a a new device 
+    fn read_io(&mut self, addr: u16) -> u8 {
+        match addr {
+            0xFF70 => self.bus.ram.read_bank_select(),
+            0xFF80 ..= 0xFFFE => self.bus.ram.read_hram(addr),
+            _ => {
+                if let Some(target) = self.io_map[(addr - IO_PAGE_ADDER) as usize] {
+                    if let MemRead::Read(data) = self.bus.io_device(target).read_byte(addr) {
+                        return data;
+                    }
+                }
+
+                Mmu::unmapped_io(addr)
+            }
+        }
+    }
+
+    fn unmapped_io(addr: u16) -> u8 {
+        // TODO move this to a a new device
         // this is double speed controller that isn't implement but makes test fail.
         if addr == 0xFF4D {
             // tell the game is unsupported
@@ -79,33 +321,38 @@ impl Mmu {
         }
 
         // no device knows how to deal with read return 0
-        return 0;
+        0
     }
 
-    pub fn write_byte(&mut self, addr: u16, value: u8) {
-        let maybe_devices = self.memory_mapped_devices.get(&addr);
-        match maybe_devices {
-            Some(devices) => {
-                for device in devices {
-                    device.borrow_mut().write_byte(self, addr, value);
-                    
+    fn write_io(&mut self, addr: u16, value: u8) {
+        match addr {
+            0xFF70 => self.bus.ram.write_bank_select(value),
+            0xFF80 ..= 0xFFFE => self.bus.ram.write_hram(addr, value),
+            _ => {
+                // no device knows how deal with write do nothing.
+                if let Some(target) = self.io_map[(addr - IO_PAGE_ADDER) as usize] {
+                    self.bus.io_device(target).write_byte(addr, value);
                 }
-            },
-            None => ()
+            }
         }
+    }
 
-
-        // no device knows how deal with write do nothing.
-        // dbg!("no device knows how deal with write do nothing");
-        return;
+    // the bank mapped at addr, rom banks come from the cartridge and the upper wram from its bank register
+    pub fn bank(&self, addr: u16) -> usize {
+        match addr {
+            0x4000 ..= 0x7FFF => self.bus.cartridge_controller.rom_bank(),
+            0xD000 ..= 0xDFFF => self.bus.ram.read_bank_select() as usize,
+            _ => 0,
+        }
     }
 
-    pub fn read_word(&self, addr: u16) -> u16 {
-        utils::build_u16(self.read_byte(addr + 1), self.read_byte(addr))
+    pub fn read_word(&mut self, addr: u16) -> u16 {
+        let low = self.read_byte(addr);
+        utils::build_u16(self.read_byte(addr.wrapping_add(1)), low)
     }
 
     pub fn write_word(&mut self, addr: u16, value: u16) {
         self.write_byte(addr, utils::get_u16_low(value));
-        self.write_byte(addr + 1, utils::get_u16_high(value));
+        self.write_byte(addr.wrapping_add(1), utils::get_u16_high(value));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,129 @@
+use crate::emulator::EmulatorError;
+use crate::hardware::ButtonState;
+use crate::state::StateWriter;
+
+const MOVIE_MAGIC: &[u8; 4] = b"GBMV";
+const MOVIE_VERSION: u8 = 1;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum MovieStart {
+    // recording started on a freshly created emulator
+    PowerOn,
+
+    // recording started from this save state
+    SaveState(Vec<u8>),
+}
+
+// the keys held down on every frame of a recording, together with where the recording started.
+// the input of a frame is latched when the frame starts so playing it back is deterministic
+#[derive(Clone, PartialEq, Debug)]
+pub struct Movie {
+    pub header_checksum: u8, // of the rom the movie was recorded on
+    pub start: MovieStart,
+    pub frames: Vec<u8>, // one bit per key, see ButtonState::to_bits
+}
+
+impl Movie {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut movie = StateWriter::new();
+
+        movie.write_bytes(MOVIE_MAGIC);
+        movie.write_u8(MOVIE_VERSION);
+        movie.write_u8(self.header_checksum);
+
+        match &self.start {
+            MovieStart::PowerOn => movie.write_u8(0),
+            MovieStart::SaveState(state) => {
+                movie.write_u8(1);
+                movie.write_u32(state.len() as u32);
+                movie.write_bytes(state);
+            }
+        }
+
+        movie.write_u32(self.frames.len() as u32);
+        movie.write_bytes(&self.frames);
+
+        movie.finish()
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Movie, EmulatorError> {
+        let mut data = MovieReader { data };
+
+        if data.take(MOVIE_MAGIC.len())? != MOVIE_MAGIC {
+            return Err(EmulatorError::InvalidMovie("not a movie"));
+        }
+
+        if data.take(1)?[0] != MOVIE_VERSION {
+            return Err(EmulatorError::InvalidMovie("recorded by an incompatible version"));
+        }
+
+        let header_checksum = data.take(1)?[0];
+
+        let start = match data.take(1)?[0] {
+            0 => MovieStart::PowerOn,
+            1 => {
+                let len = data.read_u32()?;
+                MovieStart::SaveState(data.take(len)?.to_vec())
+            }
+            _ => return Err(EmulatorError::InvalidMovie("unknown start marker")),
+        };
+
+        let len = data.read_u32()?;
+        let frames = data.take(len)?.to_vec();
+
+        if !data.data.is_empty() {
+            return Err(EmulatorError::InvalidMovie("trailing data"));
+        }
+
+        Ok(Movie {
+            header_checksum,
+            start,
+            frames,
+        })
+    }
+}
+
+struct MovieReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> MovieReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], EmulatorError> {
+        if self.data.len() < len {
+            return Err(EmulatorError::InvalidMovie("truncated"));
+        }
+
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn read_u32(&mut self) -> Result<usize, EmulatorError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes) as usize)
+    }
+}
+
+pub(crate) enum MovieSession {
+    Recording(Movie),
+    Playing { movie: Movie, frame: usize },
+}
+
+impl MovieSession {
+    // the input for the frame that starts now, recording keeps the live input and playback replaces it.
+    // returns None once playback ran out of frames
+    pub(crate) fn next_frame(&mut self, live: ButtonState) -> Option<ButtonState> {
+        match self {
+            MovieSession::Recording(movie) => {
+                movie.frames.push(live.to_bits());
+                Some(live)
+            }
+            MovieSession::Playing { movie, frame } => {
+                let buttons = movie.frames.get(*frame).map(|bits| ButtonState::from_bits(*bits));
+                *frame += 1;
+                buttons
+            }
+        }
+    }
+}
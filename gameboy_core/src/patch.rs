@@ -0,0 +1,293 @@
+use crate::emulator::EmulatorError;
+
+// patches are applied to the rom before the cartridge controller sees it, so the header checks
+// run on the patched rom again when it is loaded
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: usize = 0x454F46; // "EOF" read as an offset
+const UPS_MAGIC: &[u8] = b"UPS1";
+const BPS_MAGIC: &[u8] = b"BPS1";
+
+// source, target and patch crcs at the end of ups and bps patches
+const FOOTER_SIZE: usize = 12;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PatchFormat {
+    Ips,
+    Ups,
+    Bps,
+}
+
+impl PatchFormat {
+    pub fn detect(patch: &[u8]) -> Option<PatchFormat> {
+        if patch.starts_with(IPS_MAGIC) {
+            Some(PatchFormat::Ips)
+        } else if patch.starts_with(UPS_MAGIC) {
+            Some(PatchFormat::Ups)
+        } else if patch.starts_with(BPS_MAGIC) {
+            Some(PatchFormat::Bps)
+        } else {
+            None
+        }
+    }
+}
+
+// returns the patched rom, the format is picked by the patch magic
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, EmulatorError> {
+    match PatchFormat::detect(patch) {
+        Some(PatchFormat::Ips) => apply_ips(rom, patch),
+        Some(PatchFormat::Ups) => apply_ups(rom, patch),
+        Some(PatchFormat::Bps) => apply_bps(rom, patch),
+        None => Err(EmulatorError::InvalidPatch("not an ips, ups or bps patch")),
+    }
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+
+    !crc
+}
+
+struct PatchReader<'a> {
+    patch: &'a [u8],
+    position: usize,
+}
+
+impl<'a> PatchReader<'a> {
+    fn new(patch: &'a [u8], position: usize) -> PatchReader<'a> {
+        PatchReader { patch, position }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], EmulatorError> {
+        let bytes = self
+            .patch
+            .get(self.position..self.position.saturating_add(len))
+            .ok_or(EmulatorError::InvalidPatch("patch is truncated"))?;
+        self.position += len;
+
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, EmulatorError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn big_endian(&mut self, len: usize) -> Result<usize, EmulatorError> {
+        Ok(self.bytes(len)?.iter().fold(0, |value, byte| value << 8 | *byte as usize))
+    }
+
+    fn u32(&mut self) -> Result<u32, EmulatorError> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    // the variable length numbers ups and bps use, 7 bits a byte with the last byte marked by the top bit
+    fn number(&mut self) -> Result<usize, EmulatorError> {
+        let mut number: usize = 0;
+        let mut shift: usize = 1;
+
+        loop {
+            let byte = self.byte()?;
+            number = (byte as usize & 0x7F)
+                .checked_mul(shift)
+                .and_then(|value| number.checked_add(value))
+                .ok_or(EmulatorError::InvalidPatch("number in patch is too big"))?;
+
+            if byte & 0x80 != 0 {
+                return Ok(number);
+            }
+
+            shift = shift.checked_shl(7).ok_or(EmulatorError::InvalidPatch("number in patch is too big"))?;
+            number = number.checked_add(shift).ok_or(EmulatorError::InvalidPatch("number in patch is too big"))?;
+        }
+    }
+}
+
+// records of a 3 byte offset and a 2 byte size, a zero size is a run of one byte instead
+fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, EmulatorError> {
+    let mut target = rom.to_vec();
+    let mut reader = PatchReader::new(patch, IPS_MAGIC.len());
+
+    loop {
+        let offset = reader.big_endian(3)?;
+        if offset == IPS_EOF {
+            break;
+        }
+
+        let size = reader.big_endian(2)?;
+        let (size, data) = if size == 0 {
+            let size = reader.big_endian(2)?;
+            (size, vec![reader.byte()?; size])
+        } else {
+            (size, reader.bytes(size)?.to_vec())
+        };
+
+        if target.len() < offset + size {
+            target.resize(offset + size, 0);
+        }
+        target[offset..offset + size].copy_from_slice(&data);
+    }
+
+    // some patches cut the rom short after the end marker
+    if let Ok(size) = reader.big_endian(3) {
+        target.truncate(size);
+    }
+
+    Ok(target)
+}
+
+// checks the crcs in the footer, returns the expected target crc
+fn check_footer(rom: &[u8], patch: &[u8]) -> Result<u32, EmulatorError> {
+    if patch.len() < FOOTER_SIZE {
+        return Err(EmulatorError::InvalidPatch("patch is truncated"));
+    }
+
+    let mut footer = PatchReader::new(patch, patch.len() - FOOTER_SIZE);
+    let source_crc = footer.u32()?;
+    let target_crc = footer.u32()?;
+    let patch_crc = footer.u32()?;
+
+    if crc32(&patch[..patch.len() - 4]) != patch_crc {
+        return Err(EmulatorError::InvalidPatch("patch crc doesn't match, the patch is corrupted"));
+    }
+
+    if crc32(rom) != source_crc {
+        return Err(EmulatorError::InvalidPatch("rom crc doesn't match the rom the patch was made for"));
+    }
+
+    Ok(target_crc)
+}
+
+fn check_target(target: &[u8], target_crc: u32) -> Result<(), EmulatorError> {
+    if crc32(target) != target_crc {
+        return Err(EmulatorError::InvalidPatch("patched rom crc doesn't match"));
+    }
+
+    Ok(())
+}
+
+// runs of bytes xored with the source, each run starts a number of bytes after the last one
+fn apply_ups(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, EmulatorError> {
+    let target_crc = check_footer(rom, patch)?;
+    let actions_end = patch.len() - FOOTER_SIZE;
+
+    let mut reader = PatchReader::new(&patch[..actions_end], UPS_MAGIC.len());
+    let source_size = reader.number()?;
+    let target_size = reader.number()?;
+
+    if source_size != rom.len() {
+        return Err(EmulatorError::InvalidPatch("rom size doesn't match the rom the patch was made for"));
+    }
+
+    let mut target = rom.to_vec();
+    target.resize(target_size, 0);
+
+    let mut offset: usize = 0;
+    while reader.position < actions_end {
+        offset = offset.saturating_add(reader.number()?);
+
+        loop {
+            let xor = reader.byte()?;
+            if xor == 0 {
+                offset += 1;
+                break;
+            }
+
+            let byte = target
+                .get_mut(offset)
+                .ok_or(EmulatorError::InvalidPatch("patch writes past the end of the rom"))?;
+            *byte ^= xor;
+            offset += 1;
+        }
+    }
+
+    check_target(&target, target_crc)?;
+
+    Ok(target)
+}
+
+// actions that copy from the source, the patch or the target built so far
+fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, EmulatorError> {
+    const SOURCE_READ: usize = 0;
+    const TARGET_READ: usize = 1;
+    const SOURCE_COPY: usize = 2;
+
+    let target_crc = check_footer(rom, patch)?;
+    let actions_end = patch.len() - FOOTER_SIZE;
+
+    let mut reader = PatchReader::new(&patch[..actions_end], BPS_MAGIC.len());
+    let source_size = reader.number()?;
+    let target_size = reader.number()?;
+    let metadata_size = reader.number()?;
+    reader.bytes(metadata_size)?;
+
+    if source_size != rom.len() {
+        return Err(EmulatorError::InvalidPatch("rom size doesn't match the rom the patch was made for"));
+    }
+
+    let out_of_range = EmulatorError::InvalidPatch("patch copies from outside the rom");
+    let mut target: Vec<u8> = Vec::with_capacity(target_size);
+    let mut source_offset: usize = 0;
+    let mut target_offset: usize = 0;
+
+    // copy offsets move relative to where the last copy ended, the lowest bit is the sign
+    let relative = |offset: usize, reader: &mut PatchReader| -> Result<usize, EmulatorError> {
+        let delta = reader.number()?;
+        if delta & 1 != 0 {
+            offset.checked_sub(delta >> 1)
+        } else {
+            offset.checked_add(delta >> 1)
+        }
+        .ok_or(EmulatorError::InvalidPatch("patch copies from outside the rom"))
+    };
+
+    while reader.position < actions_end {
+        let action = reader.number()?;
+        let len = (action >> 2) + 1;
+
+        if target.len() + len > target_size {
+            return Err(EmulatorError::InvalidPatch("patch writes past the end of the rom"));
+        }
+
+        match action & 0x3 {
+            SOURCE_READ => {
+                let start = target.len();
+                let bytes = rom.get(start..start + len).ok_or_else(|| out_of_range.clone())?;
+                target.extend_from_slice(bytes);
+            }
+            TARGET_READ => target.extend_from_slice(reader.bytes(len)?),
+            SOURCE_COPY => {
+                source_offset = relative(source_offset, &mut reader)?;
+                let bytes = rom
+                    .get(source_offset..source_offset.saturating_add(len))
+                    .ok_or_else(|| out_of_range.clone())?;
+                target.extend_from_slice(bytes);
+                source_offset += len;
+            }
+            _ => {
+                // a target copy can overlap the bytes it writes, so it goes a byte at a time
+                target_offset = relative(target_offset, &mut reader)?;
+                for _ in 0..len {
+                    let byte = *target.get(target_offset).ok_or_else(|| out_of_range.clone())?;
+                    target.push(byte);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+
+    if target.len() != target_size {
+        return Err(EmulatorError::InvalidPatch("patched rom is shorter than the patch says"));
+    }
+
+    check_target(&target, target_crc)?;
+
+    Ok(target)
+}
@@ -0,0 +1,138 @@
+use crate::emulator::GameBoyMode;
+use crate::mmu::Mmu;
+
+const LOGO_ADDER: u16 = 0x0104;
+const LOGO_SIZE: u16 = 0x30;
+
+// the boot rom unpacks the logo starting from the second tile
+const LOGO_TILES_ADDER: u16 = 0x8010;
+const REGISTERED_TILE_ADDER: u16 = 0x8190;
+
+const REGISTERED_TILE: [u8; 8] = [0x3C, 0x42, 0xB9, 0xA5, 0xB9, 0xA5, 0x42, 0x3C];
+
+const LOGO_TOP_ROW_ADDER: u16 = 0x9904;
+const LOGO_BOTTOM_ROW_ADDER: u16 = 0x9924;
+const LOGO_ROW_LEN: u8 = 12;
+const REGISTERED_MAP_ADDER: u16 = 0x9910;
+const REGISTERED_TILE_INDEX: u8 = 0x19;
+
+// io registers as the boot rom leaves them, in the order they should be written.
+// sound is powered on first so the rest of the sound registers stick, and the v-blank the boot rom
+// waited for last is still requested.
+const CLASSIC_IO_REGISTERS: [(u16, u8); 30] = [
+    (0xFF26, 0xF1),
+    (0xFF05, 0x00),
+    (0xFF06, 0x00),
+    (0xFF07, 0x00),
+    (0xFF0F, 0xE1),
+    (0xFF10, 0x80),
+    (0xFF11, 0xBF),
+    (0xFF12, 0xF3),
+    (0xFF14, 0xBF),
+    (0xFF16, 0x3F),
+    (0xFF17, 0x00),
+    (0xFF19, 0xBF),
+    (0xFF1A, 0x7F),
+    (0xFF1B, 0xFF),
+    (0xFF1C, 0x9F),
+    (0xFF1E, 0xBF),
+    (0xFF20, 0xFF),
+    (0xFF21, 0x00),
+    (0xFF22, 0x00),
+    (0xFF23, 0xBF),
+    (0xFF24, 0x77),
+    (0xFF25, 0xF3),
+    (0xFF40, 0x91),
+    (0xFF42, 0x00),
+    (0xFF43, 0x00),
+    (0xFF45, 0x00),
+    (0xFF47, 0xFC),
+    (0xFF4A, 0x00),
+    (0xFF4B, 0x00),
+    (0xFFFF, 0x00),
+];
+
+// on top of the classic registers the color boot rom leaves the first wram bank selected
+const COLOR_IO_REGISTERS: [(u16, u8); 2] = [
+    (0xFF4F, 0x00),
+    (0xFF70, 0x01),
+];
+
+// bcps / ocps with auto increment and their data registers, 8 palettes of 4 colors of 2 bytes each
+const COLOR_PALETTE_REGISTERS: [(u16, u16); 2] = [(0xFF68, 0xFF69), (0xFF6A, 0xFF6B)];
+const COLOR_PALETTE_SIZE: usize = 0x40;
+const AUTO_INCREMENT: u8 = 0x80;
+
+// bring the machine to the state the boot rom leaves it in right before jumping to 0x100
+pub fn skip_boot_rom(mmu: &mut Mmu, game_boy_mode: GameBoyMode) {
+    for &(addr, value) in CLASSIC_IO_REGISTERS.iter() {
+        mmu.write_byte(addr, value);
+    }
+
+    match game_boy_mode {
+        GameBoyMode::Classic => mmu.bus.timer.skip_boot_rom(),
+        GameBoyMode::Color => {
+            for &(addr, value) in COLOR_IO_REGISTERS.iter() {
+                mmu.write_byte(addr, value);
+            }
+
+            load_color_palettes(mmu);
+        }
+    }
+
+    load_logo(mmu);
+}
+
+// the color boot rom turns every background color white. it doesn't touch the object palettes of a
+// color game, they hold garbage on hardware and are white here as well
+fn load_color_palettes(mmu: &mut Mmu) {
+    for &(index_reg, data_reg) in COLOR_PALETTE_REGISTERS.iter() {
+        mmu.write_byte(index_reg, AUTO_INCREMENT);
+
+        for _ in 0..COLOR_PALETTE_SIZE {
+            mmu.write_byte(data_reg, 0xFF);
+        }
+    }
+}
+
+// stretch a nibble to a byte by doubling every bit, this is how the boot rom scales the logo
+fn double_nibble(nibble: u8) -> u8 {
+    let mut value = 0;
+
+    for bit in 0..4 {
+        if nibble & (1 << bit) != 0 {
+            value |= 0b11 << (bit * 2);
+        }
+    }
+
+    value
+}
+
+// unpack the logo from the cartridge header into vram and lay it out on the background map
+fn load_logo(mmu: &mut Mmu) {
+    let mut tile_adder = LOGO_TILES_ADDER;
+
+    for index in 0..LOGO_SIZE {
+        let value = mmu.read_byte(LOGO_ADDER + index);
+
+        // every nibble is two rows of the tile, only the low bit plane is used
+        for nibble in [value >> 4, value & 0xF].iter() {
+            let row = double_nibble(*nibble);
+
+            mmu.write_byte(tile_adder, row);
+            mmu.write_byte(tile_adder + 2, row);
+            tile_adder += 4;
+        }
+    }
+
+    for (index, row) in REGISTERED_TILE.iter().enumerate() {
+        mmu.write_byte(REGISTERED_TILE_ADDER + index as u16 * 2, *row);
+    }
+
+    for index in 0..LOGO_ROW_LEN {
+        mmu.write_byte(LOGO_TOP_ROW_ADDER + index as u16, index + 1);
+        mmu.write_byte(LOGO_BOTTOM_ROW_ADDER + index as u16, index + 1 + LOGO_ROW_LEN);
+    }
+
+    mmu.write_byte(REGISTERED_MAP_ADDER, REGISTERED_TILE_INDEX);
+}
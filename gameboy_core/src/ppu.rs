@@ -4,21 +4,20 @@ mod control_register;
 pub mod dma;
 mod sprite;
 mod status_register;
+mod viewer;
 
-use crate::emulator::GameBoyMode;
+use crate::emulator::{DebugLayers, GameBoyMode};
 use crate::hardware::Hardware;
 use crate::hardware::{DISPLAY_HIGHT, DISPLAY_WIDTH};
-use crate::ic::Irq;
-use crate::mmu::{IoDevice, MemRead, MemWrite, Mmu};
+use crate::ic::Ic;
+use crate::mmu::{IoDevice, MemRead, MemWrite};
 use crate::ppu::color::Color;
 use crate::ppu::color_palette::{ColorPalette, MonoColorPalette};
 use crate::ppu::control_register::ControlRegister;
-use crate::ppu::dma::DmaManager;
 use crate::ppu::sprite::Attributes;
 use crate::ppu::sprite::Sprite;
 use crate::ppu::status_register::StatusRegister;
-use std::cell::RefCell;
-use std::rc::Rc;
+use crate::state::{SaveState, StateReader, StateWriter};
 
 const H_BLINK_CLOCK_CYCLES: u32 = 87;
 const V_BLINK_CLOCK_CYCLES: u32 = 456;
@@ -29,7 +28,15 @@ const VRAM_BANK_SIZE: usize = 0x2000;
 const VRAM_BANK_COUNT: usize = 0x2;
 
 const OMA_TABLE_SIZE: usize = 0xA0;
-const OMA_TABLE_ADDER: u16 = 0xFE00;
+
+// debug overlay colors
+const DISABLED_LAYER_COLOR: u32 = 0xFFFFFFFF;
+const SPRITE_OUTLINE_COLOR: u32 = 0xFFFF00FF;
+const WINDOW_AREA_TINT: u32 = 0xFF0000FF;
+const SCANLINE_TINT: u32 = 0xFFFF0000;
+
+// color numbers 0 - 3 with the palettes turned off
+const RAW_SHADES: [u32; 4] = [0xFFFFFFFF, 0xFFAAAAAA, 0xFF555555, 0xFF000000];
 
 #[derive(PartialEq, Copy, Clone)]
 enum BackGroundColorPriority {
@@ -42,8 +49,8 @@ enum BackGroundColorPriority {
 pub enum PpuMode {
     HorizontalBlanking,
     VerticalBlanking,
-    OAM,
-    VRAM,
+    Oam,
+    Vram,
 }
 
 impl From<PpuMode> for u8 {
@@ -51,8 +58,8 @@ impl From<PpuMode> for u8 {
         match mode {
             PpuMode::HorizontalBlanking => 0,
             PpuMode::VerticalBlanking => 1,
-            PpuMode::OAM => 2,
-            PpuMode::VRAM => 3,
+            PpuMode::Oam => 2,
+            PpuMode::Vram => 3,
         }
     }
 }
@@ -62,8 +69,8 @@ impl From<u8> for PpuMode {
         match mode & 0b11 {
             0 => PpuMode::HorizontalBlanking,
             1 => PpuMode::VerticalBlanking,
-            2 => PpuMode::OAM,
-            3 => PpuMode::VRAM,
+            2 => PpuMode::Oam,
+            3 => PpuMode::Vram,
             _ => panic!("bad ppu mode"),
         }
     }
@@ -71,12 +78,13 @@ impl From<u8> for PpuMode {
 
 pub struct Ppu {
     clock: u32, // CPU clock cycles stored
-    irq: Irq,
-
-    hardware: Rc<RefCell<Box<dyn Hardware>>>,
 
     game_boy_mode: GameBoyMode,
 
+    // every finished line is kept here so the whole frame can be handed out at vblank
+    frame_buffer: Vec<u32>,
+    frame_count: u64,
+
     selected_vram_bank: usize,
     vram: Vec<Vec<u8>>,
     oma_table: Vec<u8>,
@@ -98,15 +106,18 @@ pub struct Ppu {
 
     bg_color_palette: ColorPalette,
     object_color_palette: ColorPalette,
+
+    // not part of the machine state, it stays as it is across save states
+    pub debug_layers: DebugLayers,
 }
 
 impl Ppu {
-    pub fn new(hw: Rc<RefCell<Box<dyn Hardware>>>, irq: Irq, game_boy_mode: GameBoyMode) -> Ppu {
+    pub fn new(game_boy_mode: GameBoyMode) -> Ppu {
         Ppu {
-            irq: irq,
-            hardware: hw,
-            game_boy_mode: game_boy_mode,
+            game_boy_mode,
             clock: 0,
+            frame_buffer: vec![0; DISPLAY_WIDTH * DISPLAY_HIGHT],
+            frame_count: 0,
             selected_vram_bank: 0,
             vram: vec![vec![0; VRAM_BANK_SIZE]; VRAM_BANK_COUNT],
             oma_table: vec![0; OMA_TABLE_SIZE],
@@ -127,10 +138,12 @@ impl Ppu {
 
             bg_color_palette: ColorPalette::new(),
             object_color_palette: ColorPalette::new(),
+
+            debug_layers: DebugLayers::default(),
         }
     }
 
-    pub fn cycle(&mut self, _mmu: &mut Mmu, clock: u32) {
+    pub fn cycle(&mut self, clock: u32, ic: &mut Ic, hardware: &mut dyn Hardware) {
         self.clock += clock;
 
         match self.status_register.mode {
@@ -141,19 +154,20 @@ impl Ppu {
 
                     // we reached bottom of screen switch to vblank
                     if self.line >= DISPLAY_HIGHT as u8 {
-                        self.irq.v_blank(true);
+                        self.frame_count += 1;
+                        ic.v_blank(true);
 
                         if self.status_register.v_blank_int {
-                            self.irq.lcd_stat(true);
+                            ic.lcd_stat(true);
                         }
 
                         self.status_register.mode = PpuMode::VerticalBlanking;
                     } else {
                         if self.status_register.oam_int {
-                            self.irq.lcd_stat(true);
+                            ic.lcd_stat(true);
                         }
 
-                        self.status_register.mode = PpuMode::OAM;
+                        self.status_register.mode = PpuMode::Oam;
                     }
                 }
             }
@@ -165,30 +179,32 @@ impl Ppu {
                     if self.line > 153 {
                         self.line = 0;
                         if self.status_register.oam_int {
-                            self.irq.lcd_stat(true);
+                            ic.lcd_stat(true);
                         }
 
-                        self.status_register.mode = PpuMode::OAM;
+                        self.status_register.mode = PpuMode::Oam;
                     }
                 }
             }
-            PpuMode::OAM => {
+            PpuMode::Oam => {
                 if self.clock >= OAM_CLOCK_CYCLES {
                     self.clock -= OAM_CLOCK_CYCLES;
 
-                    self.status_register.mode = PpuMode::VRAM;
+                    self.status_register.mode = PpuMode::Vram;
                 }
             }
-            PpuMode::VRAM => {
+            PpuMode::Vram => {
                 if self.clock >= VRAM_CLOCK_CYCLES {
                     self.clock -= VRAM_CLOCK_CYCLES;
 
-                    self.hardware
-                        .borrow_mut()
-                        .draw_line(self.line as usize, &self.draw_line());
+                    let line_vector = self.draw_line();
+                    let base = self.line as usize * DISPLAY_WIDTH;
+                    self.frame_buffer[base..base + DISPLAY_WIDTH].copy_from_slice(&line_vector);
+
+                    hardware.draw_line(self.line as usize, &line_vector);
 
                     if self.status_register.h_blank_int {
-                        self.irq.lcd_stat(true);
+                        ic.lcd_stat(true);
                     }
 
                     self.status_register.mode = PpuMode::HorizontalBlanking;
@@ -197,7 +213,7 @@ impl Ppu {
         };
 
         if self.status_register.coincidence_int && self.line == self.line_compare {
-            self.irq.lcd_stat(true);
+            ic.lcd_stat(true);
         }
     }
 
@@ -214,8 +230,13 @@ impl Ppu {
         let mut line_vector: Vec<u32> = vec![0; DISPLAY_WIDTH];
 
         let bg_vec = self.draw_background_and_window_line(&mut line_vector);
-        let sprite_vec = self.draw_sprites_line(&mut line_vector, &bg_vec);
+        self.draw_sprites_line(&mut line_vector, &bg_vec);
 
+        if self.debug_layers.scanline == Some(self.line) {
+            for pixel in line_vector.iter_mut() {
+                *pixel = blend(*pixel, SCANLINE_TINT);
+            }
+        }
 
         line_vector
     }
@@ -237,9 +258,28 @@ impl Ppu {
         (h | l) as usize
     }
 
+    // the color a palette gives a color number, or its raw shade when the palettes are turned off
+    fn shade(&self, palette: &[Color], color_index: usize) -> u32 {
+        if self.debug_layers.palettes {
+            palette[color_index].get_rgb_values()
+        } else {
+            RAW_SHADES[color_index]
+        }
+    }
+
+    // the background and window tile data is either unsigned from 0x8000 or signed around 0x9000
+    fn bg_tile_adder(&self, tile_index: u8) -> u16 {
+        if self.control_register.get_bg_tile_base_adder() == 0x8000 {
+            self.control_register.get_bg_tile_base_adder() + (tile_index as u16 * 16)
+        } else {
+            (self.control_register.get_bg_tile_base_adder())
+                .wrapping_add((0x800 + tile_index as i8 as i16 * 16) as u16)
+        }
+    }
+
     fn draw_background_and_window_line(
         &self,
-        line_vector: &mut Vec<u32>,
+        line_vector: &mut [u32],
     ) -> Vec<BackGroundColorPriority> {
         let mut gb_priority: Vec<BackGroundColorPriority> =
             vec![BackGroundColorPriority::NormalPriority; DISPLAY_WIDTH];
@@ -254,11 +294,18 @@ impl Ppu {
             return gb_priority;
         }
 
+        let layers = self.debug_layers;
+
         for x_index in 0..DISPLAY_WIDTH {
-            let (tile_map_base_adder, tile_y, tile_x, y_offset, x_offset) = if draw_win
+            let in_window = draw_win
                 && (self.line >= self.window_y_pos)
-                && (x_index as u16 + 7 >= self.window_x_pos as u16)
-            {
+                && (x_index as u16 + 7 >= self.window_x_pos as u16);
+
+            if layers.window_area && in_window {
+                line_vector[x_index] = WINDOW_AREA_TINT;
+            }
+
+            let (tile_map_base_adder, tile_y, tile_x, y_offset, x_offset) = if in_window && layers.window {
                 let y = (self.line - self.window_y_pos) as u16;
                 let x = x_index as u16 + 7 - (self.window_x_pos as u16); // x - (win - 7) I get sub overflow
 
@@ -269,6 +316,15 @@ impl Ppu {
                     y % 8,
                     x % 8,
                 )
+            } else if draw_bg && !layers.background {
+                // nothing in the background hides sprites
+                gb_priority[x_index] = BackGroundColorPriority::ColorZero;
+                line_vector[x_index] = if layers.window_area && in_window {
+                    blend(DISABLED_LAYER_COLOR, WINDOW_AREA_TINT)
+                } else {
+                    DISABLED_LAYER_COLOR
+                };
+                continue;
             } else if draw_bg {
                 let y = (self.line as u16 + self.y_scroll as u16) % 256;
                 let x = (x_index as u16 + self.x_scroll as u16) % 256;
@@ -295,12 +351,7 @@ impl Ppu {
 
             let tile_index = self.read_from_vram(0, tile_map_adder);
 
-            let tile_adder = if self.control_register.get_bg_tile_base_adder() == 0x8000 {
-                self.control_register.get_bg_tile_base_adder() + (tile_index as u16 * 16)
-            } else {
-                (self.control_register.get_bg_tile_base_adder())
-                    .wrapping_add((0x800 + tile_index as i8 as i16 * 16) as u16)
-            };
+            let tile_adder = self.bg_tile_adder(tile_index);
 
             let y_offset = if attributes.y_flip {
                 7 - y_offset
@@ -324,9 +375,13 @@ impl Ppu {
                 BackGroundColorPriority::NormalPriority
             };
 
-            let color = attributes.palette[color_index];
-            gb_priority[(x_index) as usize] = bg_prio;
-            line_vector[(x_index) as usize] = color.get_rgb_values();
+            let color = self.shade(attributes.palette, color_index);
+            gb_priority[x_index] = bg_prio;
+            line_vector[x_index] = if layers.window_area && in_window {
+                blend(color, WINDOW_AREA_TINT)
+            } else {
+                color
+            };
         }
 
         gb_priority
@@ -334,8 +389,8 @@ impl Ppu {
 
     fn draw_sprites_line(
         &self,
-        line_vector: &mut Vec<u32>,
-        bg_vector: &Vec<BackGroundColorPriority>,
+        line_vector: &mut [u32],
+        bg_vector: &[BackGroundColorPriority],
     ) {
         if !self.control_register.sprit_display_enabled {
             return;
@@ -384,6 +439,21 @@ impl Ppu {
                 (line - sprite.y) as u16
             };
 
+            if self.debug_layers.sprite_outlines {
+                let top_or_bottom = line == sprite.y || line == sprite.y + sprite_hight as i32 - 1;
+
+                for x in sprite.x..sprite.x + sprite_width as i32 {
+                    let edge = top_or_bottom || x == sprite.x || x == sprite.x + sprite_width as i32 - 1;
+                    if edge && x >= 0 && x < DISPLAY_WIDTH as i32 {
+                        line_vector[x as usize] = SPRITE_OUTLINE_COLOR;
+                    }
+                }
+            }
+
+            if !self.debug_layers.sprites {
+                continue;
+            }
+
             // Every tile is 16 bytes of mem and eve1ry line is 2 bytes aka 8 bytes
             let tile_adder = 0x8000 + (sprite.tile_index * 16) + (tile_y * 2);
             let tile_byte_low = self.read_from_vram(sprite.attributes.vram_bank, tile_adder);
@@ -410,7 +480,7 @@ impl Ppu {
                     continue;
                 }
 
-                let color = sprite.attributes.palette[color_index].get_rgb_values();
+                let color = self.shade(sprite.attributes.palette, color_index);
                 let x_index = (x_index as i32 + sprite.x) as usize;
                 
 
@@ -418,6 +488,11 @@ impl Ppu {
                     continue;
                 }
 
+                // the outline stays on top so overlapping sprites can still be told apart
+                if self.debug_layers.sprite_outlines && line_vector[x_index] == SPRITE_OUTLINE_COLOR {
+                    continue;
+                }
+
                 line_vector[x_index] = color;
             }
         }
@@ -427,6 +502,30 @@ impl Ppu {
         self.oma_table[addr as usize - 0xFE00]
     }
 
+    pub fn write_oma(&mut self, addr: u16, value: u8) {
+        self.oma_table[addr as usize - 0xFE00] = value;
+    }
+
+    // vram access from the cpu side goes to the selected bank
+    #[inline]
+    pub fn read_vram(&self, addr: u16) -> u8 {
+        self.read_from_vram(self.selected_vram_bank, addr)
+    }
+
+    #[inline]
+    pub fn write_vram(&mut self, addr: u16, value: u8) {
+        self.write_to_vram(self.selected_vram_bank, addr, value);
+    }
+
+    pub fn frame_buffer(&self) -> &[u32] {
+        &self.frame_buffer
+    }
+
+    // how many times the ppu entered vblank
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
     pub fn get_status(&self) -> (PpuMode, u32) {
         (self.status_register.mode, self.clock)
     }
@@ -436,11 +535,14 @@ impl Ppu {
     }
 }
 
+// half way between both colors
+fn blend(color: u32, tint: u32) -> u32 {
+    (((color & 0xFEFEFE) >> 1) + ((tint & 0xFEFEFE) >> 1)) | 0xFF000000
+}
+
 impl IoDevice for Ppu {
-    fn read_byte(&mut self, _mmu: &Mmu, adder: u16) -> MemRead {
+    fn peek_byte(&self, adder: u16) -> MemRead {
         match adder {
-            0x8000..=0x9FFF => MemRead::Read(self.read_from_vram(self.selected_vram_bank, adder)),
-            0xFE00..=0xFE9F => MemRead::Read(self.oma_table[adder as usize - 0xFE00]),
             0xFF40 => MemRead::Read(self.control_register.get()),
             0xFF41 => MemRead::Read(self.status_register.get()),
             0xFF42 => MemRead::Read(self.y_scroll),
@@ -465,16 +567,8 @@ impl IoDevice for Ppu {
         }
     }
 
-    fn write_byte(&mut self, _mmu: &Mmu, adder: u16, val: u8) -> MemWrite {
+    fn write_byte(&mut self, adder: u16, val: u8) -> MemWrite {
         match adder {
-            0x8000..=0x9FFF => {
-                (self.write_to_vram(self.selected_vram_bank, adder, val));
-                MemWrite::Write
-            }
-            0xFE00..=0xFE9F => {
-                self.oma_table[adder as usize - 0xFE00] = val;
-                MemWrite::Write
-            }
             0xFF40 => {
                 self.control_register.set(val);
                 MemWrite::Write
@@ -541,3 +635,72 @@ impl IoDevice for Ppu {
         }
     }
 }
+
+impl SaveState for Ppu {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u32(self.clock);
+
+        for pixel in self.frame_buffer.iter() {
+            state.write_u32(*pixel);
+        }
+        state.write_u64(self.frame_count);
+
+        state.write_usize(self.selected_vram_bank);
+        for bank in self.vram.iter() {
+            state.write_bytes(bank);
+        }
+        state.write_bytes(&self.oma_table);
+
+        state.write_u8(self.line);
+        state.write_u8(self.line_compare);
+        state.write_u8(self.x_scroll);
+        state.write_u8(self.y_scroll);
+        state.write_u8(self.window_y_pos);
+        state.write_u8(self.window_x_pos);
+
+        state.write_u8(self.status_register.get());
+        state.write_bool(self.status_register.coincidence_flag);
+        state.write_u8(self.control_register.get());
+
+        self.bg_mono_palette.save_state(state);
+        self.object_mono_palette_0.save_state(state);
+        self.object_mono_palette_1.save_state(state);
+        self.bg_color_palette.save_state(state);
+        self.object_color_palette.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) {
+        self.clock = state.read_u32();
+
+        for pixel in self.frame_buffer.iter_mut() {
+            *pixel = state.read_u32();
+        }
+        self.frame_count = state.read_u64();
+
+        self.selected_vram_bank = state.read_index(self.vram.len(), "vram bank out of range");
+        for bank in self.vram.iter_mut() {
+            state.read_bytes(bank);
+        }
+        state.read_bytes(&mut self.oma_table);
+
+        self.line = state.read_u8();
+        self.line_compare = state.read_u8();
+        self.x_scroll = state.read_u8();
+        self.y_scroll = state.read_u8();
+        self.window_y_pos = state.read_u8();
+        self.window_x_pos = state.read_u8();
+
+        // the status register only takes the interrupt bits on write so the rest is set by hand
+        let status = state.read_u8();
+        self.status_register.set(status);
+        self.status_register.mode = PpuMode::from(status);
+        self.status_register.coincidence_flag = state.read_bool();
+        self.control_register.set(state.read_u8());
+
+        self.bg_mono_palette.load_state(state);
+        self.object_mono_palette_0.load_state(state);
+        self.object_mono_palette_1.load_state(state);
+        self.bg_color_palette.load_state(state);
+        self.object_color_palette.load_state(state);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::ppu::color::Color;
+use crate::state::{SaveState, StateReader, StateWriter};
 
 pub struct MonoColorPalette {
     colors: Vec<Color>
@@ -27,15 +28,6 @@ impl MonoColorPalette {
         self.colors[3] = Color::u8_to_monochrome((val >> 6) & 0x3);
     }
 
-    pub fn get_color(&self, index: usize) -> Color {
-        match index {
-            0 => self.colors[0],
-            1 => self.colors[1],
-            2 => self.colors[2],
-            _ => self.colors[3],
-        }
-    }
-
     pub fn get_color_array(&self) -> &Vec<Color>  {
         &self.colors
     }
@@ -72,7 +64,7 @@ impl ColorPalette {
         let palette_index = self.color_index / 8;
         let color_index = self.color_index % 8;
 
-        if (color_index % 2) == 0 {
+        if color_index % 2 == 0 {
             self.colors[palette_index][color_index / 2].get_low()
         } else {
             self.colors[palette_index][color_index / 2].get_high()
@@ -83,7 +75,7 @@ impl ColorPalette {
         let palette_index = self.color_index / 8;
         let color_index = self.color_index % 8;
 
-        if (color_index % 2) == 0 {
+        if color_index % 2 == 0 {
             self.colors[palette_index][color_index / 2].set_low(val);
         } else {
             self.colors[palette_index][color_index / 2].set_high(val);
@@ -97,8 +89,38 @@ impl ColorPalette {
     pub fn get_palette(&self, index: usize) -> &Vec<Color> {
         &self.colors[index % 8]
     }
+}
+
+impl SaveState for MonoColorPalette {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.read());
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) {
+        self.write(state.read_u8());
+    }
+}
+
+impl SaveState for ColorPalette {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.read_index_reg());
+
+        for palette in self.colors.iter() {
+            for color in palette.iter() {
+                state.write_u8(color.get_low());
+                state.write_u8(color.get_high());
+            }
+        }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) {
+        self.write_index_reg(state.read_u8());
 
-    pub fn get_color(&self, index: usize) -> Color {
-        self.colors[self.color_index][index]
+        for palette in self.colors.iter_mut() {
+            for color in palette.iter_mut() {
+                color.set_low(state.read_u8());
+                color.set_high(state.read_u8());
+            }
+        }
     }
-}
\ No newline at end of file
+}
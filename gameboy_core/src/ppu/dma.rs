@@ -1,13 +1,13 @@
+use crate::cdl;
 use crate::mmu::IoDevice;
 use crate::ppu::MemRead;
 use crate::ppu::MemWrite;
-use crate::ppu::Mmu;
-use crate::ppu::{Ppu, PpuMode};
+use crate::mmu::Mmu;
+use crate::ppu::PpuMode;
+use crate::state::{SaveState, StateReader, StateWriter};
 use crate::utils::{build_u16, get_u16_high, get_u16_low};
-use std::cell::RefCell;
 
 const OAM_DMA_TIME: u32 = 640;
-const G_DMA_TIME: u32 = 640;
 
 #[derive(Clone, Copy, PartialEq)]
 pub enum DmaType {
@@ -17,6 +17,22 @@ pub enum DmaType {
     None,
 }
 
+// a block copy the dma manager wants done, the mmu owns the memory so it runs the copy
+pub struct DmaTransfer {
+    source: u16,
+    target: u16,
+    len: u16,
+}
+
+impl DmaTransfer {
+    pub fn run(&self, mmu: &mut Mmu) {
+        for index in 0..self.len {
+            let byte = mmu.read_as(self.source.wrapping_add(index), cdl::DMA);
+            mmu.write_byte(self.target.wrapping_add(index), byte);
+        }
+    }
+}
+
 pub struct DmaManager {
     dma_type: DmaType,
     oma_base_adder: u8,
@@ -37,71 +53,68 @@ impl DmaManager {
         }
     }
 
-    // returns remaining clocks and if operation needs more time
-    pub fn cycle(&mut self, ppu_mode: PpuMode, mmu: &mut Mmu, clock: u32) -> (u32, bool) {
+    // returns remaining clocks, if operation needs more time and the copy to run if one is due
+    pub fn cycle(&mut self, ppu_mode: PpuMode, clock: u32) -> (u32, bool, Option<DmaTransfer>) {
 
         match self.dma_type {
-            DmaType::None => (clock, false),
+            DmaType::None => (clock, false, None),
             DmaType::Hdma => {
                 if ppu_mode == PpuMode::HorizontalBlanking {
                     if clock >= 8 {
-                        self.transfer_row(mmu);
+                        let transfer = self.transfer_rows(1);
 
                         if self.vram_dma_len == 0x7F {
                             self.dma_type = DmaType::None
                         }
-                        (clock - 8, false)
+                        (clock - 8, false, Some(transfer))
                     } else {
-                        (clock, true)
+                        (clock, true, None)
                     }
                 } else {
-                    (clock, false)
+                    (clock, false, None)
                 }
             }
             DmaType::Oma => {
                 if clock >= OAM_DMA_TIME {
-                    let base = (self.oma_base_adder as u16) << 8;
-
-                    for index in 0x00..0xA0 {
-                        let byte = mmu.read_byte(base + index);
-                        mmu.write_byte(0xFE00 + index, byte);
-                    }
+                    let transfer = DmaTransfer {
+                        source: (self.oma_base_adder as u16) << 8,
+                        target: 0xFE00,
+                        len: 0xA0,
+                    };
 
                     self.dma_type = DmaType::None;
-                    (clock - OAM_DMA_TIME, false)
+                    (clock - OAM_DMA_TIME, false, Some(transfer))
                 } else {
-                    (clock, true)
+                    (clock, true, None)
                 }
             }
             DmaType::Gdma => {
                 let rows = self.vram_dma_len + 1;
                 if clock > (rows as u32 * 8) {
-                    for _ in 0..rows {
-                        self.transfer_row(mmu);
-                    }
+                    let transfer = self.transfer_rows(rows);
+
                     self.dma_type = DmaType::None;
-                    (clock - (rows as u32 * 8), false)
+                    (clock - (rows as u32 * 8), false, Some(transfer))
                 } else {
-                    (clock, true)
+                    (clock, true, None)
                 }
             }
         }
     }
 
-    fn transfer_row(&mut self, mmu: &mut Mmu) {
-        for index in 0..0x10 {
-            let b = mmu.read_byte(self.vram_dma_source + index);
-            mmu.write_byte(self.vram_dma_target + index, b);
-        }
+    // rows are 16 bytes and always follow each other so any number of them is a single copy
+    fn transfer_rows(&mut self, rows: u8) -> DmaTransfer {
+        let transfer = DmaTransfer {
+            source: self.vram_dma_source,
+            target: self.vram_dma_target,
+            len: rows as u16 * 0x10,
+        };
 
-        self.vram_dma_source += 0x10;
-        self.vram_dma_target += 0x10;
+        self.vram_dma_source = self.vram_dma_source.wrapping_add(transfer.len);
+        self.vram_dma_target = self.vram_dma_target.wrapping_add(transfer.len);
+        self.vram_dma_len = self.vram_dma_len.wrapping_sub(rows) & 0x7F;
 
-        if self.vram_dma_len == 0 {
-            self.vram_dma_len = 0x7F;
-        } else {
-            self.vram_dma_len -= 1;
-        }
+        transfer
     }
 
     pub fn read_oam(&self) -> u8 {
@@ -157,14 +170,14 @@ impl DmaManager {
 }
 
 impl IoDevice for DmaManager {
-    fn read_byte(&mut self, _: &Mmu, addr: u16) -> MemRead {
+    fn peek_byte(&self, addr: u16) -> MemRead {
         match addr {
             0xFF46 => MemRead::Read(self.read_oam()),
             0xFF51..=0xFF55 => MemRead::Read(self.read_vram_dma(addr)),
             _ => MemRead::Ignore,
         }
     }
-    fn write_byte(&mut self, _: &Mmu, addr: u16, val: u8) -> MemWrite {
+    fn write_byte(&mut self, addr: u16, val: u8) -> MemWrite {
         match addr {
             0xFF46 => {
                 self.write_oam(val);
@@ -178,3 +191,31 @@ impl IoDevice for DmaManager {
         }
     }
 }
+
+impl SaveState for DmaManager {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(match self.dma_type {
+            DmaType::None => 0,
+            DmaType::Oma => 1,
+            DmaType::Hdma => 2,
+            DmaType::Gdma => 3,
+        });
+        state.write_u8(self.oma_base_adder);
+        state.write_u16(self.vram_dma_source);
+        state.write_u16(self.vram_dma_target);
+        state.write_u8(self.vram_dma_len);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) {
+        self.dma_type = match state.read_u8() {
+            1 => DmaType::Oma,
+            2 => DmaType::Hdma,
+            3 => DmaType::Gdma,
+            _ => DmaType::None,
+        };
+        self.oma_base_adder = state.read_u8();
+        self.vram_dma_source = state.read_u16();
+        self.vram_dma_target = state.read_u16();
+        self.vram_dma_len = state.read_u8();
+    }
+}
@@ -1,6 +1,5 @@
 use crate::ppu::Ppu;
 use crate::ppu::MonoColorPalette;
-use crate::ppu::Mmu;
 use crate::ppu::ColorPalette;
 use crate::ppu::Color;
 
@@ -10,7 +9,6 @@ const OAM_BASE: u16 = 0xFE00;
 pub struct Attributes<'a> {
     pub palette: &'a [Color],
     pub vram_bank: usize,
-    pub palette_number: usize,
     pub x_flip: bool,
     pub y_flip: bool,
     pub priority: bool,
@@ -21,7 +19,6 @@ impl<'a> Attributes<'a> {
         Attributes {
             palette: color_palette.get_palette((val & 0x7) as usize),
             vram_bank: ((val >> 3) & 0x1) as usize,
-            palette_number: (val & 0x10) as usize,
             x_flip: (val & 0x20) != 0,
             y_flip: (val & 0x40) != 0,
             priority: (val & 0x80) != 0,
@@ -32,7 +29,6 @@ impl<'a> Attributes<'a> {
         Attributes {
             palette: mono_color_palette.get_color_array(),
             vram_bank: 0,
-            palette_number: 0,
             x_flip: false,
             y_flip: false,
             priority: false,
@@ -49,7 +45,6 @@ impl<'a> Attributes<'a> {
         Attributes {
             palette: mono_cp.get_color_array(),
             vram_bank: 0,
-            palette_number: (val & 0x10) as usize,
             x_flip: (val & 0x20) != 0,
             y_flip: (val & 0x40) != 0,
             priority: (val & 0x80) != 0,
@@ -13,7 +13,7 @@ impl StatusRegister {
 
     pub fn new() -> StatusRegister {
         StatusRegister {
-            mode: PpuMode::OAM,
+            mode: PpuMode::Oam,
             coincidence_flag: false,
             h_blank_int: false,
             v_blank_int: false,
@@ -0,0 +1,221 @@
+use crate::emulator::{DebugImage, GameBoyMode, OamEntry};
+use crate::hardware::{DISPLAY_HIGHT, DISPLAY_WIDTH};
+use crate::ppu::color::Color;
+use crate::ppu::sprite::{Attributes, Sprite};
+use crate::ppu::Ppu;
+
+const TILES_PER_ROW: usize = 16;
+const TILES_PER_BANK: usize = 384;
+
+const TILE_MAP_SIZE: usize = 32;
+
+// sprites are drawn in cells with a one pixel gap around them
+const OAM_PER_ROW: usize = 8;
+const OAM_CELL_WIDTH: usize = 10;
+const OAM_CELL_HIGHT: usize = 18;
+
+const SWATCH_SIZE: usize = 8;
+
+const BLACK: u32 = 0xFF000000;
+const GAP_COLOR: u32 = 0xFF404040;
+const VIEWPORT_COLOR: u32 = 0xFFFF0000;
+
+impl Ppu {
+    fn bank_count(&self) -> usize {
+        if self.game_boy_mode == GameBoyMode::Color {
+            2
+        } else {
+            1
+        }
+    }
+
+    fn first_bg_palette(&self) -> &[Color] {
+        if self.game_boy_mode == GameBoyMode::Color {
+            self.bg_color_palette.get_palette(0)
+        } else {
+            self.bg_mono_palette.get_color_array()
+        }
+    }
+
+    // draws the 8x8 tile at tile_adder with its top left corner at x, y. color 0 is skipped for sprites
+    fn draw_tile(
+        &self,
+        image: &mut DebugImage,
+        x: usize,
+        y: usize,
+        tile_adder: u16,
+        attributes: &Attributes,
+        transparent: bool,
+    ) {
+        for y_offset in 0..8 {
+            for x_offset in 0..8 {
+                let tile_y = if attributes.y_flip { 7 - y_offset } else { y_offset };
+                let tile_x = if attributes.x_flip { 7 - x_offset } else { x_offset };
+
+                let color_index = self.get_tile_color(tile_adder, tile_x, tile_y, attributes.vram_bank);
+                if transparent && color_index == 0 {
+                    continue;
+                }
+
+                image.set(
+                    x + x_offset as usize,
+                    y + y_offset as usize,
+                    attributes.palette[color_index].get_rgb_values(),
+                );
+            }
+        }
+    }
+
+    pub fn tile_data_image(&self) -> DebugImage {
+        let rows = TILES_PER_BANK / TILES_PER_ROW;
+        let mut image = DebugImage::new(TILES_PER_ROW * 8 * self.bank_count(), rows * 8, BLACK);
+
+        for bank in 0..self.bank_count() {
+            let attributes = Attributes {
+                palette: self.first_bg_palette(),
+                vram_bank: bank,
+                x_flip: false,
+                y_flip: false,
+                priority: false,
+            };
+
+            for tile in 0..TILES_PER_BANK {
+                let x = (bank * TILES_PER_ROW + tile % TILES_PER_ROW) * 8;
+                let y = (tile / TILES_PER_ROW) * 8;
+
+                self.draw_tile(&mut image, x, y, 0x8000 + tile as u16 * 16, &attributes, false);
+            }
+        }
+
+        image
+    }
+
+    pub fn tile_map_image(&self, map: usize) -> DebugImage {
+        let map_adder: u16 = if map == 0 { 0x9800 } else { 0x9C00 };
+        let mut image = DebugImage::new(TILE_MAP_SIZE * 8, TILE_MAP_SIZE * 8, BLACK);
+
+        for tile in 0..(TILE_MAP_SIZE * TILE_MAP_SIZE) as u16 {
+            let tile_map_adder = map_adder + tile;
+
+            let attributes = if self.game_boy_mode == GameBoyMode::Color {
+                Attributes::new(self.read_from_vram(1, tile_map_adder), &self.bg_color_palette)
+            } else {
+                Attributes::new_normal_gb(&self.bg_mono_palette)
+            };
+
+            let tile_adder = self.bg_tile_adder(self.read_from_vram(0, tile_map_adder));
+
+            let x = (tile as usize % TILE_MAP_SIZE) * 8;
+            let y = (tile as usize / TILE_MAP_SIZE) * 8;
+            self.draw_tile(&mut image, x, y, tile_adder, &attributes, false);
+        }
+
+        // the part the screen shows, it wraps around the edges of the map
+        let map_size = TILE_MAP_SIZE * 8;
+        let left = self.x_scroll as usize;
+        let top = self.y_scroll as usize;
+
+        for x in 0..DISPLAY_WIDTH {
+            image.set((left + x) % map_size, top, VIEWPORT_COLOR);
+            image.set((left + x) % map_size, (top + DISPLAY_HIGHT - 1) % map_size, VIEWPORT_COLOR);
+        }
+
+        for y in 0..DISPLAY_HIGHT {
+            image.set(left, (top + y) % map_size, VIEWPORT_COLOR);
+            image.set((left + DISPLAY_WIDTH - 1) % map_size, (top + y) % map_size, VIEWPORT_COLOR);
+        }
+
+        image
+    }
+
+    fn sprite(&self, index: u16) -> Sprite<'_> {
+        if self.game_boy_mode == GameBoyMode::Color {
+            Sprite::new(index, self.control_register.sprite_size, self, &self.object_color_palette)
+        } else {
+            Sprite::new_normal_gb(
+                index,
+                self.control_register.sprite_size,
+                self,
+                &self.object_mono_palette_0,
+                &self.object_mono_palette_1,
+            )
+        }
+    }
+
+    pub fn oam_entries(&self) -> Vec<OamEntry> {
+        (0..40)
+            .map(|index| {
+                let sprite = self.sprite(index);
+                let flags = self.oma_table[index as usize * 4 + 3];
+
+                let palette = if self.game_boy_mode == GameBoyMode::Color {
+                    flags & 0x7
+                } else {
+                    (flags >> 4) & 0x1
+                };
+
+                OamEntry {
+                    x: sprite.x,
+                    y: sprite.y,
+                    tile_index: sprite.tile_index,
+                    palette,
+                    vram_bank: sprite.attributes.vram_bank,
+                    x_flip: sprite.attributes.x_flip,
+                    y_flip: sprite.attributes.y_flip,
+                    behind_background: sprite.attributes.priority,
+                }
+            })
+            .collect()
+    }
+
+    pub fn oam_image(&self) -> DebugImage {
+        let rows = 40 / OAM_PER_ROW;
+        let mut image = DebugImage::new(OAM_PER_ROW * OAM_CELL_WIDTH, rows * OAM_CELL_HIGHT, GAP_COLOR);
+        let tiles = if self.control_register.sprite_size { 2 } else { 1 };
+
+        for index in 0..40 {
+            let sprite = self.sprite(index);
+            let x = (index as usize % OAM_PER_ROW) * OAM_CELL_WIDTH + 1;
+            let y = (index as usize / OAM_PER_ROW) * OAM_CELL_HIGHT + 1;
+
+            for tile in 0..tiles {
+                // a flipped tall sprite swaps its two tiles as well
+                let tile_index = if sprite.attributes.y_flip { tiles - 1 - tile } else { tile };
+                let tile_adder = 0x8000 + (sprite.tile_index + tile_index) * 16;
+
+                self.draw_tile(&mut image, x, y + tile as usize * 8, tile_adder, &sprite.attributes, true);
+            }
+        }
+
+        image
+    }
+
+    pub fn palette_image(&self) -> DebugImage {
+        let palettes: Vec<&[Color]> = if self.game_boy_mode == GameBoyMode::Color {
+            (0..8)
+                .map(|index| self.bg_color_palette.get_palette(index).as_slice())
+                .chain((0..8).map(|index| self.object_color_palette.get_palette(index).as_slice()))
+                .collect()
+        } else {
+            vec![
+                self.bg_mono_palette.get_color_array(),
+                self.object_mono_palette_0.get_color_array(),
+                self.object_mono_palette_1.get_color_array(),
+            ]
+        };
+
+        let mut image = DebugImage::new(4 * SWATCH_SIZE, palettes.len() * SWATCH_SIZE, BLACK);
+
+        for (row, palette) in palettes.iter().enumerate() {
+            for (column, color) in palette.iter().enumerate() {
+                for y in 0..SWATCH_SIZE {
+                    for x in 0..SWATCH_SIZE {
+                        image.set(column * SWATCH_SIZE + x, row * SWATCH_SIZE + y, color.get_rgb_values());
+                    }
+                }
+            }
+        }
+
+        image
+    }
+}
@@ -0,0 +1,285 @@
+use crate::emulator::DebugImage;
+use crate::serial::SerialTransport;
+use std::sync::mpsc::Sender;
+
+// the game boy printer on the link port. the game talks to it in packets:
+//
+// 88 33 command compression length data checksum 00 00
+//
+// length and checksum are little endian 16 bit values, the checksum is the sum of every byte
+// from the command to the end of the data. the printer answers 00 to every byte but the last
+// two, the first of them gets 81 to say a printer is there and the second its status
+
+const MAGIC: [u8; 2] = [0x88, 0x33];
+const ALIVE: u8 = 0x81;
+
+const INIT: u8 = 0x01;
+const PRINT: u8 = 0x02;
+const DATA: u8 = 0x04;
+const STATUS: u8 = 0x0F;
+
+// status bits
+const CHECKSUM_ERROR: u8 = 0x01;
+const PRINTING: u8 = 0x02;
+const IMAGE_FULL: u8 = 0x04;
+const UNPROCESSED: u8 = 0x08;
+const PACKET_ERROR: u8 = 0x10;
+
+// the printer ram holds 8kb of tiles, rows of 20 tiles make up the 160 pixel wide paper
+const BUFFER_SIZE: usize = 0x2000;
+const PRINT_WIDTH: usize = 160;
+const TILES_PER_ROW: usize = PRINT_WIDTH / 8;
+const TILE_BYTES: usize = 16;
+
+// the printer stays busy for this many packets after a print, games wait for it before they go on
+const PRINTING_PACKETS: u32 = 8;
+
+// white paper to black ink
+const SHADES: [u32; 4] = [0xFFFFFFFF, 0xFFAAAAAA, 0xFF555555, 0xFF000000];
+
+// the part of the packet the next byte is
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Receiving {
+    Magic(usize),
+    Command,
+    Compression,
+    LengthLow,
+    LengthHigh,
+    Data,
+    ChecksumLow,
+    ChecksumHigh,
+    Alive,
+    Status,
+}
+
+// what a print command put on paper
+#[derive(Clone, PartialEq, Debug)]
+pub struct Printout {
+    pub image: DebugImage, // 160 pixels wide, no rows when the game only fed paper
+    pub margin_before: u8, // paper fed before and after the image in line feeds, 0 continues the last printout
+    pub margin_after: u8,
+}
+
+pub struct Printer {
+    receiving: Receiving,
+    command: u8,
+    compressed: bool,
+    length: u16,
+    data: Vec<u8>,
+    checksum: u16, // of the packet so far
+    sent_checksum: u16,
+    status: u8,
+    busy_packets: u32,
+    buffer: Vec<u8>, // decompressed tiles waiting for a print command
+    printouts: Sender<Printout>,
+}
+
+// runs of a byte are a control byte with the high bit set and the run length - 2, anything else
+// is a control byte with the count - 1 of the literal bytes that follow
+fn decompress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut bytes = data.iter();
+
+    while let Some(control) = bytes.next() {
+        if control & 0x80 != 0 {
+            let count = (control & 0x7F) as usize + 2;
+            if let Some(value) = bytes.next() {
+                out.extend(std::iter::repeat_n(*value, count));
+            }
+        } else {
+            out.extend(bytes.by_ref().take(*control as usize + 1));
+        }
+    }
+
+    out
+}
+
+impl Printer {
+    pub fn new(printouts: Sender<Printout>) -> Printer {
+        Printer {
+            receiving: Receiving::Magic(0),
+            command: 0,
+            compressed: false,
+            length: 0,
+            data: Vec::new(),
+            checksum: 0,
+            sent_checksum: 0,
+            status: 0,
+            busy_packets: 0,
+            buffer: Vec::new(),
+            printouts,
+        }
+    }
+
+    fn run_command(&mut self) {
+        if self.sent_checksum != self.checksum {
+            self.status |= CHECKSUM_ERROR;
+            return;
+        }
+        self.status &= !CHECKSUM_ERROR;
+
+        match self.command {
+            INIT => {
+                self.buffer.clear();
+                self.status = 0;
+                self.busy_packets = 0;
+            }
+            PRINT if self.data.len() == 4 => self.print(),
+            DATA => self.receive_data(),
+            STATUS => {}
+            _ => self.status |= PACKET_ERROR,
+        }
+    }
+
+    // an empty data packet ends the image, the printer has nothing to do for it
+    fn receive_data(&mut self) {
+        let data = if self.compressed {
+            decompress(&self.data)
+        } else {
+            std::mem::take(&mut self.data)
+        };
+
+        if self.buffer.len() + data.len() > BUFFER_SIZE {
+            self.status |= PACKET_ERROR;
+            return;
+        }
+
+        if !data.is_empty() {
+            self.buffer.extend(data);
+            self.status |= UNPROCESSED;
+        }
+    }
+
+    // the print command holds the copies (0 only feeds paper), the margins, the palette and the exposure
+    fn print(&mut self) {
+        let copies = self.data[0] as usize;
+        let margin_before = self.data[1] >> 4;
+        let margin_after = self.data[1] & 0xF;
+        // 0 is taken for the default palette
+        let palette = match self.data[2] {
+            0 => 0xE4,
+            palette => palette,
+        };
+
+        let row_bytes = TILES_PER_ROW * TILE_BYTES;
+        let rows = self.buffer.len() / row_bytes;
+        let height = rows * 8;
+
+        let mut image = DebugImage::new(PRINT_WIDTH, height * copies, SHADES[0]);
+
+        for row in 0..rows {
+            for tile in 0..TILES_PER_ROW {
+                let tile_data = &self.buffer[row * row_bytes + tile * TILE_BYTES..][..TILE_BYTES];
+
+                for y in 0..8 {
+                    let low = tile_data[y * 2];
+                    let high = tile_data[y * 2 + 1];
+
+                    for x in 0..8 {
+                        let bit = 7 - x;
+                        let color_index = ((high >> bit) & 1) << 1 | ((low >> bit) & 1);
+                        let shade = SHADES[((palette >> (color_index * 2)) & 0x3) as usize];
+
+                        for copy in 0..copies {
+                            image.set(tile * 8 + x, copy * height + row * 8 + y, shade);
+                        }
+                    }
+                }
+            }
+        }
+
+        // nobody is collecting the paper, the game doesn't notice
+        let _ = self.printouts.send(Printout {
+            image,
+            margin_before,
+            margin_after,
+        });
+
+        self.buffer.clear();
+        self.status = PRINTING | IMAGE_FULL;
+        self.busy_packets = PRINTING_PACKETS;
+    }
+
+    // the status the packet ends with, a finished print is done once it was reported busy long enough
+    fn reply_status(&mut self) -> u8 {
+        let status = self.status;
+
+        if self.status & PRINTING != 0 {
+            self.busy_packets = self.busy_packets.saturating_sub(1);
+            if self.busy_packets == 0 {
+                self.status &= !(PRINTING | IMAGE_FULL);
+            }
+        }
+
+        status
+    }
+}
+
+impl SerialTransport for Printer {
+    fn exchange(&mut self, sent: u8) -> u8 {
+        match self.receiving {
+            Receiving::Magic(index) => {
+                self.receiving = if sent == MAGIC[index] && index + 1 == MAGIC.len() {
+                    Receiving::Command
+                } else if sent == MAGIC[index] {
+                    Receiving::Magic(index + 1)
+                } else if sent == MAGIC[0] {
+                    Receiving::Magic(1)
+                } else {
+                    Receiving::Magic(0)
+                };
+            }
+            Receiving::Command => {
+                self.command = sent;
+                self.checksum = sent as u16;
+                self.receiving = Receiving::Compression;
+            }
+            Receiving::Compression => {
+                self.compressed = sent & 1 != 0;
+                self.checksum = self.checksum.wrapping_add(sent as u16);
+                self.receiving = Receiving::LengthLow;
+            }
+            Receiving::LengthLow => {
+                self.length = sent as u16;
+                self.checksum = self.checksum.wrapping_add(sent as u16);
+                self.receiving = Receiving::LengthHigh;
+            }
+            Receiving::LengthHigh => {
+                self.length |= (sent as u16) << 8;
+                self.checksum = self.checksum.wrapping_add(sent as u16);
+                self.data.clear();
+                self.receiving = if self.length == 0 {
+                    Receiving::ChecksumLow
+                } else {
+                    Receiving::Data
+                };
+            }
+            Receiving::Data => {
+                self.data.push(sent);
+                self.checksum = self.checksum.wrapping_add(sent as u16);
+                if self.data.len() == self.length as usize {
+                    self.receiving = Receiving::ChecksumLow;
+                }
+            }
+            Receiving::ChecksumLow => {
+                self.sent_checksum = sent as u16;
+                self.receiving = Receiving::ChecksumHigh;
+            }
+            Receiving::ChecksumHigh => {
+                self.sent_checksum |= (sent as u16) << 8;
+                self.run_command();
+                self.receiving = Receiving::Alive;
+            }
+            Receiving::Alive => {
+                self.receiving = Receiving::Status;
+                return ALIVE;
+            }
+            Receiving::Status => {
+                self.receiving = Receiving::Magic(0);
+                return self.reply_status();
+            }
+        }
+
+        0x00
+    }
+}
@@ -1,23 +1,35 @@
-use crate::ic::Ic;
+use crate::cdl;
+use crate::emulator::{CallFrame, GameBoyMode, LockUp};
 use crate::mmu::Mmu;
 use crate::processor::decode::Addr;
 use crate::processor::decode::Immediate8;
 use crate::processor::decode::In8;
 use crate::processor::decode::Out8;
-use crate::processor::registers::{ Reg16, Reg8, Registers};
-use std::cell::RefCell;
+use crate::processor::registers::{ Reg16, Reg8};
+pub use crate::processor::registers::Registers;
+use crate::processor::trace::Trace;
+use crate::profiler::{Profile, Profiler};
+use crate::state::{SaveState, StateReader, StateWriter};
+use crate::utils;
 use std::fmt::Debug;
-use std::rc::Rc;
+use std::io::{self, Write};
 
 mod decode;
 mod execute;
 mod registers;
+mod trace;
+
+#[cfg(test)]
+mod tests;
 
 const ZERO_PAGE_ADDR: u16 = 0xFF00;
 
 pub type TCycles = u32;
 pub const T_CYCLE_FREQUENCY: u32 = 4194304;
 
+// frames past this are dropped from the bottom of the call stack, code that never returns keeps calling deeper
+const MAX_CALL_DEPTH: usize = 256;
+
 const FETCH_T_CYCLES: TCycles = 4;
 const MEM_ACCESS_T_CYCLES_8: TCycles = 4;
 const MEM_ACCESS_T_CYCLES_16: TCycles = MEM_ACCESS_T_CYCLES_8 * 2;
@@ -27,8 +39,15 @@ pub struct Processor {
     registers: Registers,
 
     ime: bool, // Interrupt Master Enable Flag
+    ime_pending: bool, // ei ran, ime goes on after the next instruction
 
     halt: bool,
+
+    lock_up: Option<LockUp>, // halted for good by an illegal opcode
+
+    trace: Option<Trace>, // not part of save states
+    profiler: Option<Profiler>, // not part of save states
+    calls: Vec<CallFrame>, // not part of save states
 }
 
 impl Processor {
@@ -36,7 +55,136 @@ impl Processor {
         Processor {
             registers: Registers::new(),
             ime: true,
+            ime_pending: false,
+            halt: false,
+            lock_up: None,
+            trace: None,
+            profiler: None,
+            calls: Vec::new(),
+        }
+    }
+
+    // a processor in the state the boot rom leaves it in
+    pub fn post_boot(game_boy_mode: GameBoyMode) -> Processor {
+        Processor {
+            registers: Registers::post_boot(game_boy_mode),
+            ime: false,
+            ime_pending: false,
             halt: false,
+            lock_up: None,
+            trace: None,
+            profiler: None,
+            calls: Vec::new(),
+        }
+    }
+
+    pub fn registers(&self) -> Registers {
+        self.registers
+    }
+
+    pub fn set_registers(&mut self, registers: Registers) {
+        self.registers = registers;
+    }
+
+    pub fn lock_up(&self) -> Option<LockUp> {
+        self.lock_up
+    }
+
+    pub fn backtrace(&self) -> &[CallFrame] {
+        &self.calls
+    }
+
+    // keeps track of a call once its return address is pushed and pc is at the target
+    fn enter(&mut self, mmu: &Mmu, call_site: u16, interrupt: bool) {
+        if self.calls.len() == MAX_CALL_DEPTH {
+            self.calls.remove(0);
+        }
+
+        let target = self.registers.pc;
+        self.calls.push(CallFrame {
+            call_site,
+            call_site_bank: mmu.bank(call_site),
+            target,
+            target_bank: mmu.bank(target),
+            interrupt,
+            return_slot: self.registers.read16(Reg16::SP),
+        });
+    }
+
+    // drops every call whose return address was popped off the stack
+    fn leave(&mut self) {
+        let sp = self.registers.read16(Reg16::SP);
+
+        while self.calls.last().is_some_and(|frame| frame.return_slot < sp) {
+            self.calls.pop();
+        }
+    }
+
+    // starts or stops logging every instruction, the trace that was running is flushed and its first error returned
+    pub fn set_trace(&mut self, out: Option<Box<dyn Write + Send>>) -> io::Result<()> {
+        let old = std::mem::replace(&mut self.trace, out.map(Trace::new));
+
+        match old {
+            Some(trace) => trace.finish(),
+            None => Ok(()),
+        }
+    }
+
+    // kept out of cycle so a processor without a trace pays nothing but the check
+    #[cold]
+    #[inline(never)]
+    fn log_trace(&mut self, mmu: &Mmu) {
+        if let Some(trace) = &mut self.trace {
+            trace.log(&self.registers, mmu);
+        }
+    }
+
+    pub fn start_profiling(&mut self) {
+        self.profiler = Some(Profiler::default());
+    }
+
+    pub fn stop_profiling(&mut self) -> Option<Profile> {
+        self.profiler.take().map(Profiler::finish)
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profiler.as_ref().map(Profiler::profile)
+    }
+
+    // like log_trace this is only paid for while profiling
+    #[cold]
+    #[inline(never)]
+    fn profiled_execute(&mut self, mmu: &mut Mmu) -> TCycles {
+        let pc = self.registers.pc;
+        let bank = mmu.bank(pc);
+        let polled = self.polls_lcd(mmu);
+
+        let cycles = self.execute(mmu);
+
+        if let Some(profiler) = &mut self.profiler {
+            profiler.executed(bank, pc, self.registers.pc, cycles, polled);
+        }
+
+        cycles
+    }
+
+    // whether the next instruction reads LY or STAT, the registers a v-blank wait loop polls
+    fn polls_lcd(&self, mmu: &Mmu) -> bool {
+        let pc = self.registers.pc;
+        let operand = |offset: u16| mmu.peek_byte(pc.wrapping_add(offset));
+        let lcd_register = |adder: u16| adder == 0xFF41 || adder == 0xFF44;
+
+        match mmu.peek_byte(pc) {
+            0xF0 => lcd_register(ZERO_PAGE_ADDR | operand(1) as u16), // ldh a, (n)
+            0xF2 => lcd_register(ZERO_PAGE_ADDR | self.registers.c as u16), // ldh a, (c)
+            0xFA => lcd_register(utils::build_u16(operand(2), operand(1))), // ld a, (nn)
+            // ld r, (hl), and (hl) and cp (hl)
+            0x46 | 0x4E | 0x56 | 0x5E | 0x66 | 0x6E | 0x7E | 0xA6 | 0xBE => {
+                lcd_register(self.registers.read16(Reg16::HL))
+            }
+            // bit n, (hl)
+            0xCB => operand(1) & 0xC7 == 0x46 && lcd_register(self.registers.read16(Reg16::HL)),
+            _ => false,
         }
     }
 
@@ -47,7 +195,9 @@ impl Processor {
 
         self.registers.write16(Reg16::SP, sp);
 
-        mmu.write_word(sp, v);
+        // the stack grows down, the high byte goes first
+        mmu.write_byte(sp.wrapping_add(1), utils::get_u16_high(v));
+        mmu.write_byte(sp, utils::get_u16_low(v));
     }
 
     pub fn pop(&mut self, mmu: &mut Mmu) -> u16 {
@@ -60,9 +210,17 @@ impl Processor {
         val
     }
 
+    // read the first byte of the next instruction inc program counter
+    fn fetch_opcode(&mut self, mmu: &mut Mmu) -> u8 {
+        let opcode = mmu.read_as(self.registers.pc, cdl::OPCODE);
+        self.registers.pc = self.registers.pc.wrapping_add(1);
+
+        opcode
+    }
+
     // read next byte inc program counter
     pub fn fetch_byte(&mut self, mmu: &mut Mmu) -> u8 {
-        let b = mmu.read_byte(self.registers.pc);
+        let b = mmu.read_as(self.registers.pc, cdl::OPERAND);
         self.registers.pc = self.registers.pc.wrapping_add(1);
 
         b
@@ -70,23 +228,28 @@ impl Processor {
 
     // read next word inc program counter
     pub fn fetch_word(&mut self, mmu: &mut Mmu) -> u16 {
-        let w = mmu.read_word(self.registers.pc);
-        self.registers.pc = self.registers.pc.wrapping_add(2);
+        let low = self.fetch_byte(mmu);
+        let high = self.fetch_byte(mmu);
 
-        w
+        utils::build_u16(high, low)
     }
 
-    pub fn check_interrupt(&mut self, mmu: &mut Mmu, ic: &Rc<RefCell<Ic>>) -> TCycles {
+    pub fn check_interrupt(&mut self, mmu: &mut Mmu) -> TCycles {
+        // a locked up cpu never wakes up
+        if self.lock_up.is_some() {
+            return 0;
+        }
+
         if !self.ime {
             if self.halt {
-                if let Some(_value) = ic.borrow_mut().peek() {
+                if let Some(_value) = mmu.bus.ic.peek() {
                     self.halt = false;
                 }
             }
 
             0
         } else {
-            let value = match ic.borrow_mut().consume() {
+            let value = match mmu.bus.ic.consume() {
                 Some(value) => value,
                 None => return 0,
             };
@@ -102,16 +265,20 @@ impl Processor {
     fn interrupted(&mut self, mmu: &mut Mmu, value: u8) {
         self.ime = false;
 
-        self.push(mmu, self.registers.pc);
+        let interrupted_at = self.registers.pc;
+        self.push(mmu, interrupted_at);
         self.registers.pc = value as u16;
+        self.enter(mmu, interrupted_at, true);
     }
 
     fn ctrl_call(&mut self, mmu: &mut Mmu, condition: bool) -> TCycles {
         let addr = self.fetch_word(mmu);
 
         if condition {
-            self.push(mmu, self.registers.pc);
+            let return_adder = self.registers.pc;
+            self.push(mmu, return_adder);
             self.registers.pc = addr;
+            self.enter(mmu, return_adder.wrapping_sub(3), false);
 
             (FETCH_T_CYCLES * 4) + MEM_ACCESS_T_CYCLES_16
         } else {
@@ -123,6 +290,7 @@ impl Processor {
         if condition {
             let addr = self.pop(mmu);
             self.registers.pc = addr;
+            self.leave();
 
             (FETCH_T_CYCLES * 3) + MEM_ACCESS_T_CYCLES_16
         } else {
@@ -268,12 +436,12 @@ impl Processor {
             Addr::BC => (self.registers.read16(Reg16::BC), 0),
             Addr::DE => (self.registers.read16(Reg16::DE), 0),
             Addr::HL => (self.registers.read16(Reg16::HL), 0),
-            Addr::HLD => {
+            Addr::Hld => {
                 let addr = self.registers.read16(Reg16::HL);
                 self.registers.write16(Reg16::HL, addr.wrapping_sub(1));
                 (addr, 0)
             }
-            Addr::HLI => {
+            Addr::Hli => {
                 let addr = self.registers.read16(Reg16::HL);
                 self.registers.write16(Reg16::HL, addr.wrapping_add(1));
                 (addr, 0)
@@ -350,4 +518,31 @@ impl std::fmt::Display for Processor {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::result::Result<(), std::fmt::Error> {
         write!(f, "{}", self.registers)
     }
-}
\ No newline at end of file
+}
+
+impl SaveState for Processor {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.registers.save_state(state);
+        state.write_bool(self.ime);
+        state.write_bool(self.ime_pending);
+        state.write_bool(self.halt);
+        state.write_bool(self.lock_up.is_some());
+        let lock_up = self.lock_up.unwrap_or(LockUp { adder: 0, opcode: 0 });
+        state.write_u16(lock_up.adder);
+        state.write_u8(lock_up.opcode);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) {
+        self.calls.clear();
+        self.registers.load_state(state);
+        self.ime = state.read_bool();
+        self.ime_pending = state.read_bool();
+        self.halt = state.read_bool();
+        let locked_up = state.read_bool();
+        let lock_up = LockUp {
+            adder: state.read_u16(),
+            opcode: state.read_u8(),
+        };
+        self.lock_up = if locked_up { Some(lock_up) } else { None };
+    }
+}
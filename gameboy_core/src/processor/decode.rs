@@ -21,8 +21,8 @@ pub enum Addr {
 	BC,
 	DE,
 	HL,
-	HLD,
-	HLI,
+	Hld,
+	Hli,
 	Immediate16,
 	ZeroPage,
 	ZeroPageAndC,
@@ -49,10 +49,38 @@ impl Processor {
 	// this function was created using https://github.com/lmmendes/game-boy-opcodes
 	pub fn cycle(&mut self, mmu: &mut Mmu) -> TCycles {
 		if self.halt {
+			if let Some(profiler) = &mut self.profiler {
+				profiler.halted(FETCH_T_CYCLES);
+			}
+
 			return FETCH_T_CYCLES;
 		}
 
-		let opcode = self.fetch_byte(mmu);
+		if self.trace.is_some() {
+			self.log_trace(mmu);
+		}
+
+		let enable_ime = self.ime_pending;
+
+		let cycles = if self.profiler.is_some() {
+			self.profiled_execute(mmu)
+		} else {
+			self.execute(mmu)
+		};
+
+		// ei takes effect once the instruction after it has run, unless that was a di
+		if enable_ime && self.ime_pending {
+			self.ime = true;
+			self.ime_pending = false;
+		}
+
+		cycles
+	}
+
+	// fetches and runs the next instruction
+	#[inline(always)]
+	pub fn execute(&mut self, mmu: &mut Mmu) -> TCycles {
+		let opcode = self.fetch_opcode(mmu);
 
 		match opcode {
 			0x00 => self.nop(),
@@ -89,7 +117,7 @@ impl Processor {
 			0x1f => self.rra(),
 			0x20 => self.jr_cc(mmu, Condition::NZ),
 			0x21 => self.load16_imm(mmu, HL),
-			0x22 => self.load(mmu, Addr::HLI, A),
+			0x22 => self.load(mmu, Addr::Hli, A),
 			0x23 => self.inc16(HL),
 			0x24 => self.inc(mmu, H),
 			0x25 => self.dec(mmu, H),
@@ -97,7 +125,7 @@ impl Processor {
 			0x27 => self.daa(),
 			0x28 => self.jr_cc(mmu, Condition::Z),
 			0x29 => self.add16(HL),
-			0x2a => self.load(mmu, A, Addr::HLI),
+			0x2a => self.load(mmu, A, Addr::Hli),
 			0x2b => self.dec16(HL),
 			0x2c => self.inc(mmu, L),
 			0x2d => self.dec(mmu, L),
@@ -105,7 +133,7 @@ impl Processor {
 			0x2f => self.cpl(),
 			0x30 => self.jr_cc(mmu, Condition::NC),
 			0x31 => self.load16_imm(mmu, SP),
-			0x32 => self.load(mmu, Addr::HLD, A),
+			0x32 => self.load(mmu, Addr::Hld, A),
 			0x33 => self.inc16(SP),
 			0x34 => self.inc(mmu, Addr::HL),
 			0x35 => self.dec(mmu, Addr::HL),
@@ -113,7 +141,7 @@ impl Processor {
 			0x37 => self.scf(),
 			0x38 => self.jr_cc(mmu, Condition::C),
 			0x39 => self.add16(SP),
-			0x3a => self.load(mmu, A, Addr::HLD),
+			0x3a => self.load(mmu, A, Addr::Hld),
 			0x3b => self.dec16(SP),
 			0x3c => self.inc(mmu, A),
 			0x3d => self.dec(mmu, A),
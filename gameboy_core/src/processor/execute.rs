@@ -158,7 +158,7 @@ impl Processor {
         Self: In8<IO> + Out8<IO>,
     {
         let (val, cycles) = self.read(io, mmu);
-        let res = val >> 4 | val << 4;
+        let res = val.rotate_left(4);
         
         let cycles = self.write(io, mmu, res) + cycles;
 
@@ -167,7 +167,7 @@ impl Processor {
         self.registers.set_hf(false);
         self.registers.set_cf(false);
 
-        cycles + FETCH_T_CYCLES
+        cycles + (FETCH_T_CYCLES * 2)
     }
 
     pub fn daa(&mut self) -> TCycles
@@ -239,12 +239,13 @@ impl Processor {
 
     pub fn di(&mut self) -> TCycles {
         self.ime = false;
+        self.ime_pending = false;
         
         FETCH_T_CYCLES
     }
 
     pub fn ei(&mut self) -> TCycles {
-        self.ime = true;
+        self.ime_pending = true;
         
         FETCH_T_CYCLES
     }
@@ -288,7 +289,7 @@ impl Processor {
         let cycles = self.write(io, mmu, result) + cycles;
 
 
-        cycles + FETCH_T_CYCLES
+        cycles + (FETCH_T_CYCLES * 2)
     }
 
     pub fn rl<IO: Copy>(&mut self, mmu: &mut Mmu, io: IO) -> TCycles
@@ -302,7 +303,7 @@ impl Processor {
         let cycles = self.write(io, mmu, result) + cycles;
 
 
-        cycles + FETCH_T_CYCLES
+        cycles + (FETCH_T_CYCLES * 2)
     }
 
     pub fn rrc<IO: Copy>(&mut self, mmu: &mut Mmu, io: IO) -> TCycles
@@ -316,7 +317,7 @@ impl Processor {
         let cycles = self.write(io, mmu, result) + cycles;
 
 
-        cycles + FETCH_T_CYCLES
+        cycles + (FETCH_T_CYCLES * 2)
     }
 
     pub fn rr<IO: Copy>(&mut self, mmu: &mut Mmu, io: IO) -> TCycles
@@ -330,7 +331,7 @@ impl Processor {
         let cycles = self.write(io, mmu, result) + cycles;
 
 
-        cycles + FETCH_T_CYCLES
+        cycles + (FETCH_T_CYCLES * 2)
     }
 
     pub fn sla<IO: Copy>(&mut self, mmu: &mut Mmu, io: IO) -> TCycles
@@ -344,7 +345,7 @@ impl Processor {
         let cycles = self.write(io, mmu, result) + cycles;
 
 
-        cycles + FETCH_T_CYCLES
+        cycles + (FETCH_T_CYCLES * 2)
     }
 
     pub fn sra<IO: Copy>(&mut self, mmu: &mut Mmu, io: IO) -> TCycles
@@ -358,7 +359,7 @@ impl Processor {
         let cycles = self.write(io, mmu, result) + cycles;
 
 
-        cycles + FETCH_T_CYCLES
+        cycles + (FETCH_T_CYCLES * 2)
     }
 
     pub fn srl<IO: Copy>(&mut self, mmu: &mut Mmu, io: IO) -> TCycles
@@ -372,7 +373,7 @@ impl Processor {
         let cycles = self.write(io, mmu, result) + cycles;
 
 
-        cycles + FETCH_T_CYCLES
+        cycles + (FETCH_T_CYCLES * 2)
     }
 
     pub fn bit<IO: Copy>(&mut self, mmu: &mut Mmu, io: IO, bit: u8) -> TCycles
@@ -465,9 +466,11 @@ impl Processor {
     }
 
     pub fn rst(&mut self, mmu: &mut Mmu, addr: u8) -> TCycles {
-        self.push(mmu, self.registers.pc);
+        let return_adder = self.registers.pc;
+        self.push(mmu, return_adder);
 
         self.registers.pc = addr as u16;
+        self.enter(mmu, return_adder.wrapping_sub(1), false);
 
         (FETCH_T_CYCLES * 2) + MEM_ACCESS_T_CYCLES_16
     }
@@ -548,7 +551,7 @@ impl Processor {
 
         self.registers.write16(reg, val);
 
-        16
+        FETCH_T_CYCLES + MEM_ACCESS_T_CYCLES_16
     }
 
     // add 16 bit reg to HL
@@ -599,13 +602,14 @@ impl Processor {
         8
     }
 
-    pub fn undefined(&mut self, opcode: u8) -> ! {
-        panic!("Undefined opcode {:04X}", opcode)
-    }
-
-    pub fn cb_opcodes(&mut self, mmu: &mut Mmu) -> TCycles {
-        self.fetch_byte(mmu);
+    // the opcodes with no instruction hang the cpu until the next reset, interrupts included
+    pub fn undefined(&mut self, opcode: u8) -> TCycles {
+        self.lock_up = Some(LockUp {
+            adder: self.registers.pc.wrapping_sub(1),
+            opcode,
+        });
+        self.halt = true;
 
-        todo!()
+        FETCH_T_CYCLES
     }
 }
@@ -1,9 +1,12 @@
+use crate::emulator::GameBoyMode;
+use crate::state::{SaveState, StateReader, StateWriter};
+
 const ZERO_FLAG_MASK: u8 = 0b1 << 7;
 const NEG_FLAG_MASK: u8 = 0b1 << 6;
 const HALF_CARRY_FLAG_MASK: u8 = 0b1 << 5;
 const CARRY_FLAG_MASK: u8 = 0b1 << 4;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Default)]
 pub struct Registers {
     pub a: u8,
     pub f: u8,
@@ -53,6 +56,36 @@ impl Registers {
         }
     }
 
+    // the register values the boot rom leaves behind when it jumps to the cartridge
+    pub fn post_boot(game_boy_mode: GameBoyMode) -> Self {
+        match game_boy_mode {
+            GameBoyMode::Classic => Self {
+                pc: 0x0100,
+                sp: 0xFFFE,
+                a: 0x01,
+                f: 0xB0,
+                b: 0x00,
+                c: 0x13,
+                d: 0x00,
+                e: 0xD8,
+                h: 0x01,
+                l: 0x4D,
+            },
+            GameBoyMode::Color => Self {
+                pc: 0x0100,
+                sp: 0xFFFE,
+                a: 0x11,
+                f: 0x80,
+                b: 0x00,
+                c: 0x00,
+                d: 0xFF,
+                e: 0x56,
+                h: 0x00,
+                l: 0x0D,
+            },
+        }
+    }
+
     pub fn read16(&self, reg: Reg16) -> u16 {
         use self::Reg16::*;
         use crate::utils::*;
@@ -165,3 +198,26 @@ impl std::fmt::Display for Registers {
         )
     }
 }
+
+impl SaveState for Registers {
+    fn save_state(&self, state: &mut StateWriter) {
+        for reg in [self.a, self.f, self.b, self.c, self.d, self.e, self.h, self.l].iter() {
+            state.write_u8(*reg);
+        }
+        state.write_u16(self.sp);
+        state.write_u16(self.pc);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) {
+        self.a = state.read_u8();
+        self.f = state.read_u8();
+        self.b = state.read_u8();
+        self.c = state.read_u8();
+        self.d = state.read_u8();
+        self.e = state.read_u8();
+        self.h = state.read_u8();
+        self.l = state.read_u8();
+        self.sp = state.read_u16();
+        self.pc = state.read_u16();
+    }
+}
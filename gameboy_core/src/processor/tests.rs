@@ -0,0 +1,421 @@
+// runs the sm83 single step tests (https://github.com/SingleStepTests/sm83), every base and cb opcode
+// has a json file of cases with the cpu state and memory before and after a single instruction.
+//
+// git clone https://github.com/SingleStepTests/sm83 gameboy_core/tests/sm83
+//
+// they aren't part of the repo so the test is ignored, cargo test and ci never run it. run it by hand with
+//
+// cargo test single_step_tests -- --ignored
+//
+// SM83_TESTS points at another copy of the v1 directory, SM83_OPCODE runs a single file ("8e" or "cb 1f").
+// only the handful of cases written by hand in the same format run with every cargo test
+
+use crate::apu::Apu;
+use crate::bus::Bus;
+use crate::cartridge_controller::CartridgeController;
+use crate::emulator::GameBoyMode;
+use crate::ic::Ic;
+use crate::joypad::Joypad;
+use crate::mmu::{IoDevice, MemRead, MemWrite, Mmu};
+use crate::ppu::dma::DmaManager;
+use crate::ppu::Ppu;
+use crate::processor::{Processor, FETCH_T_CYCLES};
+use crate::ram::Ram;
+use crate::serial::Serial;
+use crate::timer::Timer;
+use serde_json::{json, Value};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+// stop isn't implemented
+const SKIPPED_OPCODES: [&str; 1] = ["10"];
+
+// mismatching cases shown per opcode, the rest are only counted
+const FAILURES_SHOWN: usize = 3;
+
+// a read or write the cpu made, in the order it made them
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Access {
+    Read(u16, u8),
+    Write(u16, u8),
+}
+
+// 64kb of plain memory that logs what the cpu does with it
+struct TestMemory {
+    bytes: Vec<u8>,
+    accesses: Arc<Mutex<Vec<Access>>>,
+}
+
+impl IoDevice for TestMemory {
+    fn read_byte(&mut self, addr: u16) -> MemRead {
+        let value = self.bytes[addr as usize];
+        self.accesses.lock().unwrap().push(Access::Read(addr, value));
+        MemRead::Read(value)
+    }
+
+    fn peek_byte(&self, addr: u16) -> MemRead {
+        MemRead::Read(self.bytes[addr as usize])
+    }
+
+    fn write_byte(&mut self, addr: u16, value: u8) -> MemWrite {
+        self.bytes[addr as usize] = value;
+        self.accesses.lock().unwrap().push(Access::Write(addr, value));
+        MemWrite::Write
+    }
+}
+
+// the bus only holds the interrupt controller the cpu asks, the blank cartridge is never read
+fn test_bus() -> Bus {
+    let rom = vec![0; 0x8000];
+
+    Bus {
+        cartridge_controller: CartridgeController::new(rom, None, GameBoyMode::Classic, true).unwrap(),
+        ppu: Ppu::new(GameBoyMode::Classic),
+        apu: Apu::new(238),
+        timer: Timer::new(),
+        ic: Ic::new(),
+        ram: Ram::new(),
+        joypad: Joypad::new(false),
+        dma_manager: DmaManager::new(),
+        serial: Serial::new(GameBoyMode::Classic),
+        devices: Vec::new(),
+    }
+}
+
+fn vectors_dir() -> PathBuf {
+    match std::env::var("SM83_TESTS") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/sm83/v1"),
+    }
+}
+
+fn number(state: &Value, field: &str) -> u16 {
+    state[field].as_u64().unwrap_or_else(|| panic!("missing {}", field)) as u16
+}
+
+fn byte(state: &Value, field: &str) -> u8 {
+    number(state, field) as u8
+}
+
+// the [adder, value] pairs of a state's ram
+fn ram(state: &Value) -> Vec<(u16, u8)> {
+    state["ram"]
+        .as_array()
+        .expect("missing ram")
+        .iter()
+        .map(|pair| (pair[0].as_u64().unwrap() as u16, pair[1].as_u64().unwrap() as u8))
+        .collect()
+}
+
+// the reads and writes of a case's machine cycles, the cycles where the cpu is busy inside are left out.
+// the cpu doesn't run a machine cycle at a time, so the order of the accesses is checked but not which
+// cycle they land in
+fn accesses(case: &Value) -> Vec<Access> {
+    case["cycles"]
+        .as_array()
+        .expect("missing cycles")
+        .iter()
+        .filter_map(|cycle| {
+            let addr = cycle[0].as_u64()? as u16;
+            let value = cycle[1].as_u64()? as u8;
+
+            match cycle[2].as_str()? {
+                kind if kind.contains('r') => Some(Access::Read(addr, value)),
+                kind if kind.contains('w') => Some(Access::Write(addr, value)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn describe_accesses(accesses: &[Access]) -> String {
+    let accesses: Vec<String> = accesses
+        .iter()
+        .map(|access| match access {
+            Access::Read(addr, value) => format!("read {:02x} from {:04x}", value, addr),
+            Access::Write(addr, value) => format!("wrote {:02x} to {:04x}", value, addr),
+        })
+        .collect();
+
+    format!("[{}]", accesses.join(", "))
+}
+
+// runs one case, a description of every difference from the expected state
+fn run_case(case: &Value) -> Vec<String> {
+    let initial = &case["initial"];
+    let expected = &case["final"];
+
+    let accesses_made = Arc::new(Mutex::new(Vec::new()));
+    let mut memory = TestMemory {
+        bytes: vec![0; 0x10000],
+        accesses: accesses_made.clone(),
+    };
+    for (addr, value) in ram(initial) {
+        memory.bytes[addr as usize] = value;
+    }
+    let mut mmu = Mmu::flat(test_bus(), Box::new(memory));
+
+    let mut processor = Processor::new();
+    processor.ime = byte(initial, "ime") != 0;
+    // cases right after an ei say that it is still pending
+    processor.ime_pending = initial["ei"].as_u64() == Some(1);
+    let registers = &mut processor.registers;
+    registers.a = byte(initial, "a");
+    registers.f = byte(initial, "f");
+    registers.b = byte(initial, "b");
+    registers.c = byte(initial, "c");
+    registers.d = byte(initial, "d");
+    registers.e = byte(initial, "e");
+    registers.h = byte(initial, "h");
+    registers.l = byte(initial, "l");
+    registers.sp = number(initial, "sp");
+    registers.pc = number(initial, "pc");
+
+    let cycles = match panic::catch_unwind(AssertUnwindSafe(|| processor.cycle(&mut mmu))) {
+        Ok(cycles) => cycles,
+        Err(_) => return vec!["panicked".to_string()],
+    };
+
+    let mut differences = Vec::new();
+    let registers = processor.registers;
+
+    let bytes = [
+        ("a", registers.a),
+        ("b", registers.b),
+        ("c", registers.c),
+        ("d", registers.d),
+        ("e", registers.e),
+        ("h", registers.h),
+        ("l", registers.l),
+    ];
+    for (name, actual) in bytes.iter() {
+        if *actual != byte(expected, name) {
+            differences.push(format!("{} is {:02x} expected {:02x}", name, actual, byte(expected, name)));
+        }
+    }
+
+    if registers.f != byte(expected, "f") {
+        differences.push(format!("f is {:08b} expected {:08b} (znhc----)", registers.f, byte(expected, "f")));
+    }
+
+    for (name, actual) in [("sp", registers.sp), ("pc", registers.pc)].iter() {
+        if *actual != number(expected, name) {
+            differences.push(format!("{} is {:04x} expected {:04x}", name, actual, number(expected, name)));
+        }
+    }
+
+    if processor.ime != (byte(expected, "ime") != 0) {
+        differences.push(format!("ime is {} expected {}", processor.ime as u8, byte(expected, "ime")));
+    }
+
+    if let Some(ei) = expected["ei"].as_u64() {
+        if processor.ime_pending != (ei != 0) {
+            differences.push(format!("ei pending is {} expected {}", processor.ime_pending as u8, ei));
+        }
+    }
+
+    for (addr, value) in ram(expected) {
+        let actual = mmu.peek_byte(addr);
+        if actual != value {
+            differences.push(format!("[{:04x}] is {:02x} expected {:02x}", addr, actual, value));
+        }
+    }
+
+    // every entry is a machine cycle
+    let expected_cycles = case["cycles"].as_array().expect("missing cycles").len() as u32 * FETCH_T_CYCLES;
+    if cycles != expected_cycles {
+        differences.push(format!("took {} t cycles expected {}", cycles, expected_cycles));
+    }
+
+    let accesses_made = accesses_made.lock().unwrap();
+    let expected_accesses = accesses(case);
+    if *accesses_made != expected_accesses {
+        differences.push(format!(
+            "{} expected {}",
+            describe_accesses(&accesses_made),
+            describe_accesses(&expected_accesses)
+        ));
+    }
+
+    differences
+}
+
+// a case in the format of the vectors, every register but the ones given is 0
+fn case(name: &str, initial: Value, expected: Value, cycles: Value) -> Value {
+    let registers = |state: Value| {
+        let mut full = json!({
+            "a": 0, "b": 0, "c": 0, "d": 0, "e": 0, "f": 0, "h": 0, "l": 0,
+            "pc": 0xC000, "sp": 0xD000, "ime": 0, "ram": []
+        });
+        for (field, value) in state.as_object().unwrap() {
+            full[field] = value.clone();
+        }
+        full
+    };
+
+    json!({ "name": name, "initial": registers(initial), "final": registers(expected), "cycles": cycles })
+}
+
+// written by hand from the pandocs timings so the harness runs without the vectors
+#[test]
+fn hand_written_cases() {
+    let cases = [
+        case(
+            "3e ld a, n",
+            json!({ "ram": [[0xC000, 0x3E], [0xC001, 0x42]] }),
+            json!({ "a": 0x42, "pc": 0xC002 }),
+            json!([[0xC000, 0x3E, "r-m"], [0xC001, 0x42, "r-m"]]),
+        ),
+        case(
+            "ea ld (nn), a",
+            json!({ "a": 0x42, "ram": [[0xC000, 0xEA], [0xC001, 0x00], [0xC002, 0xC1]] }),
+            json!({ "a": 0x42, "pc": 0xC003, "ram": [[0xC100, 0x42]] }),
+            json!([[0xC000, 0xEA, "r-m"], [0xC001, 0x00, "r-m"], [0xC002, 0xC1, "r-m"], [0xC100, 0x42, "-wm"]]),
+        ),
+        case(
+            "cb 7e bit 7, (hl)",
+            json!({ "h": 0xC1, "ram": [[0xC000, 0xCB], [0xC001, 0x7E], [0xC100, 0x80]] }),
+            json!({ "h": 0xC1, "f": 0x20, "pc": 0xC002 }),
+            json!([[0xC000, 0xCB, "r-m"], [0xC001, 0x7E, "r-m"], [0xC100, 0x80, "r-m"]]),
+        ),
+        case(
+            "cb 00 rlc b",
+            json!({ "b": 0x85, "ram": [[0xC000, 0xCB], [0xC001, 0x00]] }),
+            json!({ "b": 0x0B, "f": 0x10, "pc": 0xC002, "ram": [[0xC000, 0xCB], [0xC001, 0x00]] }),
+            json!([[0xC000, 0xCB, "r-m"], [0xC001, 0x00, "r-m"]]),
+        ),
+        case(
+            "cb 37 swap a",
+            json!({ "a": 0x00, "f": 0xF0, "ram": [[0xC000, 0xCB], [0xC001, 0x37]] }),
+            json!({ "f": 0x80, "pc": 0xC002, "ram": [[0xC000, 0xCB], [0xC001, 0x37]] }),
+            json!([[0xC000, 0xCB, "r-m"], [0xC001, 0x37, "r-m"]]),
+        ),
+        case(
+            "cb 3e srl (hl)",
+            json!({ "h": 0xC1, "ram": [[0xC000, 0xCB], [0xC001, 0x3E], [0xC100, 0x03]] }),
+            json!({ "h": 0xC1, "f": 0x10, "pc": 0xC002, "ram": [[0xC100, 0x01]] }),
+            json!([[0xC000, 0xCB, "r-m"], [0xC001, 0x3E, "r-m"], [0xC100, 0x03, "r-m"], [0xC100, 0x01, "-wm"]]),
+        ),
+        case(
+            "c5 push bc",
+            json!({ "b": 0x12, "c": 0x34, "ram": [[0xC000, 0xC5]] }),
+            json!({ "b": 0x12, "c": 0x34, "sp": 0xCFFE, "pc": 0xC001, "ram": [[0xCFFE, 0x34], [0xCFFF, 0x12]] }),
+            json!([[0xC000, 0xC5, "r-m"], [null, null, "---"], [0xCFFF, 0x12, "-wm"], [0xCFFE, 0x34, "-wm"]]),
+        ),
+        case(
+            "cd call",
+            json!({ "ram": [[0xC000, 0xCD], [0xC001, 0x00], [0xC002, 0xC8]] }),
+            json!({ "sp": 0xCFFE, "pc": 0xC800, "ram": [[0xCFFE, 0x03], [0xCFFF, 0xC0]] }),
+            json!([
+                [0xC000, 0xCD, "r-m"],
+                [0xC001, 0x00, "r-m"],
+                [0xC002, 0xC8, "r-m"],
+                [null, null, "---"],
+                [0xCFFF, 0xC0, "-wm"],
+                [0xCFFE, 0x03, "-wm"]
+            ]),
+        ),
+        case(
+            "d1 pop de",
+            json!({ "ram": [[0xC000, 0xD1], [0xD000, 0x78], [0xD001, 0x56]] }),
+            json!({ "d": 0x56, "e": 0x78, "sp": 0xD002, "pc": 0xC001 }),
+            json!([[0xC000, 0xD1, "r-m"], [0xD000, 0x78, "r-m"], [0xD001, 0x56, "r-m"]]),
+        ),
+        case(
+            "f3 di",
+            json!({ "ime": 1, "ram": [[0xC000, 0xF3]] }),
+            json!({ "ime": 0, "pc": 0xC001 }),
+            json!([[0xC000, 0xF3, "r-m"]]),
+        ),
+        case(
+            "d9 reti",
+            json!({ "ram": [[0xC000, 0xD9], [0xD000, 0x34], [0xD001, 0x12]] }),
+            json!({ "ime": 1, "sp": 0xD002, "pc": 0x1234 }),
+            json!([[0xC000, 0xD9, "r-m"], [0xD000, 0x34, "r-m"], [0xD001, 0x12, "r-m"], [null, null, "---"]]),
+        ),
+        case(
+            "fb ei",
+            json!({ "ram": [[0xC000, 0xFB]] }),
+            json!({ "ime": 0, "ei": 1, "pc": 0xC001 }),
+            json!([[0xC000, 0xFB, "r-m"]]),
+        ),
+        case(
+            "00 nop after ei",
+            json!({ "ei": 1, "ram": [[0xC000, 0x00]] }),
+            json!({ "ime": 1, "ei": 0, "pc": 0xC001 }),
+            json!([[0xC000, 0x00, "r-m"]]),
+        ),
+        case(
+            "f3 di after ei",
+            json!({ "ei": 1, "ram": [[0xC000, 0xF3]] }),
+            json!({ "ime": 0, "ei": 0, "pc": 0xC001 }),
+            json!([[0xC000, 0xF3, "r-m"]]),
+        ),
+    ];
+
+    for case in cases.iter() {
+        let differences = run_case(case);
+        assert!(differences.is_empty(), "{}: {}", case["name"], differences.join(", "));
+    }
+}
+
+#[test]
+#[ignore]
+fn single_step_tests() {
+    let dir = vectors_dir();
+    let only = std::env::var("SM83_OPCODE").ok();
+
+    let mut files: Vec<PathBuf> = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+            .collect(),
+        Err(_) => panic!(
+            "no sm83 test vectors in {}, clone https://github.com/SingleStepTests/sm83 into gameboy_core/tests/sm83",
+            dir.display()
+        ),
+    };
+    files.sort();
+
+    // the panics are counted as failures, their messages would drown the report
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+
+    let mut report = Vec::new();
+    let mut cases = 0;
+
+    for path in files {
+        let opcode = path.file_stem().unwrap().to_string_lossy().into_owned();
+        if SKIPPED_OPCODES.contains(&opcode.as_str()) || only.as_ref().is_some_and(|only| *only != opcode) {
+            continue;
+        }
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let file: Value = serde_json::from_str(&text).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        let file = file.as_array().unwrap_or_else(|| panic!("{}: expected a list of cases", path.display()));
+
+        let failures: Vec<String> = file
+            .iter()
+            .filter_map(|case| {
+                let differences = run_case(case);
+                if differences.is_empty() {
+                    None
+                } else {
+                    Some(format!("    {}: {}", case["name"].as_str().unwrap_or("?"), differences.join(", ")))
+                }
+            })
+            .collect();
+
+        cases += file.len();
+
+        if !failures.is_empty() {
+            report.push(format!("{}: {} of {} cases failed", opcode, failures.len(), file.len()));
+            report.extend(failures.into_iter().take(FAILURES_SHOWN));
+        }
+    }
+
+    panic::set_hook(hook);
+
+    assert!(report.is_empty(), "{} cases run\n{}", cases, report.join("\n"));
+}
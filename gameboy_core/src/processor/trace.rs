@@ -0,0 +1,67 @@
+use crate::mmu::Mmu;
+use crate::processor::registers::Registers;
+use std::fmt;
+use std::io::{self, BufWriter, Write};
+
+// a line per instruction in the gameboy doctor format, the state right before the instruction runs:
+//
+// A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,50,01
+pub struct Trace {
+    out: BufWriter<Box<dyn Write + Send>>,
+    error: Option<io::Error>, // the first failed write, nothing more is written after it
+}
+
+impl Trace {
+    pub fn new(out: Box<dyn Write + Send>) -> Trace {
+        Trace {
+            out: BufWriter::new(out),
+            error: None,
+        }
+    }
+
+    pub fn log(&mut self, registers: &Registers, mmu: &Mmu) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let pc = registers.pc;
+        let pcmem = |offset: u16| mmu.peek_byte(pc.wrapping_add(offset));
+
+        let written = writeln!(
+            self.out,
+            "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+            registers.a,
+            registers.f,
+            registers.b,
+            registers.c,
+            registers.d,
+            registers.e,
+            registers.h,
+            registers.l,
+            registers.sp,
+            pc,
+            pcmem(0),
+            pcmem(1),
+            pcmem(2),
+            pcmem(3),
+        );
+
+        if let Err(e) = written {
+            self.error = Some(e);
+        }
+    }
+
+    // flushes what is left, returns the first error the trace ran into
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.out.flush(),
+        }
+    }
+}
+
+impl fmt::Debug for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Trace")
+    }
+}
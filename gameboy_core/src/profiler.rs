@@ -0,0 +1,194 @@
+use crate::symbols::SymbolTable;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+// rows in each table of the text report
+const REPORT_ROWS: usize = 32;
+
+// a backwards jump at most this far closes a loop the profiler looks at for v-blank waits
+const MAX_WAIT_LOOP_SIZE: u16 = 32;
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Hits {
+    pub count: u64, // times the instruction ran
+    pub cycles: u64, // t cycles it took, a halt instruction also gets the cycles spent halted
+}
+
+// what the cpu spent its time on while profiling
+#[derive(Clone, Default, Debug)]
+pub struct Profile {
+    pub hits: HashMap<(usize, u16), Hits>, // bank, adder
+    pub cycles: u64, // every cpu cycle, halted ones included
+    pub halt_cycles: u64,
+    pub vblank_wait_cycles: u64, // spent in loops that poll LY or STAT
+}
+
+// collects the profile as the cpu runs, the processor hands it every instruction
+#[derive(Default, Debug)]
+pub struct Profiler {
+    profile: Profile,
+    halt_adder: (usize, u16), // the halt instruction the cpu is waiting in
+    loop_cycles: u64, // since the last jump, a wait loop is straight code ending in a jump back
+    loop_polled: bool, // LY or STAT were read since the last jump
+}
+
+fn percent(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / total as f64
+    }
+}
+
+impl Profiler {
+    // an instruction at pc ran and moved the cpu to next_pc, polled is whether it read LY or STAT
+    pub fn executed(&mut self, bank: usize, pc: u16, next_pc: u16, cycles: u32, polled: bool) {
+        let cycles = cycles as u64;
+
+        let hits = self.profile.hits.entry((bank, pc)).or_default();
+        hits.count += 1;
+        hits.cycles += cycles;
+        self.profile.cycles += cycles;
+
+        self.halt_adder = (bank, pc);
+
+        self.loop_cycles += cycles;
+        self.loop_polled |= polled;
+
+        // anything but the next instruction is a jump, instructions are at most 3 bytes
+        let sequential = next_pc > pc && next_pc - pc <= 3;
+        if !sequential {
+            let wait_loop = next_pc <= pc && pc - next_pc <= MAX_WAIT_LOOP_SIZE;
+            if wait_loop && self.loop_polled {
+                self.profile.vblank_wait_cycles += self.loop_cycles;
+            }
+
+            self.loop_cycles = 0;
+            self.loop_polled = false;
+        }
+    }
+
+    // the cpu spent cycles halted, they go to the halt instruction it is waiting in
+    pub fn halted(&mut self, cycles: u32) {
+        let cycles = cycles as u64;
+
+        self.profile.hits.entry(self.halt_adder).or_default().cycles += cycles;
+        self.profile.cycles += cycles;
+        self.profile.halt_cycles += cycles;
+    }
+
+    pub fn profile(&self) -> &Profile {
+        &self.profile
+    }
+
+    pub fn finish(self) -> Profile {
+        self.profile
+    }
+}
+
+impl Profile {
+    // the hits summed up by the function they are in, functions come from the symbols and
+    // without one every bank counts as a single function
+    pub fn functions(&self, symbols: &SymbolTable) -> Vec<(String, Hits)> {
+        let mut functions: HashMap<String, Hits> = HashMap::new();
+
+        for ((bank, adder), hits) in self.hits.iter() {
+            let name = match symbols.function(*bank, *adder) {
+                Some(name) => name.to_string(),
+                None => format!("bank {:02x}", bank),
+            };
+
+            let function = functions.entry(name).or_default();
+            function.count += hits.count;
+            function.cycles += hits.cycles;
+        }
+
+        let mut functions: Vec<(String, Hits)> = functions.into_iter().collect();
+        functions.sort_by(|a, b| b.1.cycles.cmp(&a.1.cycles).then_with(|| a.0.cmp(&b.0)));
+
+        functions
+    }
+
+    // the hottest functions and instructions as a text table
+    pub fn report(&self, symbols: &SymbolTable) -> String {
+        let mut report = format!(
+            "{} cpu cycles, {} halted ({:.1}%), {} waiting for v-blank ({:.1}%)\n",
+            self.cycles,
+            self.halt_cycles,
+            percent(self.halt_cycles, self.cycles),
+            self.vblank_wait_cycles,
+            percent(self.vblank_wait_cycles, self.cycles),
+        );
+
+        report += &format!("\n{:>12} {:>6} {:>12}  function\n", "cycles", "%", "instructions");
+        for (name, hits) in self.functions(symbols).iter().take(REPORT_ROWS) {
+            report += &format!(
+                "{:>12} {:>6.2} {:>12}  {}\n",
+                hits.cycles,
+                percent(hits.cycles, self.cycles),
+                hits.count,
+                name
+            );
+        }
+
+        let mut instructions: Vec<(&(usize, u16), &Hits)> = self.hits.iter().collect();
+        instructions.sort_by(|a, b| b.1.cycles.cmp(&a.1.cycles).then_with(|| a.0.cmp(b.0)));
+
+        report += &format!("\n{:>12} {:>6} {:>12}  address\n", "cycles", "%", "count");
+        for ((bank, adder), hits) in instructions.iter().take(REPORT_ROWS) {
+            let label = symbols.symbolize(*bank, *adder).unwrap_or_default();
+            report += &format!(
+                "{:>12} {:>6.2} {:>12}  {:02x}:{:04x} {}\n",
+                hits.cycles,
+                percent(hits.cycles, self.cycles),
+                hits.count,
+                bank,
+                adder,
+                label
+            );
+        }
+
+        report
+    }
+
+    // the profile in the callgrind format for kcachegrind and friends, every bank is a file,
+    // every function a function and every instruction a position with its count and cycles
+    pub fn write_callgrind(&self, symbols: &SymbolTable, out: &mut dyn Write) -> io::Result<()> {
+        let instructions: u64 = self.hits.values().map(|hits| hits.count).sum();
+
+        writeln!(out, "# callgrind format")?;
+        writeln!(out, "version: 1")?;
+        writeln!(out, "creator: gameboy_core")?;
+        writeln!(out, "positions: instr")?;
+        writeln!(out, "events: Instructions Cycles")?;
+        writeln!(out, "summary: {} {}", instructions, self.cycles)?;
+
+        let mut hits: Vec<(&(usize, u16), &Hits)> = self.hits.iter().collect();
+        hits.sort_by_key(|(location, _)| **location);
+
+        let mut file = None;
+        let mut function = None;
+
+        for ((bank, adder), hits) in hits {
+            if file != Some(*bank) {
+                writeln!(out, "\nfl=bank {:02x}", bank)?;
+                file = Some(*bank);
+                function = None;
+            }
+
+            let name = match symbols.function(*bank, *adder) {
+                Some(name) => name.to_string(),
+                None => format!("bank {:02x}", bank),
+            };
+
+            if function.as_ref() != Some(&name) {
+                writeln!(out, "fn={}", name)?;
+                function = Some(name);
+            }
+
+            writeln!(out, "{:#06x} {} {}", adder, hits.count, hits.cycles)?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,10 +1,11 @@
-use crate::mmu::*;
+use crate::state::{SaveState, StateReader, StateWriter};
 
 const ZERO_PAGE_SIZE : usize = 0x7F;
 const WRAM_BANK_SIZE : usize = 0x1000;
 const WRAM_BANK_COUNT : usize = 8;
 
 
+// work ram and the zero page, the mmu reads and writes these directly
 pub struct Ram {
     selected_wram: usize,
     wram_banks: Vec<Vec<u8>>,
@@ -19,46 +20,67 @@ impl Ram {
             zero_ram_page: vec![0; ZERO_PAGE_SIZE],
         }
     }
-}
 
+    // 0xC000 - 0xDFFF
+    #[inline]
+    pub fn read_wram(&self, adder: u16) -> u8 {
+        if adder < 0xD000 {
+            self.wram_banks[0][adder as usize - 0xC000]
+        } else {
+            self.wram_banks[self.selected_wram][adder as usize - 0xD000]
+        }
+    }
+
+    #[inline]
+    pub fn write_wram(&mut self, adder: u16, value: u8) {
+        if adder < 0xD000 {
+            self.wram_banks[0][adder as usize - 0xC000] = value;
+        } else {
+            self.wram_banks[self.selected_wram][adder as usize - 0xD000] = value;
+        }
+    }
+
+    // 0xD000 - 0xDFFF in the given bank no matter which one is selected
+    pub fn write_wram_bank(&mut self, bank: usize, adder: u16, value: u8) {
+        self.wram_banks[bank % WRAM_BANK_COUNT][adder as usize - 0xD000] = value;
+    }
+
+    // 0xFF80 - 0xFFFE
+    #[inline]
+    pub fn read_hram(&self, adder: u16) -> u8 {
+        self.zero_ram_page[adder as usize - 0xFF80]
+    }
 
+    #[inline]
+    pub fn write_hram(&mut self, adder: u16, value: u8) {
+        self.zero_ram_page[adder as usize - 0xFF80] = value;
+    }
 
-impl IoDevice for Ram {
-    fn read_byte(&mut self, mmu: &Mmu, adder: u16) -> MemRead { 
-        match adder {
-            0xC000 ..= 0xCFFF => MemRead::Read(self.wram_banks[0][adder as usize - 0xC000]),
-            0xD000 ..= 0xDFFF => MemRead::Read(self.wram_banks[self.selected_wram][adder as usize - 0xD000]),
-            0xE000 ..= 0xFDFF => self.read_byte(mmu, (adder - 0xE000) + 0xC000),
-            0xFF70 => MemRead::Read(self.selected_wram as u8),
-            0xFF80 ..= 0xFFFE => MemRead::Read(self.zero_ram_page[adder as usize - 0xFF80]),
-            _ => MemRead::Ignore
+    // 0xFF70
+    pub fn read_bank_select(&self) -> u8 {
+        self.selected_wram as u8
+    }
+
+    pub fn write_bank_select(&mut self, value: u8) {
+        // self.selected_wram = value.max(1) as usize;
+        self.selected_wram = (value & 0x3).max(1) as usize; // TODO: if i remove the & then we crash
+    }
+}
+
+impl SaveState for Ram {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_usize(self.selected_wram);
+        for bank in self.wram_banks.iter() {
+            state.write_bytes(bank);
         }
+        state.write_bytes(&self.zero_ram_page);
     }
 
-    fn write_byte(&mut self, mmu: &Mmu, adder: u16, value: u8) -> MemWrite { 
-        match adder {
-            0xC000 ..= 0xCFFF => {
-                self.wram_banks[0][adder as usize - 0xC000] = value;
-                MemWrite::Write
-            },
-            0xD000 ..= 0xDFFF => {
-                self.wram_banks[self.selected_wram][adder as usize - 0xD000] = value;
-                MemWrite::Write
-            },
-            0xE000 ..= 0xFDFF => {
-                self.write_byte(mmu, (adder - 0xE000) + 0xC000, value);
-                MemWrite::Write
-            },
-            0xFF70 => {
-                // self.selected_wram = value.max(1) as usize;
-                self.selected_wram = (value & 0x3).max(1) as usize; // TODO: if i remove the & then we crash
-                MemWrite::Write
-            },
-            0xFF80 ..= 0xFFFE => {
-                self.zero_ram_page[adder as usize - 0xFF80] = value;
-                MemWrite::Write
-            },
-            _ => MemWrite::Ignore
+    fn load_state(&mut self, state: &mut StateReader) {
+        self.selected_wram = state.read_index(self.wram_banks.len(), "wram bank out of range");
+        for bank in self.wram_banks.iter_mut() {
+            state.read_bytes(bank);
         }
-     }
-}
\ No newline at end of file
+        state.read_bytes(&mut self.zero_ram_page);
+    }
+}
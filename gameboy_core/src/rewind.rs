@@ -0,0 +1,155 @@
+use std::collections::VecDeque;
+
+// keeps the newest snapshot whole and every older one as a delta against the snapshot that came after it,
+// so stepping back is undoing the newest delta. the oldest deltas are dropped once the budget is used up.
+pub struct RewindBuffer {
+    interval: u32, // frames between snapshots
+    budget: usize, // bytes
+
+    frames: u32, // frames since the last snapshot
+    newest: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>,
+    used: usize,
+}
+
+impl RewindBuffer {
+    pub fn new(interval: u32, budget: usize) -> RewindBuffer {
+        RewindBuffer {
+            interval: interval.max(1),
+            budget,
+            frames: 0,
+            newest: None,
+            deltas: VecDeque::new(),
+            used: 0,
+        }
+    }
+
+    // called once a frame, returns true when a snapshot is due
+    pub fn frame(&mut self) -> bool {
+        self.frames += 1;
+
+        if self.frames >= self.interval {
+            self.frames = 0;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn push(&mut self, state: Vec<u8>) {
+        if let Some(newest) = self.newest.take() {
+            if newest.len() == state.len() {
+                let delta = encode_delta(&state, &newest);
+                self.used += delta.len();
+                self.deltas.push_back(delta);
+            } else {
+                // snapshots of different sizes can't be diffed, start over
+                self.clear();
+            }
+        }
+
+        self.newest = Some(state);
+
+        while self.used + self.newest_len() > self.budget {
+            match self.deltas.pop_front() {
+                Some(delta) => self.used -= delta.len(),
+                None => break,
+            }
+        }
+    }
+
+    // steps back to the snapshot before the newest one and returns it
+    pub fn pop(&mut self) -> Option<&[u8]> {
+        let delta = self.deltas.pop_back()?;
+        self.used -= delta.len();
+
+        let newest = self.newest.as_mut()?;
+        apply_delta(newest, &delta);
+
+        // a rewind restarts the interval so the next snapshot is a full interval away
+        self.frames = 0;
+
+        self.newest.as_deref()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames = 0;
+        self.newest = None;
+        self.deltas.clear();
+        self.used = 0;
+    }
+
+    fn newest_len(&self) -> usize {
+        self.newest.as_ref().map_or(0, |newest| newest.len())
+    }
+}
+
+// the delta is the xor of both snapshots run length encoded.
+// snapshots barely change between frames so the xor is mostly zeros,
+// it is written as pairs of a zero run length and a literal run length followed by the literal bytes.
+fn encode_delta(current: &[u8], previous: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    let mut index = 0;
+
+    while index < current.len() {
+        let zeros_start = index;
+        while index < current.len() && current[index] == previous[index] {
+            index += 1;
+        }
+
+        let literal_start = index;
+        while index < current.len() && current[index] != previous[index] {
+            index += 1;
+        }
+
+        write_length(&mut delta, literal_start - zeros_start);
+        write_length(&mut delta, index - literal_start);
+        for offset in literal_start..index {
+            delta.push(current[offset] ^ previous[offset]);
+        }
+    }
+
+    delta
+}
+
+fn apply_delta(state: &mut [u8], delta: &[u8]) {
+    let mut index = 0;
+    let mut position = 0;
+
+    while position < delta.len() {
+        index += read_length(delta, &mut position);
+        let literals = read_length(delta, &mut position);
+
+        for byte in &delta[position..position + literals] {
+            state[index] ^= byte;
+            index += 1;
+        }
+        position += literals;
+    }
+}
+
+// lengths are written 7 bits at a time, the high bit marks that more bytes follow
+fn write_length(delta: &mut Vec<u8>, mut length: usize) {
+    while length >= 0x80 {
+        delta.push((length as u8 & 0x7F) | 0x80);
+        length >>= 7;
+    }
+    delta.push(length as u8);
+}
+
+fn read_length(delta: &[u8], position: &mut usize) -> usize {
+    let mut length = 0;
+    let mut shift = 0;
+
+    loop {
+        let byte = delta[*position];
+        *position += 1;
+
+        length |= ((byte & 0x7F) as usize) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            return length;
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use crate::emulator::GameBoyMode;
+use crate::ic::Ic;
+use crate::mmu::{IoDevice, MemRead, MemWrite};
+use crate::state::{SaveState, StateReader, StateWriter};
+
+// t cycles to shift a whole byte with the 8192hz internal clock, the color fast clock is 32 times faster
+const BYTE_CYCLES: u32 = 8 * 512;
+const FAST_BYTE_CYCLES: u32 = BYTE_CYCLES / 32;
+
+const TRANSFER_START: u8 = 0x80;
+const FAST_CLOCK: u8 = 0x02;
+const INTERNAL_CLOCK: u8 = 0x01;
+
+// whatever is plugged into the link port. the game boy drives the clock, every byte it shifts
+// out is exchanged for the byte the other end shifts back at the same time
+pub trait SerialTransport: Send {
+    fn exchange(&mut self, sent: u8) -> u8;
+}
+
+// nothing plugged in, the data line is pulled high
+pub struct Disconnected;
+
+impl SerialTransport for Disconnected {
+    fn exchange(&mut self, _sent: u8) -> u8 {
+        0xFF
+    }
+}
+
+// the link port registers, sb (0xff01) and sc (0xff02)
+pub struct Serial {
+    data: u8,
+    control: u8,
+    clock: u32, // t cycles into the running transfer
+    game_boy_mode: GameBoyMode,
+    transport: Box<dyn SerialTransport>,
+}
+
+impl Serial {
+    pub fn new(game_boy_mode: GameBoyMode) -> Serial {
+        Serial {
+            data: 0,
+            control: 0,
+            clock: 0,
+            game_boy_mode,
+            transport: Box::new(Disconnected),
+        }
+    }
+
+    pub fn connect(&mut self, transport: Box<dyn SerialTransport>) {
+        self.transport = transport;
+    }
+
+    pub fn cycle(&mut self, clock: u32, ic: &mut Ic) {
+        // with the external clock the other end would have to drive the transfer, none of the transports do
+        if self.control & (TRANSFER_START | INTERNAL_CLOCK) != TRANSFER_START | INTERNAL_CLOCK {
+            return;
+        }
+
+        let byte_cycles = match self.game_boy_mode {
+            GameBoyMode::Color if self.control & FAST_CLOCK != 0 => FAST_BYTE_CYCLES,
+            _ => BYTE_CYCLES,
+        };
+
+        self.clock += clock;
+
+        if self.clock >= byte_cycles {
+            self.clock = 0;
+            self.data = self.transport.exchange(self.data);
+            self.control &= !TRANSFER_START;
+            ic.serial(true);
+        }
+    }
+}
+
+impl IoDevice for Serial {
+    fn peek_byte(&self, adder: u16) -> MemRead {
+        match adder {
+            0xFF01 => MemRead::Read(self.data),
+            // the unused bits read high
+            0xFF02 => MemRead::Read(self.control | 0x7C),
+            _ => MemRead::Ignore,
+        }
+    }
+
+    fn write_byte(&mut self, adder: u16, value: u8) -> MemWrite {
+        match adder {
+            0xFF01 => {
+                self.data = value;
+                MemWrite::Write
+            }
+            0xFF02 => {
+                self.control = value & (TRANSFER_START | FAST_CLOCK | INTERNAL_CLOCK);
+                self.clock = 0;
+                MemWrite::Write
+            }
+            _ => MemWrite::Ignore,
+        }
+    }
+}
+
+// the transport is plugged in from outside, it isn't part of a snapshot
+impl SaveState for Serial {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.data);
+        state.write_u8(self.control);
+        state.write_u32(self.clock);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) {
+        self.data = state.read_u8();
+        self.control = state.read_u8();
+        self.clock = state.read_u32();
+    }
+}
@@ -0,0 +1,127 @@
+// every component that holds emulated state knows how to write itself into a snapshot and read itself back.
+// components are always written in the same order and with the same size for a given rom,
+// so snapshots carry no field names or lengths, the emulator checks the total size before loading one.
+pub trait SaveState {
+    fn save_state(&self, state: &mut StateWriter);
+
+    fn load_state(&mut self, state: &mut StateReader);
+}
+
+pub struct StateWriter {
+    buffer: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> StateWriter {
+        StateWriter { buffer: Vec::new() }
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.buffer.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u8(value as u8);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.buffer
+    }
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    invalid: Option<&'static str>, // the first field that was out of range
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> StateReader<'a> {
+        StateReader {
+            data,
+            position: 0,
+            invalid: None,
+        }
+    }
+
+    fn take(&mut self, len: usize) -> &'a [u8] {
+        let bytes = &self.data[self.position..self.position + len];
+        self.position += len;
+        bytes
+    }
+
+    pub fn read_u8(&mut self) -> u8 {
+        self.take(1)[0]
+    }
+
+    pub fn read_bool(&mut self) -> bool {
+        self.read_u8() != 0
+    }
+
+    pub fn read_u16(&mut self) -> u16 {
+        let mut bytes = [0; 2];
+        bytes.copy_from_slice(self.take(2));
+        u16::from_le_bytes(bytes)
+    }
+
+    pub fn read_u32(&mut self) -> u32 {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4));
+        u32::from_le_bytes(bytes)
+    }
+
+    pub fn read_u64(&mut self) -> u64 {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8));
+        u64::from_le_bytes(bytes)
+    }
+
+    pub fn read_usize(&mut self) -> usize {
+        self.read_u64() as usize
+    }
+
+    // an index into something len long, one past the end marks the state invalid and reads as 0
+    pub fn read_index(&mut self, len: usize, reason: &'static str) -> usize {
+        let index = self.read_usize();
+        if index >= len {
+            self.reject(reason);
+            return 0;
+        }
+
+        index
+    }
+
+    // components can't fail while loading, the emulator refuses the state once everything was read
+    pub fn reject(&mut self, reason: &'static str) {
+        self.invalid.get_or_insert(reason);
+    }
+
+    pub fn invalid(&self) -> Option<&'static str> {
+        self.invalid
+    }
+
+    // fills the whole buffer, buffers keep their size for the life time of the emulator
+    pub fn read_bytes(&mut self, buffer: &mut [u8]) {
+        buffer.copy_from_slice(self.take(buffer.len()));
+    }
+}
@@ -0,0 +1,139 @@
+use crate::emulator::EmulatorError;
+use std::collections::HashMap;
+
+// symbol files as rgbds (rgblink -n) and wla-dx write them, one "bank:adder label" per line:
+//
+// ; comments run to the end of the line
+// 00:0150 Start
+// 01:4000 Banked.local
+//
+// wla-dx splits the file in [sections], only [labels] holds symbols
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symbol {
+    pub bank: usize,
+    pub adder: u16,
+    pub name: String,
+}
+
+#[derive(Default)]
+pub struct SymbolTable {
+    symbols: Vec<Symbol>, // sorted by bank then adder
+    by_name: HashMap<String, usize>,
+}
+
+// a label only names the memory it sits in, 0x3fff+1 is not a label in the next bank
+fn region_start(adder: u16) -> u16 {
+    match adder {
+        0x0000 ..= 0x3FFF => 0x0000,
+        0x4000 ..= 0x7FFF => 0x4000,
+        0x8000 ..= 0x9FFF => 0x8000,
+        0xA000 ..= 0xBFFF => 0xA000,
+        0xC000 ..= 0xCFFF => 0xC000,
+        0xD000 ..= 0xDFFF => 0xD000,
+        0xE000 ..= 0xFDFF => 0xE000,
+        0xFE00 ..= 0xFEFF => 0xFE00,
+        0xFF00 ..= 0xFF7F => 0xFF00,
+        0xFF80 ..= 0xFFFF => 0xFF80,
+    }
+}
+
+fn parse_line(line: &str) -> Result<Option<Symbol>, EmulatorError> {
+    let line = match line.find(';') {
+        Some(comment) => &line[..comment],
+        None => line,
+    };
+
+    let mut words = line.split_whitespace();
+    let (location, name) = match (words.next(), words.next(), words.next()) {
+        (None, _, _) => return Ok(None),
+        (Some(location), Some(name), None) => (location, name),
+        _ => return Err(EmulatorError::InvalidSymbols("expected a bank:adder and a label on every line")),
+    };
+
+    let (bank, adder) = location
+        .split_once(':')
+        .ok_or(EmulatorError::InvalidSymbols("a symbol location is missing its bank"))?;
+
+    let bank = usize::from_str_radix(bank, 16).map_err(|_| EmulatorError::InvalidSymbols("bad bank number"))?;
+    let adder = u16::from_str_radix(adder, 16).map_err(|_| EmulatorError::InvalidSymbols("bad symbol address"))?;
+
+    Ok(Some(Symbol {
+        bank,
+        adder,
+        name: name.to_string(),
+    }))
+}
+
+impl SymbolTable {
+    pub fn parse(text: &str) -> Result<SymbolTable, EmulatorError> {
+        let mut symbols = Vec::new();
+        let mut in_labels = true;
+
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if trimmed.starts_with('[') {
+                in_labels = trimmed == "[labels]";
+                continue;
+            }
+
+            if in_labels {
+                symbols.extend(parse_line(line)?);
+            }
+        }
+
+        symbols.sort_by_key(|symbol| (symbol.bank, symbol.adder));
+
+        // the first label at an adder is the one shown, a later one with the same name is dropped
+        let mut by_name = HashMap::new();
+        for (index, symbol) in symbols.iter().enumerate() {
+            by_name.entry(symbol.name.clone()).or_insert(index);
+        }
+
+        Ok(SymbolTable { symbols, by_name })
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<&Symbol> {
+        self.by_name.get(name).map(|index| &self.symbols[*index])
+    }
+
+    // the closest label at or before adder in the bank
+    pub fn containing(&self, bank: usize, adder: u16) -> Option<&Symbol> {
+        let end = self.symbols.partition_point(|symbol| (symbol.bank, symbol.adder) <= (bank, adder));
+        let symbol = self.symbols[..end].last()?;
+
+        if symbol.bank != bank || symbol.adder < region_start(adder) {
+            return None;
+        }
+
+        Some(symbol)
+    }
+
+    // the function adder is in, local labels (Function.loop) count as part of their parent
+    pub fn function(&self, bank: usize, adder: u16) -> Option<&str> {
+        let name = &self.containing(bank, adder)?.name;
+
+        match name.find('.') {
+            Some(0) | None => Some(name),
+            Some(dot) => Some(&name[..dot]),
+        }
+    }
+
+    // the closest label at or before adder in the bank, as label or label+offset
+    pub fn symbolize(&self, bank: usize, adder: u16) -> Option<String> {
+        let symbol = self.containing(bank, adder)?;
+
+        match adder - symbol.adder {
+            0 => Some(symbol.name.clone()),
+            offset => Some(format!("{}+{:x}", symbol.name, offset)),
+        }
+    }
+}
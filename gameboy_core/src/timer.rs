@@ -1,11 +1,11 @@
-use crate::mmu::{ Mmu, MemRead, MemWrite, IoDevice};
-use crate::ic::Irq;
+use crate::mmu::{ MemRead, MemWrite, IoDevice};
+use crate::ic::Ic;
+use crate::state::{SaveState, StateReader, StateWriter};
 
 const CYCLES_PER_DIVIDER: u32 = 256;
 
 
 pub struct Timer {
-    irq: Irq,
     divider: u8,
     divider_clock: u32,
 
@@ -14,25 +14,31 @@ pub struct Timer {
     timer_modulo: u8,
     
     timer_enabled: bool,
-    timer_speed: u32,
+    timer_select: u8, // the speed bits of tac
 }
 
 
 impl Timer {
-    pub fn new(irq: Irq) -> Self {
+    pub fn new() -> Self {
         Self {
-            irq,
             divider: 0,
             divider_clock: 0,
             timer: 0,
             timer_clock: 0,
             timer_modulo: 0,
-            timer_speed: 0,
+            timer_select: 0,
             timer_enabled: false,
         }
     }
 
-    pub fn cycle(&mut self, clock: u32) {
+    // the classic boot rom hands over with the divider at 0xabcc, so div reads 0xab at 0x100. on the
+    // color game boy it depends on how long the logo animation ran and is left at 0
+    pub fn skip_boot_rom(&mut self) {
+        self.divider = 0xAB;
+        self.divider_clock = 0xCC;
+    }
+
+    pub fn cycle(&mut self, clock: u32, ic: &mut Ic) {
         self.divider_clock += clock;
 
         while self.divider_clock >= CYCLES_PER_DIVIDER {
@@ -46,30 +52,21 @@ impl Timer {
 
         self.timer_clock += clock;
 
-        while self.timer_clock >= self.timer_speed {
-            self.timer_clock -= self.timer_speed;
+        let timer_speed = u8_to_speed(self.timer_select);
+        while self.timer_clock >= timer_speed {
+            self.timer_clock -= timer_speed;
 
             let (time, of) = self.timer.overflowing_add(1);
             self.timer = time;
 
             if of {
                 self.timer = self.timer_modulo;
-                self.irq.timer(true);
+                ic.timer(true);
             }
         }
     }
 }
 
-fn speed_to_u8(speed: u32) -> u8 {
-    match speed {
-        1024 => 0x0,
-        16 => 0x1,
-        64 => 0x2,
-        256 => 0x3,
-        _ => unreachable!(),
-    }
-}
-
 fn u8_to_speed(speed_reg: u8) -> u32 {
     match speed_reg {
         0x0 => 1024,
@@ -81,17 +78,17 @@ fn u8_to_speed(speed_reg: u8) -> u32 {
 }
 
 impl IoDevice for Timer {
-    fn read_byte(&mut self, _mmu: &Mmu, adder: u16) -> MemRead { 
+    fn peek_byte(&self, adder: u16) -> MemRead { 
         match adder {
             0xFF04 => MemRead::Read(self.divider),
             0xFF05 => MemRead::Read(self.timer),
             0xFF06  => MemRead::Read(self.timer_modulo),
-            0xFF07 => MemRead::Read(speed_to_u8(self.timer_speed) | if self.timer_enabled { 0x4 } else { 0 }),
+            0xFF07 => MemRead::Read(self.timer_select | if self.timer_enabled { 0x4 } else { 0 }),
             _ => MemRead::Ignore,
         }
     }
 
-    fn write_byte(&mut self, _mmu: &Mmu, adder: u16, value: u8) -> MemWrite {
+    fn write_byte(&mut self, adder: u16, value: u8) -> MemWrite {
         match adder {
             0xFF04 => {
                 self.divider = 0;
@@ -107,11 +104,37 @@ impl IoDevice for Timer {
             },
             0xFF07 => {
                 self.timer_enabled = (value & 0x4) != 0;
-                self.timer_speed = u8_to_speed(value & 0x3);
+                self.timer_select = value & 0x3;
 
                 MemWrite::Write
             },
             _ => MemWrite::Ignore,
         }
     }
-}
\ No newline at end of file
+}
+
+impl SaveState for Timer {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.divider);
+        state.write_u32(self.divider_clock);
+        state.write_u8(self.timer);
+        state.write_u32(self.timer_clock);
+        state.write_u8(self.timer_modulo);
+        state.write_bool(self.timer_enabled);
+        state.write_u8(self.timer_select);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) {
+        self.divider = state.read_u8();
+        self.divider_clock = state.read_u32();
+        self.timer = state.read_u8();
+        self.timer_clock = state.read_u32();
+        self.timer_modulo = state.read_u8();
+        self.timer_enabled = state.read_bool();
+        self.timer_select = state.read_u8();
+        if self.timer_select > 0x3 {
+            state.reject("timer speed out of range");
+            self.timer_select = 0;
+        }
+    }
+}
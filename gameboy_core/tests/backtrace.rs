@@ -0,0 +1,91 @@
+// the call stack follows calls, rsts, interrupts and the returns from them
+
+mod common;
+
+use common::test_rom;
+use gameboy_core::emulator::CallFrame;
+use gameboy_core::symbols::SymbolTable;
+
+// main calls a function that goes through rst $38 into another one, that one waits for v-blank
+// and then everything returns back to main
+const PROGRAM: [u8; 5] = [
+    0xCD, 0x60, 0x01, // $150: call $160
+    0x18, 0xFE, // $153: jr $153
+];
+
+const SYMBOLS: &str = "\
+00:0038 Rst38
+00:0040 VBlank
+00:0150 Main
+00:0160 Outer
+00:0170 Inner
+";
+
+fn rom() -> Vec<u8> {
+    let mut rom = test_rom(&PROGRAM, 0x8000);
+
+    // rst $38: call $170, ret
+    rom[0x38..0x3C].copy_from_slice(&[0xCD, 0x70, 0x01, 0xC9]);
+    // v-blank: reti
+    rom[0x40] = 0xD9;
+    // $160: rst $38, ret
+    rom[0x160..0x162].copy_from_slice(&[0xFF, 0xC9]);
+    // $170: ld a, 1; ldh (ie), a; xor a; ldh (if), a; ei; halt; nop; ret
+    rom[0x170..0x17B].copy_from_slice(&[0x3E, 0x01, 0xE0, 0xFF, 0xAF, 0xE0, 0x0F, 0xFB, 0x76, 0x00, 0xC9]);
+
+    rom
+}
+
+fn call_sites(frames: &[CallFrame]) -> Vec<(u16, u16, bool)> {
+    frames.iter().map(|frame| (frame.call_site, frame.target, frame.interrupt)).collect()
+}
+
+#[test]
+fn calls_rsts_and_interrupts() {
+    let mut emulator = common::emulator(rom());
+    emulator.set_symbols(SymbolTable::parse(SYMBOLS).unwrap());
+
+    emulator.run_until(|emulator| emulator.registers().pc == 0x40);
+    assert_eq!(
+        call_sites(emulator.backtrace()),
+        vec![(0x150, 0x160, false), (0x160, 0x38, false), (0x38, 0x170, false), (0x179, 0x40, true)]
+    );
+    assert_eq!(
+        emulator.describe_backtrace(),
+        "interrupted at 00:0179 Inner+9\n\
+         called from 00:0038 Rst38\n\
+         called from 00:0160 Outer\n\
+         called from 00:0150 Main\n"
+    );
+
+    // every return takes its frame off again
+    emulator.run_until(|emulator| emulator.registers().pc == 0x17A);
+    assert_eq!(emulator.backtrace().len(), 3);
+    emulator.run_until(|emulator| emulator.registers().pc == 0x153);
+    assert!(emulator.backtrace().is_empty());
+    assert_eq!(emulator.describe_backtrace(), "");
+}
+
+#[test]
+fn endless_recursion_keeps_the_innermost_calls() {
+    // $150: ld sp, $dffe; $153: call $153. a thousand calls stay inside wram
+    let mut emulator = common::emulator(test_rom(&[0x31, 0xFE, 0xDF, 0xCD, 0x53, 0x01], 0x8000));
+    emulator.run_cycles(24 * 1000);
+
+    let frames = emulator.backtrace();
+    assert_eq!(frames.len(), 256);
+    assert!(frames.iter().all(|frame| frame.call_site == 0x153));
+    assert_eq!(frames[255].return_slot, emulator.registers().sp);
+}
+
+#[test]
+fn a_loaded_state_starts_without_calls() {
+    let mut emulator = common::emulator(rom());
+    let state = emulator.save_state();
+
+    emulator.run_until(|emulator| emulator.registers().pc == 0x40);
+    assert!(!emulator.backtrace().is_empty());
+
+    emulator.load_state(&state).unwrap();
+    assert!(emulator.backtrace().is_empty());
+}
@@ -0,0 +1,115 @@
+// the code/data log flags every rom byte with how the game read it
+
+mod common;
+
+use common::{test_rom, LOOP};
+use gameboy_core::cdl::{CodeDataLog, DATA, DMA, OPCODE, OPERAND};
+use gameboy_core::emulator::{Emulator, EmulatorConfig, EmulatorError};
+
+// ld a, ($0200); ld a, 2; ld ($2000), a; ld a, ($4010); ld a, 3; ldh ($46), a; jr -2
+// reads $200 as data, maps bank 2 to read $4010 from it and copies $300 - $39f to oam
+const PROGRAM: [u8; 17] = [
+    0xFA, 0x00, 0x02, 0x3E, 0x02, 0xEA, 0x00, 0x20, 0xFA, 0x10, 0x40, 0x3E, 0x03, 0xE0, 0x46, 0x18, 0xFE,
+];
+
+// a 64kb mbc1 rom
+fn rom() -> Vec<u8> {
+    let mut rom = test_rom(&PROGRAM, 0x10000);
+    rom[0x147] = 0x01;
+    rom[0x148] = 0x01;
+    rom
+}
+
+fn invalid(bytes: &[u8], rom_size: usize) -> &'static str {
+    match CodeDataLog::from_bytes(bytes, rom_size) {
+        Err(EmulatorError::InvalidCodeDataLog(reason)) => reason,
+        other => panic!("{:?} was taken", other),
+    }
+}
+
+// runs the program to its loop and lets the dma finish
+fn logged(emulator: &mut Emulator) -> Vec<u8> {
+    emulator.run_until(|emulator| emulator.registers().pc == 0x015F);
+    emulator.run_cycles(1000);
+    emulator.code_data_log().unwrap().flags().to_vec()
+}
+
+#[test]
+fn bytes_are_flagged_by_how_they_were_read() {
+    let mut emulator = common::emulator(rom());
+    emulator.start_code_data_log(None).unwrap();
+
+    // debugger reads don't count
+    emulator.peek(0x0250);
+    let flags = logged(&mut emulator);
+
+    // the entry point: nop; jp $0150
+    assert_eq!(flags[0x100..0x104], [OPCODE, OPCODE, OPERAND, OPERAND]);
+
+    assert_eq!(flags[0x150..0x153], [OPCODE, OPERAND, OPERAND]);
+    assert_eq!(flags[0x15F..0x161], [OPCODE, OPERAND]);
+    assert_eq!(flags[0x200], DATA);
+    assert_eq!(flags[0x250], 0);
+
+    // banked reads land at their offset in the rom file
+    assert_eq!(flags[0x8010], DATA);
+    assert_eq!(flags[0x4010], 0);
+
+    assert!(flags[0x300..0x3A0].iter().all(|flags| *flags == DMA));
+    assert_eq!(flags[0x3A0], 0);
+}
+
+#[test]
+fn coverage_counts_code_data_and_unused_bytes() {
+    let mut emulator = common::emulator(rom());
+    emulator.start_code_data_log(None).unwrap();
+    logged(&mut emulator);
+
+    // 4 bytes at the entry point and 17 of the program, 2 data reads and 160 dma bytes
+    let coverage = emulator.code_data_log().unwrap().coverage();
+    assert_eq!((coverage.code, coverage.data), (21, 162));
+    assert_eq!(coverage.unused, 0x10000 - 21 - 162);
+}
+
+#[test]
+fn an_earlier_log_is_added_to() {
+    let mut previous = vec![0; 0x10000];
+    previous[0x7000] = DATA;
+    previous[0x200] = OPCODE;
+
+    let mut emulator = common::emulator(rom());
+    emulator.start_code_data_log(Some(&previous)).unwrap();
+    let flags = logged(&mut emulator);
+
+    assert_eq!(flags[0x7000], DATA);
+    assert_eq!(flags[0x200], OPCODE | DATA);
+
+    let log = emulator.stop_code_data_log().unwrap();
+    assert_eq!(log.to_bytes(), flags);
+    assert!(emulator.code_data_log().is_none());
+}
+
+#[test]
+fn logs_that_do_not_fit_the_rom_are_refused() {
+    assert_eq!(invalid(&[0; 0x4000], 0x8000), "it doesn't have a byte for every rom byte");
+    assert_eq!(invalid(&[0x10; 0x8000], 0x8000), "unknown flags");
+
+    let mut emulator = common::emulator(test_rom(&LOOP, 0x8000));
+    assert!(emulator.start_code_data_log(Some(&[0; 0x10000])).is_err());
+    assert!(emulator.code_data_log().is_none());
+}
+
+#[test]
+fn the_boot_rom_is_not_logged() {
+    // ld a, 1; ldh ($50), a unmaps the boot rom, from $04 on the nops of the cartridge run up to $100
+    let mut boot_rom = vec![0; 0x100];
+    boot_rom[..4].copy_from_slice(&[0x3E, 0x01, 0xE0, 0x50]);
+
+    let config = EmulatorConfig { boot_rom: Some(boot_rom), ..common::config() };
+    let mut emulator = Emulator::new(rom(), Box::new(common::TestHardware), config).unwrap();
+    emulator.start_code_data_log(None).unwrap();
+    let flags = logged(&mut emulator);
+
+    assert_eq!(flags[..4], [0, 0, 0, 0]);
+    assert!(flags[0x04..0x101].iter().all(|flags| *flags == OPCODE));
+}
@@ -0,0 +1,86 @@
+// narrowing down where a game keeps a variable
+
+mod common;
+
+use common::{test_rom, LOOP};
+use gameboy_core::cheat_search::{CheatSearch, SearchFilter};
+use gameboy_core::emulator::Emulator;
+
+// ld hl, $c0a3; ld (hl), 3; dec (hl); jr -2, a game that starts with 3 lives and loses one
+const LOSE_A_LIFE: [u8; 8] = [0x21, 0xA3, 0xC0, 0x36, 0x03, 0x35, 0x18, 0xFE];
+
+fn addresses(search: &CheatSearch) -> Vec<u16> {
+    search.candidates().iter().map(|(addr, _)| *addr).collect()
+}
+
+fn emulator() -> Emulator {
+    common::emulator(test_rom(&LOOP, 0x8000))
+}
+
+#[test]
+fn a_new_search_takes_every_ram_address() {
+    let mut emulator = emulator();
+    emulator.poke(0xC0A3, 0x09);
+
+    let search = CheatSearch::new(&emulator);
+    let addresses = addresses(&search);
+
+    // cartridge ram, wram and hram without ie
+    assert_eq!(addresses.len(), 0x2000 + 0x2000 + 0x7F);
+    assert_eq!(addresses.first(), Some(&0xA000));
+    assert!(addresses.contains(&0xBFFF) && addresses.contains(&0xC000) && addresses.contains(&0xDFFF));
+    assert!(!addresses.contains(&0xE000) && !addresses.contains(&0xFF7F));
+    assert_eq!(addresses.last(), Some(&0xFFFE));
+    assert!(search.candidates().contains(&(0xC0A3, 0x09)));
+}
+
+#[test]
+fn a_value_keeps_the_addresses_holding_it() {
+    let mut emulator = emulator();
+    emulator.poke(0xC001, 0x03);
+    emulator.poke(0xFF90, 0x03);
+
+    let mut search = CheatSearch::new(&emulator);
+    search.filter(&emulator, SearchFilter::Value(3));
+
+    assert_eq!(search.candidates(), &[(0xC001, 3), (0xFF90, 3)]);
+}
+
+#[test]
+fn comparisons_are_against_the_last_snapshot() {
+    let mut emulator = emulator();
+    emulator.poke(0xC001, 0x05);
+    emulator.poke(0xC002, 0x05);
+    emulator.poke(0xC003, 0x05);
+
+    let mut search = CheatSearch::new(&emulator);
+    emulator.poke(0xC001, 0x06);
+    emulator.poke(0xC002, 0x04);
+    search.filter(&emulator, SearchFilter::Changed);
+    assert_eq!(search.candidates(), &[(0xC001, 6), (0xC002, 4)]);
+
+    // the snapshot moved on with the filter, so 6 is compared with 6 now
+    search.filter(&emulator, SearchFilter::Equal);
+    assert_eq!(addresses(&search), vec![0xC001, 0xC002]);
+
+    emulator.poke(0xC001, 0x07);
+    emulator.poke(0xC002, 0x07);
+    search.filter(&emulator, SearchFilter::Greater);
+    assert_eq!(search.candidates(), &[(0xC001, 7), (0xC002, 7)]);
+
+    emulator.poke(0xC002, 0x01);
+    search.filter(&emulator, SearchFilter::Less);
+    assert_eq!(search.candidates(), &[(0xC002, 1)]);
+}
+
+#[test]
+fn a_few_steps_find_a_counter_the_game_keeps() {
+    let mut emulator = common::emulator(test_rom(&LOSE_A_LIFE, 0x8000));
+    let mut search = CheatSearch::new(&emulator);
+
+    emulator.run_frame();
+    search.filter(&emulator, SearchFilter::Changed);
+    search.filter(&emulator, SearchFilter::Value(2));
+
+    assert_eq!(addresses(&search), vec![0xC0A3]);
+}
@@ -0,0 +1,147 @@
+// cheat codes decoded the way the game genie and gameshark do it
+
+mod common;
+
+use common::{test_rom, LOOP};
+use gameboy_core::cheats::{CheatCode, RomPatch};
+use gameboy_core::emulator::EmulatorError;
+
+fn game_genie(code: &str) -> RomPatch {
+    match CheatCode::parse(code) {
+        Ok(CheatCode::GameGenie(patch)) => patch,
+        other => panic!("{} isn't a game genie code: {:?}", code, other),
+    }
+}
+
+fn game_shark(code: &str) -> (u16, u8, Option<usize>) {
+    match CheatCode::parse(code) {
+        Ok(CheatCode::GameShark { adder, value, wram_bank }) => (adder, value, wram_bank),
+        other => panic!("{} isn't a gameshark code: {:?}", code, other),
+    }
+}
+
+fn invalid(code: &str) -> &'static str {
+    match CheatCode::parse(code) {
+        Err(EmulatorError::InvalidCheat(reason)) => reason,
+        other => panic!("{} was taken: {:?}", code, other),
+    }
+}
+
+// the code for a patch, the other way round from parse: AB the value, FCDE the address with F
+// xored by 0xf, GI the compare byte xored with 0xba and rotated left by 2, H anything
+fn encode(adder: u16, value: u8, compare: u8) -> String {
+    let compare = (compare ^ 0xBA).rotate_left(2);
+
+    format!(
+        "{:02X}{:X}-{:X}{:X}{:X}-{:X}5{:X}",
+        value,
+        (adder >> 8) & 0xF,
+        (adder >> 4) & 0xF,
+        adder & 0xF,
+        (adder >> 12) ^ 0xF,
+        compare >> 4,
+        compare & 0xF
+    )
+}
+
+#[test]
+fn game_genie_codes() {
+    assert_eq!(
+        game_genie("00A-17B-C49"),
+        RomPatch {
+            adder: 0x4A17,
+            value: 0x00,
+            compare: Some(0xC8),
+        }
+    );
+    assert_eq!(
+        game_genie("00A-17B"),
+        RomPatch {
+            adder: 0x4A17,
+            value: 0x00,
+            compare: None,
+        }
+    );
+
+    // dashes are optional and the digits can be lower case
+    assert_eq!(game_genie("00a17bc49"), game_genie("00A-17B-C49"));
+    assert_eq!(game_genie(" 3E0-6FB "), game_genie("3E06FB"));
+
+    // the address has to land in the rom
+    assert_eq!(invalid("00A-177"), "game genie codes can only patch the rom");
+    assert_eq!(invalid("00A-170-C49"), "game genie codes can only patch the rom");
+}
+
+#[test]
+fn game_genie_compare_round_trip() {
+    for compare in 0..=0xFF {
+        let code = encode(0x0150, 0x3C, compare);
+
+        assert_eq!(
+            game_genie(&code),
+            RomPatch {
+                adder: 0x0150,
+                value: 0x3C,
+                compare: Some(compare),
+            },
+            "{}",
+            code
+        );
+    }
+}
+
+#[test]
+fn game_shark_codes() {
+    // pokemon red and blue, walk through walls and the three bytes of 999999 money
+    assert_eq!(game_shark("010138CD"), (0xCD38, 0x01, None));
+    assert_eq!(game_shark("019947D3"), (0xD347, 0x99, None));
+    assert_eq!(game_shark("019948D3"), (0xD348, 0x99, None));
+    assert_eq!(game_shark("019949D3"), (0xD349, 0x99, None));
+
+    // the color game boy types pick a wram bank, bank 0 is read as bank 1
+    assert_eq!(game_shark("910530DA"), (0xDA30, 0x05, Some(1)));
+    assert_eq!(game_shark("830530DA"), (0xDA30, 0x05, Some(3)));
+    assert_eq!(game_shark("900530DA"), (0xDA30, 0x05, Some(1)));
+
+    assert_eq!(invalid("020138CD"), "unknown gameshark code type");
+
+    // rom (an mbc bank switch), vram, echo ram, io (a dma) and ie are refused
+    for code in ["01010020", "01000080", "010000E0", "01C046FF", "0101FFFF"] {
+        assert_eq!(invalid(code), "gameshark codes can only write cartridge ram, wram or hram");
+    }
+    assert_eq!(game_shark("010100A0"), (0xA000, 0x01, None));
+    assert_eq!(game_shark("0101FEFF"), (0xFFFE, 0x01, None));
+
+    // eight digits with dashes are neither kind
+    assert_eq!(invalid("0101-38CD"), "codes are 6 or 9 digit game genie codes or 8 digit gameshark codes");
+    assert_eq!(invalid("0G0138CD"), "codes can only hold hex digits and dashes");
+}
+
+#[test]
+fn codes_change_the_game() {
+    let mut rom = test_rom(&LOOP, 0x8000);
+    rom[0x4000] = 0x12;
+    let mut emulator = common::emulator(rom);
+
+    // a compare byte the rom doesn't hold leaves it alone
+    emulator.add_cheat(&encode(0x4000, 0x34, 0x56)).unwrap();
+    assert_eq!(emulator.peek(0x4000), 0x12);
+
+    emulator.add_cheat(&encode(0x4000, 0x34, 0x12)).unwrap();
+    assert_eq!(emulator.peek(0x4000), 0x34);
+
+    emulator.set_cheat_enabled(1, false);
+    assert_eq!(emulator.peek(0x4000), 0x12);
+
+    // gameshark codes are written every frame, whatever the game does to the byte in between
+    emulator.add_cheat("017700C0").unwrap();
+    emulator.run_frame();
+    assert_eq!(emulator.peek(0xC000), 0x77);
+    emulator.poke(0xC000, 0);
+    emulator.run_frame();
+    assert_eq!(emulator.peek(0xC000), 0x77);
+
+    emulator.add_cheat("014290FF").unwrap();
+    emulator.run_frame();
+    assert_eq!(emulator.peek(0xFF90), 0x42);
+}
@@ -0,0 +1,74 @@
+// the test rom and emulator every integration test starts from
+#![allow(dead_code)]
+
+use gameboy_core::emulator::{Emulator, EmulatorConfig, GameBoyMode};
+use gameboy_core::hardware::{Hardware, Key, PROCESSOR_CLOCK_SPEED};
+use std::time::Duration;
+
+const LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
+// a program that loops in place, for tests that only need the machine running
+pub const LOOP: [u8; 2] = [0x18, 0xFE];
+
+// a rom of the given size that jumps from the entry point to the program at $150
+pub fn test_rom(program: &[u8], size: usize) -> Vec<u8> {
+    let mut rom = vec![0; size];
+
+    // nop; jp $0150
+    rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+    rom[0x104..0x134].copy_from_slice(&LOGO);
+    rom[0x150..0x150 + program.len()].copy_from_slice(program);
+
+    rom
+}
+
+pub struct TestHardware;
+
+impl Hardware for TestHardware {
+    fn draw_line(&mut self, _line: usize, _buffer: &[u32]) {}
+
+    fn joypad_pressed(&mut self, _key: Key) -> bool {
+        false
+    }
+
+    fn clock(&mut self) -> Duration {
+        Duration::from_secs(0)
+    }
+
+    fn pcm_sample_rate(&mut self) -> u32 {
+        44100
+    }
+
+    fn next_pcm_amplitude(&mut self, _amplitude: f32) {}
+
+    fn run(&mut self) -> bool {
+        true
+    }
+}
+
+// the header checksum the boot rom checks, over 0x134 - 0x14c
+pub fn fix_checksum(rom: &mut [u8]) {
+    rom[0x14D] = rom[0x134..0x14D].iter().fold(0u8, |sum, byte| sum.wrapping_sub(*byte).wrapping_sub(1));
+}
+
+// a classic game boy without a boot rom, uncapped, without audio and lenient about the header
+pub fn config() -> EmulatorConfig {
+    EmulatorConfig {
+        game_boy_mode: GameBoyMode::Classic,
+        boot_rom: None,
+        allow_bad_checksum: true,
+        native_speed: true,
+        cpu_speed: PROCESSOR_CLOCK_SPEED,
+        rewind: None,
+        block_opposite_directions: false,
+        audio: false,
+    }
+}
+
+pub fn emulator(rom: Vec<u8>) -> Emulator {
+    Emulator::new(rom, Box::new(TestHardware), config()).unwrap()
+}
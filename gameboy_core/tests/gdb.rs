@@ -0,0 +1,207 @@
+// drives the gdb stub with a scripted rsp client over a local socket
+
+mod common;
+
+use common::test_rom;
+use gameboy_core::gdb;
+use gameboy_core::symbols::SymbolTable;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+// writes a counter to $c000 forever
+const PROGRAM: [u8; 8] = [
+    0x3E, 0x42, // $150: ld a, $42
+    0xEA, 0x00, 0xC0, // $152: ld ($c000), a
+    0x3C, // $155: inc a
+    0x18, 0xFA, // $156: jr $152
+];
+
+// calls the same address in rom bank 2 and then bank 1 of an mbc1 cartridge
+const BANKED_PROGRAM: [u8; 18] = [
+    0x3E, 0x02, // $150: ld a, 2
+    0xEA, 0x00, 0x20, // $152: ld ($2000), a
+    0xCD, 0x00, 0x40, // $155: call $4000
+    0x3E, 0x01, // $158: ld a, 1
+    0xEA, 0x00, 0x20, // $15a: ld ($2000), a
+    0xCD, 0x00, 0x40, // $15d: call $4000
+    0x18, 0xEE, // $160: jr $150
+];
+
+const SYMBOLS: &str = "\
+; rgblink symbol file
+00:0150 Start
+01:4000 BankOne
+02:4000 BankTwo
+";
+
+fn connect(rom: Vec<u8>, symbols: &str) -> (Client, std::thread::JoinHandle<std::io::Result<()>>) {
+    let mut emulator = common::emulator(rom);
+    emulator.set_symbols(SymbolTable::parse(symbols).unwrap());
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || gdb::serve(&mut emulator, &listener, &|| false));
+
+    let client = Client {
+        stream: TcpStream::connect(addr).unwrap(),
+    };
+
+    (client, server)
+}
+
+struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    fn read_byte(&mut self) -> u8 {
+        let mut byte = [0u8];
+        self.stream.read_exact(&mut byte).unwrap();
+        byte[0]
+    }
+
+    fn read_reply(&mut self) -> String {
+        while self.read_byte() != b'$' {}
+
+        let mut data = Vec::new();
+        loop {
+            match self.read_byte() {
+                b'#' => break,
+                byte => data.push(byte),
+            }
+        }
+
+        let sum = [self.read_byte(), self.read_byte()];
+        let expected = data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        assert_eq!(u8::from_str_radix(std::str::from_utf8(&sum).unwrap(), 16).unwrap(), expected);
+
+        self.stream.write_all(b"+").unwrap();
+        String::from_utf8(data).unwrap()
+    }
+
+    fn send(&mut self, data: &str) {
+        let sum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        write!(self.stream, "${}#{:02x}", data, sum).unwrap();
+        assert_eq!(self.read_byte(), b'+');
+    }
+
+    fn request(&mut self, data: &str) -> String {
+        self.send(data);
+        self.read_reply()
+    }
+
+    // the console output of a monitor command, it comes hex encoded in an O packet before the OK
+    fn monitor(&mut self, command: &str) -> String {
+        let command: String = command.bytes().map(|byte| format!("{:02x}", byte)).collect();
+        self.send(&format!("qRcmd,{}", command));
+
+        let output = match self.read_reply() {
+            reply if reply == "OK" => return String::new(),
+            reply => reply,
+        };
+        assert_eq!(self.read_reply(), "OK");
+
+        let output = output.strip_prefix('O').unwrap();
+        let bytes: Vec<u8> = (0..output.len())
+            .step_by(2)
+            .map(|index| u8::from_str_radix(&output[index..index + 2], 16).unwrap())
+            .collect();
+        String::from_utf8(bytes).unwrap()
+    }
+}
+
+#[test]
+fn scripted_session() {
+    let (mut client, server) = connect(test_rom(&PROGRAM, 0x8000), "");
+
+    assert!(client.request("qSupported:swbreak+").contains("qXfer:features:read+"));
+    let target = client.request("qXfer:features:read:target.xml:0,fff");
+    assert!(target.starts_with('l') && target.contains("sm83"));
+    // a length running past the end of the address space is cut at the end of the document
+    let tail = client.request("qXfer:features:read:target.xml:10,ffffffffffffffff");
+    assert_eq!(tail, format!("l{}", &target[0x11..]));
+    assert_eq!(client.request("?"), "S05");
+
+    // a f b c d e h l, then sp and pc little endian, as the boot rom leaves them
+    assert_eq!(client.request("g"), "01b0001300d8014dfeff0001");
+
+    assert_eq!(client.request("Z0,152,1"), "OK");
+    assert_eq!(client.request("c"), "T05swbreak:;");
+    assert_eq!(client.request("p9"), "5201");
+    assert_eq!(client.request("p0"), "42");
+    assert_eq!(client.request("z0,152,1"), "OK");
+
+    assert_eq!(client.request("Z2,c000,1"), "OK");
+    assert_eq!(client.request("c"), "T05watch:c000;");
+    assert_eq!(client.request("mc000,1"), "42");
+    assert_eq!(client.request("z2,c000,1"), "OK");
+
+    assert_eq!(client.request("s"), "S05");
+    assert_eq!(client.request("p9"), "5601");
+
+    assert_eq!(client.request("P0=99"), "OK");
+    assert_eq!(client.request("p0"), "99");
+    assert_eq!(client.request("Mc100,2:abcd"), "OK");
+    assert_eq!(client.request("mc100,2"), "abcd");
+
+    // runs until interrupted with ctrl-c
+    client.send("c");
+    std::thread::sleep(Duration::from_millis(50));
+    client.stream.write_all(&[0x03]).unwrap();
+    assert_eq!(client.read_reply(), "S02");
+
+    assert_eq!(client.request("D"), "OK");
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn banked_label_breakpoints() {
+    let mut rom = test_rom(&BANKED_PROGRAM, 0x10000);
+    rom[0x147] = 0x01; // mbc1
+    rom[0x148] = 0x01; // 4 banks
+
+    // inc c; ret in bank 1 and inc b; ret in bank 2
+    rom[0x4000..0x4002].copy_from_slice(&[0x0C, 0xC9]);
+    rom[0x8000..0x8002].copy_from_slice(&[0x04, 0xC9]);
+
+    let (mut client, server) = connect(rom, SYMBOLS);
+
+    assert_eq!(client.monitor("sym 158"), "00:0158 Start+8\n");
+    assert_eq!(client.monitor("sym BankTwo"), "02:4000 BankTwo\n");
+
+    // the call into bank 2 comes first and goes by
+    assert_eq!(client.monitor("break BankOne"), "breakpoint at 01:4000\n");
+    assert_eq!(client.request("c"), "T05swbreak:;");
+    assert_eq!(client.monitor("where"), "pc 01:4000 BankOne\ncalled from 00:015d Start+d\n");
+    assert_eq!(client.request("p9"), "0040");
+    assert_eq!(client.request("p2"), "01"); // b went up in bank 2
+
+    assert_eq!(client.monitor("delete BankOne").len(), 0);
+    assert_eq!(client.monitor("break BankTwo"), "breakpoint at 02:4000\n");
+    assert_eq!(client.request("c"), "T05swbreak:;");
+    // the call that went by in bank 1 returned, the loop came around to the call into bank 2
+    assert_eq!(client.monitor("where"), "pc 02:4000 BankTwo\ncalled from 00:0155 Start+5\n");
+
+    assert_eq!(client.monitor("break Nowhere"), "no symbol named Nowhere\n");
+
+    assert_eq!(client.request("D"), "OK");
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn illegal_opcode_locks_up() {
+    // ld a, 1; an illegal opcode; inc a
+    let (mut client, server) = connect(test_rom(&[0x3E, 0x01, 0xD3, 0x3C], 0x8000), "");
+
+    assert_eq!(client.request("c"), "S04");
+    assert_eq!(client.monitor("where"), "pc 00:0153\nlocked up on illegal opcode d3 at 00:0152\n");
+
+    // the cpu stays where it is, a step only lets the rest of the system run
+    assert_eq!(client.request("s"), "S05");
+    assert_eq!(client.request("p9"), "5301");
+    assert_eq!(client.request("p0"), "01");
+
+    assert_eq!(client.request("D"), "OK");
+    server.join().unwrap().unwrap();
+}
@@ -0,0 +1,105 @@
+// the cartridge header checks a rom has to pass to be loaded
+
+mod common;
+
+use common::{test_rom, TestHardware, LOOP};
+use gameboy_core::emulator::{Emulator, EmulatorConfig, EmulatorError};
+
+fn load(rom: Vec<u8>, strict: bool) -> Result<Emulator, EmulatorError> {
+    let config = EmulatorConfig {
+        allow_bad_checksum: !strict,
+        ..common::config()
+    };
+
+    Emulator::new(rom, Box::new(TestHardware), config)
+}
+
+#[test]
+fn rom_sizes() {
+    let mut rom = test_rom(&LOOP, 0x8000);
+    common::fix_checksum(&mut rom);
+    assert!(load(rom.clone(), true).is_ok());
+
+    // an overdump, the extra bytes aren't on the cartridge so it isn't taken even with a bad checksum allowed
+    let mut overdump = rom.clone();
+    overdump.resize(0xC000, 0xFF);
+    for strict in [true, false] {
+        match load(overdump.clone(), strict) {
+            Err(EmulatorError::SizeMismatch { size_code: 0, actual: 0xC000 }) => {}
+            other => panic!("an overdump was taken: {:?}", other.err()),
+        }
+    }
+
+    // the header says 64kb, the mbc would read past the end
+    let mut short = rom.clone();
+    short[0x147] = 0x01;
+    short[0x148] = 0x01;
+    common::fix_checksum(&mut short);
+    for strict in [true, false] {
+        match load(short.clone(), strict) {
+            Err(EmulatorError::SizeMismatch { size_code: 1, actual: 0x8000 }) => {}
+            other => panic!("a short rom was taken: {:?}", other.err()),
+        }
+    }
+
+    // a size code no cartridge uses
+    let mut unknown = rom;
+    unknown[0x148] = 0x10;
+    common::fix_checksum(&mut unknown);
+    match load(unknown, false) {
+        Err(EmulatorError::SizeMismatch { size_code: 0x10, actual: 0x8000 }) => {}
+        other => panic!("an unknown size code was taken: {:?}", other.err()),
+    }
+}
+
+#[test]
+fn too_small_for_a_header() {
+    let rom = test_rom(&LOOP, 0x8000);
+
+    // the header ends at 0x150
+    for len in [0, 0x100, 0x14F] {
+        for strict in [true, false] {
+            match load(rom[..len].to_vec(), strict) {
+                Err(EmulatorError::TruncatedRom(actual)) if actual == len => {}
+                other => panic!("a {} byte rom wasn't refused as truncated: {:?}", len, other.err()),
+            }
+        }
+    }
+
+    // a header with nothing behind it is still refused by its size
+    match load(rom[..0x150].to_vec(), false) {
+        Err(EmulatorError::SizeMismatch { size_code: 0, actual: 0x150 }) => {}
+        other => panic!("a bare header was taken: {:?}", other.err()),
+    }
+}
+
+#[test]
+fn checksum() {
+    let mut rom = test_rom(&LOOP, 0x8000);
+    common::fix_checksum(&mut rom);
+    let expected = rom[0x14D];
+    rom[0x14D] ^= 0xFF;
+
+    match load(rom.clone(), true) {
+        Err(EmulatorError::ChecksumMismatch { expected: header, actual }) => {
+            assert_eq!(header, expected ^ 0xFF);
+            assert_eq!(actual, expected);
+        }
+        other => panic!("strict loading took a bad checksum: {:?}", other.err()),
+    }
+
+    let mut emulator = load(rom, false).unwrap();
+    emulator.run_frame();
+}
+
+#[test]
+fn unsupported_mapper() {
+    let mut rom = test_rom(&LOOP, 0x8000);
+    rom[0x147] = 0xFC;
+    common::fix_checksum(&mut rom);
+
+    match load(rom, true) {
+        Err(EmulatorError::UnsupportedMapper(0xFC)) => {}
+        other => panic!("an unsupported mapper was taken: {:?}", other.err()),
+    }
+}
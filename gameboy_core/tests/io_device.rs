@@ -0,0 +1,48 @@
+// a device of our own mapped onto io registers through the emulator
+
+mod common;
+
+use common::test_rom;
+use gameboy_core::emulator::{IoDevice, MemRead, MemWrite};
+use std::sync::{Arc, Mutex};
+
+// ld a, $5a; ldh ($72), a; ldh a, ($73); ld ($c000), a; jr -2
+const PROGRAM: [u8; 11] = [0x3E, 0x5A, 0xE0, 0x72, 0xF0, 0x73, 0xEA, 0x00, 0xC0, 0x18, 0xFE];
+
+// answers 0x73 with 0xa5 and keeps what is written to 0x72
+struct Latch {
+    written: Arc<Mutex<Vec<u8>>>,
+}
+
+impl IoDevice for Latch {
+    fn peek_byte(&self, addr: u16) -> MemRead {
+        match addr {
+            0xFF73 => MemRead::Read(0xA5),
+            _ => MemRead::Ignore,
+        }
+    }
+
+    fn write_byte(&mut self, addr: u16, value: u8) -> MemWrite {
+        match addr {
+            0xFF72 => {
+                self.written.lock().unwrap().push(value);
+                MemWrite::Write
+            }
+            _ => MemWrite::Ignore,
+        }
+    }
+}
+
+#[test]
+fn the_game_talks_to_a_mapped_device() {
+    let written = Arc::new(Mutex::new(Vec::new()));
+    let mut emulator = common::emulator(test_rom(&PROGRAM, 0x8000));
+    emulator.map_io_device((0xFF72, 0xFF73), Box::new(Latch { written: written.clone() }));
+
+    emulator.run_frame();
+
+    assert_eq!(*written.lock().unwrap(), vec![0x5A]);
+    assert_eq!(emulator.peek(0xC000), 0xA5);
+    // a register the device ignores reads like an unmapped one
+    assert_eq!(emulator.peek(0xFF72), 0x00);
+}
@@ -0,0 +1,106 @@
+// p1 as the game reads it with the select lines it wrote and the keys held down
+
+mod common;
+
+use common::test_rom;
+use gameboy_core::emulator::{Emulator, EmulatorConfig};
+use gameboy_core::hardware::ButtonState;
+
+const P1: u16 = 0xFF00;
+const IF: u16 = 0xFF0F;
+
+// runs up to the loop of a program that writes p1 once: ld a, select; ldh ($00), a; jr -2
+fn emulator(select: u8, block_opposite_directions: bool) -> Emulator {
+    let program = [0x3E, select, 0xE0, 0x00, 0x18, 0xFE];
+    let config = EmulatorConfig { block_opposite_directions, ..common::config() };
+
+    let mut emulator = Emulator::new(test_rom(&program, 0x8000), Box::new(common::TestHardware), config).unwrap();
+    emulator.run_until(|emulator| emulator.registers().pc == 0x0154);
+    emulator
+}
+
+fn buttons(set: impl Fn(&mut ButtonState)) -> ButtonState {
+    let mut buttons = ButtonState::default();
+    set(&mut buttons);
+    buttons
+}
+
+#[test]
+fn only_the_select_bits_are_written() {
+    // the top two bits always read set, the low nibble are the key lines and can't be written
+    for (select, read) in [(0x00, 0xCF), (0x10, 0xDF), (0x20, 0xEF), (0x30, 0xFF), (0x05, 0xCF), (0xEA, 0xEF)] {
+        let emulator = emulator(select, false);
+        assert_eq!(emulator.peek(P1), read, "after writing {:02x}", select);
+    }
+}
+
+#[test]
+fn a_group_reads_only_while_selected() {
+    let right_and_b = buttons(|b| {
+        b.right = true;
+        b.b = true;
+    });
+
+    // directions on bit 0, buttons on bit 1
+    for (select, read) in [(0x20, 0xEE), (0x10, 0xDD), (0x30, 0xFF)] {
+        let mut emulator = emulator(select, false);
+        emulator.set_input(right_and_b);
+        assert_eq!(emulator.peek(P1), read, "with select {:02x}", select);
+    }
+}
+
+#[test]
+fn both_groups_selected_pull_the_lines_together() {
+    let mut emulator = emulator(0x00, false);
+
+    emulator.set_input(buttons(|b| b.right = true));
+    assert_eq!(emulator.peek(P1), 0xCE);
+
+    // right and a share bit 0, b is on bit 1
+    emulator.set_input(buttons(|b| {
+        b.right = true;
+        b.a = true;
+        b.b = true;
+    }));
+    assert_eq!(emulator.peek(P1), 0xCC);
+
+    emulator.set_input(buttons(|b| {
+        b.down = true;
+        b.start = true;
+    }));
+    assert_eq!(emulator.peek(P1), 0xC7);
+}
+
+#[test]
+fn opposite_directions_block_each_other() {
+    let left_right_up = buttons(|b| {
+        b.left = true;
+        b.right = true;
+        b.up = true;
+    });
+
+    let mut blocked = emulator(0x20, true);
+    blocked.set_input(left_right_up);
+    assert_eq!(blocked.peek(P1), 0xEB);
+
+    let mut unblocked = emulator(0x20, false);
+    unblocked.set_input(left_right_up);
+    assert_eq!(unblocked.peek(P1), 0xE8);
+
+    // up and down block each other as well
+    blocked.set_input(buttons(|b| {
+        b.up = true;
+        b.down = true;
+    }));
+    assert_eq!(blocked.peek(P1), 0xEF);
+}
+
+#[test]
+fn pressing_a_selected_key_requests_the_interrupt() {
+    let mut emulator = emulator(0x20, false);
+    emulator.set_input(buttons(|b| b.a = true));
+    assert_eq!(emulator.peek(IF) & 0x10, 0x00);
+
+    emulator.set_input(buttons(|b| b.left = true));
+    assert_eq!(emulator.peek(IF) & 0x10, 0x10);
+}
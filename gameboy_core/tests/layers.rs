@@ -0,0 +1,51 @@
+// the debug toggles only change the picture the ppu draws
+
+mod common;
+
+use common::test_rom;
+use gameboy_core::hardware::DISPLAY_WIDTH;
+
+// fills tile 0 with color 3, maps color 3 to white with bgp = 1b and loops with the background on
+const PROGRAM: [u8; 21] = [
+    0x21, 0x00, 0x80, // ld hl, $8000
+    0x3E, 0xFF, // ld a, $ff
+    0x06, 0x10, // ld b, 16
+    0x22, // ld (hl+), a
+    0x05, // dec b
+    0x20, 0xFC, // jr nz, -4
+    0x3E, 0x1B, // ld a, $1b
+    0xE0, 0x47, // ldh (bgp), a
+    0x3E, 0x91, // ld a, $91
+    0xE0, 0x40, // ldh (lcdc), a
+    0x18, 0xFE, // jr -2
+];
+
+const BLACK: u32 = 0xFF000000;
+
+#[test]
+fn palettes_and_scanline() {
+    let mut emulator = common::emulator(test_rom(&PROGRAM, 0x8000));
+    emulator.run_frame();
+    emulator.run_frame();
+
+    let pixel = |frame: &[u32], x: usize, y: usize| frame[y * DISPLAY_WIDTH + x];
+    let white = pixel(emulator.frame_buffer(), 0, 0);
+    assert_ne!(white, BLACK);
+
+    // past the palette color 3 is black again
+    let mut layers = emulator.debug_layers();
+    layers.palettes = false;
+    emulator.set_debug_layers(layers);
+    emulator.run_frame();
+    assert_eq!(pixel(emulator.frame_buffer(), 0, 0), BLACK);
+
+    layers.palettes = true;
+    layers.scanline = Some(0x40);
+    emulator.set_debug_layers(layers);
+    emulator.run_frame();
+    let frame = emulator.frame_buffer();
+    assert_eq!(pixel(frame, 0, 0x3F), white);
+    assert_ne!(pixel(frame, 0, 0x40), white);
+    assert_eq!(pixel(frame, 159, 0x40), pixel(frame, 0, 0x40));
+    assert_eq!(pixel(frame, 0, 0x41), white);
+}
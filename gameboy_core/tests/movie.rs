@@ -0,0 +1,124 @@
+// input movies replay the same frames they were recorded on
+
+mod common;
+
+use common::test_rom;
+use gameboy_core::emulator::{Emulator, EmulatorError};
+use gameboy_core::hardware::ButtonState;
+use gameboy_core::movie::{Movie, MovieStart};
+
+// selects the action buttons and copies them into bgp forever, so the color the blank
+// background is drawn in shows which of a and b are held
+const PROGRAM: [u8; 10] = [
+    0x3E, 0x10, // ld a, $10
+    0xE0, 0x00, // ldh (p1), a
+    0xF0, 0x00, // ldh a, (p1)
+    0xE0, 0x47, // ldh (bgp), a
+    0x18, 0xFA, // jr -6
+];
+
+// the keys of every frame, in ButtonState::to_bits order. nothing, a, a + b, then b
+const FRAMES: [u8; 12] = [0x00, 0x00, 0x00, 0x10, 0x10, 0x10, 0x30, 0x30, 0x30, 0x20, 0x20, 0x20];
+
+// the frame the movie above leaves on screen
+const FRAME_HASH: u64 = 0x8525A91CDD9B1D85;
+
+fn rom() -> Vec<u8> {
+    let mut rom = test_rom(&PROGRAM, 0x8000);
+    common::fix_checksum(&mut rom);
+    rom
+}
+
+// fnv-1a over the pixels
+fn frame_hash(emulator: &Emulator) -> u64 {
+    emulator
+        .frame_buffer()
+        .iter()
+        .flat_map(|pixel| pixel.to_le_bytes())
+        .fold(0xCBF29CE484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001B3))
+}
+
+fn play(emulator: &mut Emulator, movie: Movie) {
+    let frames = movie.frames.len();
+    emulator.play_movie(movie).unwrap();
+
+    for _ in 1..frames {
+        assert!(emulator.playing_movie());
+        emulator.run_frame();
+    }
+}
+
+#[test]
+fn recorded_movie_plays_back() {
+    let movie = Movie {
+        header_checksum: rom()[0x14D],
+        start: MovieStart::PowerOn,
+        frames: FRAMES.to_vec(),
+    };
+
+    let mut emulator = common::emulator(rom());
+    play(&mut emulator, movie);
+    assert_eq!(frame_hash(&emulator), FRAME_HASH);
+
+    // without the keys the screen ends up different
+    let mut idle = common::emulator(rom());
+    for _ in 1..FRAMES.len() {
+        idle.run_frame();
+    }
+    assert_ne!(frame_hash(&idle), FRAME_HASH);
+}
+
+#[test]
+fn recording_replays_the_same_frames() {
+    let mut recorder = common::emulator(rom());
+
+    // a recording started later starts from a save state
+    recorder.run_frame();
+    recorder.start_recording();
+    for bits in FRAMES.iter() {
+        recorder.set_input(ButtonState::from_bits(*bits));
+        recorder.run_frame();
+    }
+    let movie = recorder.stop_recording().unwrap();
+    assert!(matches!(movie.start, MovieStart::SaveState(_)));
+
+    let bytes = movie.to_bytes();
+    let read = Movie::from_bytes(&bytes).unwrap();
+    assert_eq!(read, movie);
+
+    let mut player = common::emulator(rom());
+    let frames = movie.frames.len();
+    player.play_movie(read).unwrap();
+    for _ in 1..frames {
+        player.run_frame();
+    }
+    assert_eq!(frame_hash(&player), frame_hash(&recorder));
+
+    // cut short or with something after it, the movie is refused
+    for damaged in [&bytes[..bytes.len() - 1], &[bytes.as_slice(), &[0]].concat()[..]] {
+        match Movie::from_bytes(damaged) {
+            Err(EmulatorError::InvalidMovie(_)) => {}
+            other => panic!("a damaged movie was read: {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn movie_for_another_rom_is_refused() {
+    let checksum = rom()[0x14D];
+    let movie = Movie {
+        header_checksum: checksum.wrapping_add(1),
+        start: MovieStart::PowerOn,
+        frames: FRAMES.to_vec(),
+    };
+
+    let mut emulator = common::emulator(rom());
+    assert_eq!(
+        emulator.play_movie(movie),
+        Err(EmulatorError::MovieMismatch {
+            expected: checksum.wrapping_add(1),
+            actual: checksum,
+        })
+    );
+    assert!(!emulator.playing_movie());
+}
@@ -0,0 +1,171 @@
+// ips, ups and bps patches built by hand, good ones and broken ones
+
+use gameboy_core::emulator::EmulatorError;
+use gameboy_core::patch::{self, crc32};
+
+fn rom() -> Vec<u8> {
+    (0..64).map(|index| index as u8).collect()
+}
+
+fn invalid(rom: &[u8], patch: &[u8]) -> &'static str {
+    match patch::apply(rom, patch) {
+        Err(EmulatorError::InvalidPatch(reason)) => reason,
+        other => panic!("the patch was taken: {:?}", other),
+    }
+}
+
+// the variable length numbers of ups and bps
+fn number(mut value: usize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+
+    loop {
+        let low = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(0x80 | low);
+            return bytes;
+        }
+        bytes.push(low);
+        value -= 1;
+    }
+}
+
+// the source, target and patch crcs
+fn with_footer(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8> {
+    patch.extend_from_slice(&crc32(source).to_le_bytes());
+    patch.extend_from_slice(&crc32(target).to_le_bytes());
+    let patch_crc = crc32(&patch);
+    patch.extend_from_slice(&patch_crc.to_le_bytes());
+    patch
+}
+
+#[test]
+fn crc32_check_values() {
+    assert_eq!(crc32(b""), 0);
+    assert_eq!(crc32(b"123456789"), 0xCBF43926);
+    assert_eq!(crc32(b"The quick brown fox jumps over the lazy dog"), 0x414FA339);
+}
+
+#[test]
+fn ips() {
+    let mut patch = b"PATCH".to_vec();
+    // three bytes at 0x10
+    patch.extend_from_slice(&[0x00, 0x00, 0x10, 0x00, 0x03, 0xAA, 0xBB, 0xCC]);
+    // a run of 4 0xee at 0x20
+    patch.extend_from_slice(&[0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x04, 0xEE]);
+    // two bytes past the end grow the rom
+    patch.extend_from_slice(&[0x00, 0x00, 0x42, 0x00, 0x02, 0x11, 0x22]);
+    patch.extend_from_slice(b"EOF");
+
+    let mut expected = rom();
+    expected[0x10..0x13].copy_from_slice(&[0xAA, 0xBB, 0xCC]);
+    expected[0x20..0x24].copy_from_slice(&[0xEE; 4]);
+    expected.extend_from_slice(&[0x00, 0x00, 0x11, 0x22]);
+    assert_eq!(patch::apply(&rom(), &patch), Ok(expected.clone()));
+
+    // the size after the end marker cuts the rom short
+    let mut truncating = patch.clone();
+    truncating.extend_from_slice(&[0x00, 0x00, 0x30]);
+    expected.truncate(0x30);
+    assert_eq!(patch::apply(&rom(), &truncating), Ok(expected));
+
+    // cut off in the middle of a record, and without the end marker
+    assert_eq!(invalid(&rom(), &patch[..patch.len() - 6]), "patch is truncated");
+    assert_eq!(invalid(&rom(), &patch[..patch.len() - 3]), "patch is truncated");
+    // a run record without its byte
+    assert_eq!(invalid(&rom(), &patch[..5 + 8 + 7]), "patch is truncated");
+}
+
+#[test]
+fn ups() {
+    let source = rom();
+    let mut target = source.clone();
+    target[4] ^= 0x0F;
+    target[5] ^= 0xF0;
+    target[40] ^= 0x01;
+    target.extend_from_slice(&[0x33, 0x44]);
+
+    let mut actions = b"UPS1".to_vec();
+    actions.extend(number(source.len()));
+    actions.extend(number(target.len()));
+    // skip 4 bytes, xor two and end the run
+    actions.extend(number(4));
+    actions.extend_from_slice(&[0x0F, 0xF0, 0x00]);
+    // the run ended one byte past the last xor, so 40 is 33 further on
+    actions.extend(number(33));
+    actions.extend_from_slice(&[0x01, 0x00]);
+    // the new bytes are xored with the zeros the rom grew by
+    actions.extend(number(22));
+    actions.extend_from_slice(&[0x33, 0x44, 0x00]);
+
+    let patch = with_footer(actions.clone(), &source, &target);
+    assert_eq!(patch::apply(&source, &patch), Ok(target.clone()));
+
+    let mut corrupted = patch.clone();
+    corrupted[10] ^= 0x01;
+    assert_eq!(invalid(&source, &corrupted), "patch crc doesn't match, the patch is corrupted");
+
+    let mut other_rom = source.clone();
+    other_rom[0] = 0xFF;
+    assert_eq!(invalid(&other_rom, &patch), "rom crc doesn't match the rom the patch was made for");
+
+    let wrong_target = with_footer(actions.clone(), &source, &source);
+    assert_eq!(invalid(&source, &wrong_target), "patched rom crc doesn't match");
+
+    assert_eq!(invalid(&source, &patch[..8]), "patch is truncated");
+    // the last run isn't ended, the footer is cut off
+    let unfinished = with_footer(actions[..actions.len() - 1].to_vec(), &source, &target);
+    assert_eq!(invalid(&source, &unfinished), "patch is truncated");
+}
+
+#[test]
+fn bps() {
+    let source = rom();
+    let mut target = Vec::new();
+    // source read: the first 8 bytes where they are
+    target.extend_from_slice(&source[..8]);
+    // target read: 2 new bytes
+    target.extend_from_slice(&[0xAB, 0xCD]);
+    // source copy: 4 bytes from 0x30
+    target.extend_from_slice(&source[0x30..0x34]);
+    // target copy: 6 bytes starting 2 back, overlapping what it writes
+    target.extend_from_slice(&[0x32, 0x33, 0x32, 0x33, 0x32, 0x33]);
+
+    let mut actions = b"BPS1".to_vec();
+    actions.extend(number(source.len()));
+    actions.extend(number(target.len()));
+    actions.extend(number(0));
+    actions.extend(number((8 - 1) << 2));
+    actions.extend(number((2 - 1) << 2 | 1));
+    actions.extend_from_slice(&[0xAB, 0xCD]);
+    actions.extend(number((4 - 1) << 2 | 2));
+    actions.extend(number(0x30 << 1));
+    actions.extend(number((6 - 1) << 2 | 3));
+    actions.extend(number(12 << 1));
+
+    let patch = with_footer(actions.clone(), &source, &target);
+    assert_eq!(patch::apply(&source, &patch), Ok(target.clone()));
+
+    let mut corrupted = patch.clone();
+    corrupted[12] ^= 0x01;
+    assert_eq!(invalid(&source, &corrupted), "patch crc doesn't match, the patch is corrupted");
+
+    assert_eq!(invalid(&source[..32], &patch), "rom crc doesn't match the rom the patch was made for");
+
+    let wrong_target = with_footer(actions.clone(), &source, &source);
+    assert_eq!(invalid(&source, &wrong_target), "patched rom crc doesn't match");
+
+    // a source copy going back past the start of the rom
+    let mut backwards = actions[..actions.len() - 4].to_vec();
+    backwards.extend(number((4 - 1) << 2 | 2));
+    backwards.extend(number(0x40 << 1 | 1));
+    let backwards = with_footer(backwards, &source, &target);
+    assert_eq!(invalid(&source, &backwards), "patch copies from outside the rom");
+
+    assert_eq!(invalid(&source, b"BPS1"), "patch is truncated");
+}
+
+#[test]
+fn unknown_format() {
+    assert_eq!(invalid(&rom(), b"NOT A PATCH"), "not an ips, ups or bps patch");
+}
@@ -0,0 +1,70 @@
+// debugger writes land in memory without anything the cpu's writes would set off
+
+mod common;
+
+use common::{test_rom, LOOP};
+
+// ld a, $0a; ld ($0000), a; jr -2, enables the mbc1 ram so it can be read back
+const ENABLE_RAM: [u8; 7] = [0x3E, 0x0A, 0xEA, 0x00, 0x00, 0x18, 0xFE];
+
+// a 64kb mbc1 rom whose bank 1 starts with 0x11 and bank 2 with 0x22
+fn mbc1_rom(program: &[u8]) -> Vec<u8> {
+    let mut rom = test_rom(program, 0x10000);
+    rom[0x147] = 0x03;
+    rom[0x148] = 0x01;
+    rom[0x4000] = 0x11;
+    rom[0x8000] = 0x22;
+    rom
+}
+
+#[test]
+fn ram_vram_oam_and_hram_are_written() {
+    let mut emulator = common::emulator(test_rom(&LOOP, 0x8000));
+
+    for addr in [0x8000, 0x9FFF, 0xC000, 0xDFFF, 0xFE00, 0xFE9F, 0xFF80, 0xFFFE] {
+        assert!(emulator.poke(addr, 0x5A), "{:04x} wasn't written", addr);
+        assert_eq!(emulator.peek(addr), 0x5A, "{:04x}", addr);
+    }
+
+    // echo ram is wram
+    assert!(emulator.poke(0xE123, 0xA5));
+    assert_eq!(emulator.peek(0xC123), 0xA5);
+}
+
+#[test]
+fn rom_and_io_are_left_alone() {
+    let mut emulator = common::emulator(mbc1_rom(&LOOP));
+    emulator.run_frame();
+
+    let div = emulator.peek(0xFF04);
+    let oam = emulator.peek(0xFE00);
+
+    // a bank switch, a dma from wram, a div reset, the unusable area and ie
+    for addr in [0x2000, 0xFF46, 0xFF04, 0xFEA0, 0xFFFF] {
+        assert!(!emulator.poke(addr, 0xC0), "{:04x} was written", addr);
+    }
+
+    assert_eq!(emulator.peek(0x4000), 0x11);
+    assert_eq!(emulator.peek(0xFF04), div);
+    assert_eq!(emulator.peek(0xFE00), oam);
+    assert_eq!(emulator.peek(0xFFFF), 0x00);
+}
+
+#[test]
+fn cartridge_ram_is_written_even_while_disabled() {
+    let mut emulator = common::emulator(mbc1_rom(&ENABLE_RAM));
+
+    assert!(emulator.poke(0xA010, 0x42));
+    emulator.run_frame();
+
+    assert_eq!(emulator.peek(0xA010), 0x42);
+}
+
+#[test]
+fn a_rom_only_cartridge_has_no_ram_to_poke() {
+    let mut emulator = common::emulator(test_rom(&LOOP, 0x8000));
+
+    assert!(!emulator.poke(0x0150, 0x00));
+    assert!(!emulator.poke(0xA000, 0x42));
+    assert_eq!(emulator.peek(0x0150), LOOP[0]);
+}
@@ -0,0 +1,124 @@
+// the state the boot rom leaves behind when it is skipped, from the pan docs power up sequence
+
+mod common;
+
+use common::{test_rom, LOOP};
+use gameboy_core::emulator::{Emulator, EmulatorConfig, GameBoyMode};
+
+// copies the background palettes to $c000 and the object palettes to $c040, a byte at a time through
+// bcps / bcpd and ocps / ocpd:
+// ld hl, $c000; ld c, $68; next: xor a; byte: ld (c), a; inc c; ld b, a; ld a, (c); ld (hl+), a; dec c;
+// ld a, b; inc a; cp $40; jr nz, byte; inc c; inc c; ld a, c; cp $6c; jr nz, next; jr -2
+const READ_PALETTES: [u8; 27] = [
+    0x21, 0x00, 0xC0, 0x0E, 0x68, 0xAF, 0xE2, 0x0C, 0x47, 0xF2, 0x22, 0x0D, 0x78, 0x3C, 0xFE, 0x40,
+    0x20, 0xF4, 0x0C, 0x0C, 0x79, 0xFE, 0x6C, 0x20, 0xEC, 0x18, 0xFE,
+];
+
+fn emulator(game_boy_mode: GameBoyMode) -> Emulator {
+    let config = EmulatorConfig { game_boy_mode, ..common::config() };
+    Emulator::new(test_rom(&LOOP, 0x8000), Box::new(common::TestHardware), config).unwrap()
+}
+
+// a f b c d e h l sp pc
+fn registers(emulator: &Emulator) -> (u8, u8, u8, u8, u8, u8, u8, u8, u16, u16) {
+    let r = emulator.registers();
+    (r.a, r.f, r.b, r.c, r.d, r.e, r.h, r.l, r.sp, r.pc)
+}
+
+#[test]
+fn classic_registers() {
+    let emulator = emulator(GameBoyMode::Classic);
+
+    assert_eq!(registers(&emulator), (0x01, 0xB0, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D, 0xFFFE, 0x0100));
+}
+
+#[test]
+fn color_registers() {
+    let emulator = emulator(GameBoyMode::Color);
+
+    assert_eq!(registers(&emulator), (0x11, 0x80, 0x00, 0x00, 0xFF, 0x56, 0x00, 0x0D, 0xFFFE, 0x0100));
+}
+
+#[test]
+fn classic_io_registers() {
+    let emulator = emulator(GameBoyMode::Classic);
+
+    // address, value and the bits of it the emulator keeps, unused bits don't read back as 1 yet
+    let registers = [
+        (0xFF00, 0xCF, 0xFF), // p1
+        (0xFF01, 0x00, 0xFF), // sb
+        (0xFF02, 0x7E, 0x81), // sc
+        (0xFF04, 0xAB, 0xFF), // div
+        (0xFF05, 0x00, 0xFF), // tima
+        (0xFF06, 0x00, 0xFF), // tma
+        (0xFF07, 0xF8, 0x07), // tac
+        (0xFF0F, 0xE1, 0x1F), // if
+        (0xFF40, 0x91, 0xFF), // lcdc
+        (0xFF42, 0x00, 0xFF), // scy
+        (0xFF43, 0x00, 0xFF), // scx
+        (0xFF44, 0x00, 0xFF), // ly
+        (0xFF45, 0x00, 0xFF), // lyc
+        (0xFF47, 0xFC, 0xFF), // bgp
+        (0xFF4A, 0x00, 0xFF), // wy
+        (0xFF4B, 0x00, 0xFF), // wx
+        (0xFFFF, 0x00, 0xFF), // ie
+    ];
+
+    for (addr, value, mask) in registers {
+        assert_eq!(emulator.peek(addr) & mask, value & mask, "{:04x} is {:02x}", addr, emulator.peek(addr));
+    }
+}
+
+#[test]
+fn div_counts_on_from_the_boot_rom() {
+    let mut emulator = emulator(GameBoyMode::Classic);
+    assert_eq!(emulator.peek(0xFF04), 0xAB);
+
+    // the boot rom left 0xcc cycles towards the next step, nop, jp and a jr add up to 0x20
+    emulator.run_cycles(0x20);
+    assert_eq!(emulator.peek(0xFF04), 0xAB);
+
+    // 0x18 more passes 0x100
+    emulator.run_cycles(0x18);
+    assert_eq!(emulator.peek(0xFF04), 0xAC);
+}
+
+#[test]
+fn color_game_boy_selects_the_first_banks() {
+    let emulator = emulator(GameBoyMode::Color);
+
+    assert_eq!(emulator.peek(0xFF4F) & 0x01, 0x00);
+    assert_eq!(emulator.peek(0xFF70) & 0x07, 0x01);
+}
+
+#[test]
+fn color_palettes_are_white() {
+    let config = EmulatorConfig { game_boy_mode: GameBoyMode::Color, ..common::config() };
+    let mut emulator = Emulator::new(test_rom(&READ_PALETTES, 0x8000), Box::new(common::TestHardware), config).unwrap();
+    emulator.run_frame();
+
+    // white is 0x7fff, low byte first
+    let palettes: Vec<u8> = (0xC000..0xC080).map(|addr| emulator.peek(addr)).collect();
+    assert_eq!(palettes, [0xFF, 0x7F].repeat(0x40));
+}
+
+#[test]
+fn logo_is_left_in_vram() {
+    let emulator = emulator(GameBoyMode::Classic);
+
+    // the first logo byte 0xce is two rows of 0xf0 and two of 0xfc in the low bit plane of tile 1
+    let tile: Vec<u8> = (0x8010..0x8018).map(|addr| emulator.peek(addr)).collect();
+    assert_eq!(tile, vec![0xF0, 0x00, 0xF0, 0x00, 0xFC, 0x00, 0xFC, 0x00]);
+
+    // the registered mark is tile 0x19
+    let registered: Vec<u8> = (0..8).map(|row| emulator.peek(0x8190 + row * 2)).collect();
+    assert_eq!(registered, vec![0x3C, 0x42, 0xB9, 0xA5, 0xB9, 0xA5, 0x42, 0x3C]);
+
+    // two rows of 12 tiles in the middle of the map with the mark to the right of the top row
+    let top: Vec<u8> = (0x9904..0x9910).map(|addr| emulator.peek(addr)).collect();
+    let bottom: Vec<u8> = (0x9924..0x9930).map(|addr| emulator.peek(addr)).collect();
+    assert_eq!(top, (0x01..=0x0C).collect::<Vec<u8>>());
+    assert_eq!(bottom, (0x0D..=0x18).collect::<Vec<u8>>());
+    assert_eq!(emulator.peek(0x9910), 0x19);
+    assert_eq!(emulator.peek(0x9903), 0x00);
+}
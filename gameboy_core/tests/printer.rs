@@ -0,0 +1,121 @@
+// talks to the printer a byte at a time the way a game does over the link cable
+
+use gameboy_core::printer::{Printer, Printout};
+use gameboy_core::serial::SerialTransport;
+use std::sync::mpsc::{self, Receiver};
+
+const INIT: u8 = 0x01;
+const PRINT: u8 = 0x02;
+const DATA: u8 = 0x04;
+const STATUS: u8 = 0x0F;
+
+const CHECKSUM_ERROR: u8 = 0x01;
+const PRINTING: u8 = 0x02;
+const IMAGE_FULL: u8 = 0x04;
+const UNPROCESSED: u8 = 0x08;
+
+const BLACK: u32 = 0xFF000000;
+const LIGHT_GRAY: u32 = 0xFFAAAAAA;
+const WHITE: u32 = 0xFFFFFFFF;
+
+fn packet(command: u8, compressed: bool, data: &[u8]) -> Vec<u8> {
+    let mut body = vec![command, compressed as u8];
+    body.extend_from_slice(&(data.len() as u16).to_le_bytes());
+    body.extend_from_slice(data);
+    let checksum = body.iter().fold(0u16, |sum, byte| sum.wrapping_add(*byte as u16));
+
+    let mut packet = vec![0x88, 0x33];
+    packet.extend(body);
+    packet.extend_from_slice(&checksum.to_le_bytes());
+    packet.extend_from_slice(&[0x00, 0x00]);
+    packet
+}
+
+// sends a packet and returns the status the printer ends it with
+fn send(printer: &mut Printer, packet: &[u8]) -> u8 {
+    let replies: Vec<u8> = packet.iter().map(|byte| printer.exchange(*byte)).collect();
+    let (body, ending) = replies.split_at(replies.len() - 2);
+
+    assert!(body.iter().all(|reply| *reply == 0x00), "{:02x?}", replies);
+    assert_eq!(ending[0], 0x81);
+
+    ending[1]
+}
+
+fn printer() -> (Printer, Receiver<Printout>) {
+    let (sender, printouts) = mpsc::channel();
+    (Printer::new(sender), printouts)
+}
+
+// one row of 20 tiles: ten black ones, one in the lightest gray and nine white ones
+fn compressed_row() -> Vec<u8> {
+    let mut data = vec![0x80 | (128 - 2), 0xFF, 0x80 | (32 - 2), 0xFF];
+
+    data.push(16 - 1);
+    for _ in 0..8 {
+        data.extend_from_slice(&[0xFF, 0x00]);
+    }
+
+    data.extend_from_slice(&[0x80 | (129 - 2), 0x00, 0x80 | (15 - 2), 0x00]);
+    data
+}
+
+#[test]
+fn prints_a_compressed_row() {
+    let (mut printer, printouts) = printer();
+
+    assert_eq!(send(&mut printer, &packet(INIT, false, &[])), 0x00);
+    assert_eq!(send(&mut printer, &packet(DATA, true, &compressed_row())), UNPROCESSED);
+    // the empty data packet that ends the image
+    assert_eq!(send(&mut printer, &packet(DATA, false, &[])), UNPROCESSED);
+    assert!(printouts.try_recv().is_err());
+
+    // one copy, a margin of 1 before and 3 after, the default palette and exposure
+    assert_eq!(send(&mut printer, &packet(PRINT, false, &[0x01, 0x13, 0xE4, 0x40])), PRINTING | IMAGE_FULL);
+
+    let printout = printouts.try_recv().unwrap();
+    assert_eq!((printout.margin_before, printout.margin_after), (1, 3));
+    assert_eq!((printout.image.width, printout.image.height), (160, 8));
+
+    let pixel = |x: usize, y: usize| printout.image.pixels[y * 160 + x];
+    assert_eq!(pixel(0, 0), BLACK);
+    assert_eq!(pixel(79, 7), BLACK);
+    assert_eq!(pixel(80, 0), LIGHT_GRAY);
+    assert_eq!(pixel(87, 7), LIGHT_GRAY);
+    assert_eq!(pixel(88, 0), WHITE);
+    assert_eq!(pixel(159, 7), WHITE);
+
+    // the game polls the status until the printer is done
+    let busy = (0..16)
+        .take_while(|_| send(&mut printer, &packet(STATUS, false, &[])) != 0x00)
+        .count();
+    assert!(busy > 0 && busy < 16, "busy for {} packets", busy);
+}
+
+#[test]
+fn reports_a_bad_checksum() {
+    let (mut printer, printouts) = printer();
+
+    let mut damaged = packet(DATA, true, &compressed_row());
+    let checksum_low = damaged.len() - 4;
+    damaged[checksum_low] ^= 0x01;
+
+    assert_eq!(send(&mut printer, &damaged), CHECKSUM_ERROR);
+
+    // the next good packet clears the error, the damaged data wasn't taken
+    assert_eq!(send(&mut printer, &packet(STATUS, false, &[])), 0x00);
+    send(&mut printer, &packet(PRINT, false, &[0x01, 0x00, 0xE4, 0x40]));
+    assert_eq!(printouts.try_recv().unwrap().image.height, 0);
+}
+
+#[test]
+fn copies_are_stacked() {
+    let (mut printer, printouts) = printer();
+
+    send(&mut printer, &packet(DATA, true, &compressed_row()));
+    send(&mut printer, &packet(PRINT, false, &[0x02, 0x00, 0x00, 0x40]));
+
+    let image = printouts.try_recv().unwrap().image;
+    assert_eq!(image.height, 16);
+    assert_eq!(image.pixels[..160 * 8], image.pixels[160 * 8..]);
+}
@@ -0,0 +1,158 @@
+// cycles attributed to instructions, functions, halts and v-blank waits
+
+mod common;
+
+use common::{test_rom, LOOP};
+use gameboy_core::profiler::{Profile, Profiler};
+use gameboy_core::symbols::SymbolTable;
+
+const SYMBOLS: &str = "\
+00:0150 Main
+00:0160 Main.wait
+00:0200 Other
+01:4000 Banked
+";
+
+fn hits(profile: &Profile, bank: usize, adder: u16) -> (u64, u64) {
+    let hits = profile.hits[&(bank, adder)];
+    (hits.count, hits.cycles)
+}
+
+// ldh a, ($44); cp $90; jr nz, -6 as the cpu runs it at $160 for a few rounds
+fn wait_loop(profiler: &mut Profiler, polled: bool, rounds: usize) {
+    for _ in 0..rounds {
+        profiler.executed(0, 0x160, 0x162, 12, polled);
+        profiler.executed(0, 0x162, 0x164, 8, false);
+        profiler.executed(0, 0x164, 0x160, 12, false);
+    }
+}
+
+#[test]
+fn instructions_count_their_runs_and_cycles() {
+    let mut profiler = Profiler::default();
+    profiler.executed(0, 0x150, 0x152, 8, false);
+    profiler.executed(0, 0x152, 0x150, 12, false);
+    profiler.executed(0, 0x150, 0x152, 8, false);
+    profiler.executed(1, 0x4000, 0x4001, 4, false);
+
+    let profile = profiler.finish();
+    assert_eq!(hits(&profile, 0, 0x150), (2, 16));
+    assert_eq!(hits(&profile, 0, 0x152), (1, 12));
+
+    // the same address in another bank is another instruction
+    assert_eq!(hits(&profile, 1, 0x4000), (1, 4));
+    assert_eq!(profile.cycles, 32);
+}
+
+#[test]
+fn halted_cycles_go_to_the_halt() {
+    let mut profiler = Profiler::default();
+    profiler.executed(0, 0x150, 0x151, 4, false);
+    profiler.halted(100);
+    profiler.halted(20);
+
+    let profile = profiler.finish();
+    assert_eq!(hits(&profile, 0, 0x150), (1, 124));
+    assert_eq!(profile.halt_cycles, 120);
+    assert_eq!(profile.cycles, 124);
+}
+
+#[test]
+fn short_loops_polling_ly_wait_for_vblank() {
+    let mut profiler = Profiler::default();
+    wait_loop(&mut profiler, true, 3);
+    assert_eq!(profiler.profile().vblank_wait_cycles, 3 * 32);
+
+    // the same loop without reading ly is busy
+    let mut profiler = Profiler::default();
+    wait_loop(&mut profiler, false, 3);
+    assert_eq!(profiler.profile().vblank_wait_cycles, 0);
+
+    // a poll followed by a jump forward or far back isn't a wait loop
+    let mut profiler = Profiler::default();
+    profiler.executed(0, 0x160, 0x162, 12, true);
+    profiler.executed(0, 0x162, 0x200, 16, false);
+    profiler.executed(0, 0x200, 0x202, 12, true);
+    profiler.executed(0, 0x202, 0x160, 16, false);
+    assert_eq!(profiler.profile().vblank_wait_cycles, 0);
+}
+
+#[test]
+fn functions_take_their_local_labels() {
+    let symbols = SymbolTable::parse(SYMBOLS).unwrap();
+
+    let mut profiler = Profiler::default();
+    profiler.executed(0, 0x150, 0x153, 24, false);
+    wait_loop(&mut profiler, true, 2);
+    profiler.executed(0, 0x200, 0x201, 4, false);
+    profiler.executed(1, 0x4000, 0x4001, 4, false);
+    profiler.executed(2, 0x4000, 0x4001, 4, false);
+
+    let functions: Vec<(String, u64, u64)> = profiler
+        .finish()
+        .functions(&symbols)
+        .into_iter()
+        .map(|(name, hits)| (name, hits.count, hits.cycles))
+        .collect();
+
+    // hottest first, ties by name, a bank without labels is one function
+    assert_eq!(
+        functions,
+        vec![
+            ("Main".to_string(), 7, 24 + 2 * 32),
+            ("Banked".to_string(), 1, 4),
+            ("Other".to_string(), 1, 4),
+            ("bank 02".to_string(), 1, 4),
+        ]
+    );
+}
+
+#[test]
+fn report_and_callgrind() {
+    let symbols = SymbolTable::parse(SYMBOLS).unwrap();
+
+    let mut profiler = Profiler::default();
+    profiler.executed(0, 0x150, 0x151, 4, false);
+    profiler.halted(12);
+    profiler.executed(0, 0x161, 0x162, 4, false);
+    let profile = profiler.finish();
+
+    let report = profile.report(&symbols);
+    assert!(report.starts_with("20 cpu cycles, 12 halted (60.0%), 0 waiting for v-blank (0.0%)\n"), "{}", report);
+    assert!(report.contains("          20 100.00            2  Main\n"), "{}", report);
+    assert!(report.contains("          16  80.00            1  00:0150 Main\n"), "{}", report);
+    assert!(report.contains("           4  20.00            1  00:0161 Main.wait+1\n"), "{}", report);
+
+    let mut callgrind = Vec::new();
+    profile.write_callgrind(&symbols, &mut callgrind).unwrap();
+    assert_eq!(
+        String::from_utf8(callgrind).unwrap(),
+        "# callgrind format\n\
+         version: 1\n\
+         creator: gameboy_core\n\
+         positions: instr\n\
+         events: Instructions Cycles\n\
+         summary: 2 20\n\
+         \n\
+         fl=bank 00\n\
+         fn=Main\n\
+         0x0150 1 16\n\
+         0x0161 1 4\n"
+    );
+}
+
+#[test]
+fn the_emulator_profiles_what_the_cpu_runs() {
+    let mut emulator = common::emulator(test_rom(&LOOP, 0x8000));
+    emulator.run_until(|emulator| emulator.registers().pc == 0x0150);
+    assert!(emulator.profile().is_none());
+
+    emulator.start_profiling();
+    let ran = emulator.run_cycles(1200);
+    let profile = emulator.stop_profiling().unwrap();
+
+    // jr -2 takes 12 cycles a round
+    assert_eq!(hits(&profile, 0, 0x150), (100, 1200));
+    assert_eq!(profile.cycles, ran as u64);
+    assert!(emulator.profile().is_none());
+}
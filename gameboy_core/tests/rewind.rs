@@ -0,0 +1,82 @@
+// the rewind buffer keeps snapshots as run length encoded xor deltas, rewinding has to undo them exactly
+
+mod common;
+
+use common::{test_rom, TestHardware};
+use gameboy_core::emulator::{Emulator, EmulatorConfig, RewindConfig};
+
+// fills $c000-$c0ff with a value that goes up every pass, so every frame changes a run of 256 bytes,
+// longer than a single length byte of the delta can hold
+const PROGRAM: [u8; 13] = [
+    0x21, 0x00, 0xC0, // ld hl, $c000
+    0x06, 0x00, // ld b, 0
+    0x1C, // inc e
+    0x7B, // ld a, e
+    0x22, // ld (hl+), a
+    0x05, // dec b
+    0x20, 0xFB, // jr nz, -5
+    0x18, 0xF3, // jr -13
+];
+
+fn emulator(budget: usize) -> Emulator {
+    let config = EmulatorConfig {
+        rewind: Some(RewindConfig { interval: 1, budget }),
+        ..common::config()
+    };
+
+    Emulator::new(test_rom(&PROGRAM, 0x8000), Box::new(TestHardware), config).unwrap()
+}
+
+// runs the frames and returns the snapshot taken after each of them
+fn run(emulator: &mut Emulator, frames: usize) -> Vec<Vec<u8>> {
+    (0..frames)
+        .map(|_| {
+            emulator.run_frame();
+            emulator.save_state()
+        })
+        .collect()
+}
+
+#[test]
+fn rewinding_restores_every_snapshot() {
+    let mut emulator = emulator(usize::MAX);
+    let snapshots = run(&mut emulator, 20);
+
+    // the long literal runs are there
+    let (last, before) = (&snapshots[19], &snapshots[18]);
+    let changed = (0..last.len()).filter(|index| last[*index] != before[*index]).count();
+    assert!(changed >= 256, "only {} bytes changed", changed);
+
+    for expected in snapshots[..19].iter().rev() {
+        assert!(emulator.rewind());
+        assert_eq!(emulator.save_state(), *expected);
+    }
+    assert!(!emulator.rewind());
+
+    // the machine runs on from the oldest snapshot the way it did the first time
+    assert_eq!(run(&mut emulator, 5), snapshots[1..6].to_vec());
+}
+
+#[test]
+fn the_budget_drops_the_oldest_snapshots() {
+    let state_len = emulator(usize::MAX).save_state().len();
+    let mut emulator = emulator(state_len + 2000);
+    let snapshots = run(&mut emulator, 30);
+
+    let mut rewinds = 0;
+    while emulator.rewind() {
+        rewinds += 1;
+        assert_eq!(emulator.save_state(), snapshots[29 - rewinds]);
+    }
+
+    assert!(rewinds > 0 && rewinds < 29, "{} rewinds", rewinds);
+}
+
+#[test]
+fn a_budget_too_small_for_a_delta_keeps_only_the_newest() {
+    let state_len = emulator(usize::MAX).save_state().len();
+    let mut emulator = emulator(state_len);
+    run(&mut emulator, 5);
+
+    assert!(!emulator.rewind());
+}
@@ -0,0 +1,72 @@
+// running the machine by frames, by cycles and until a condition holds
+
+mod common;
+
+use common::{test_rom, LOOP};
+
+// the longest instruction, a taken call, takes 24 t cycles
+const LONGEST_INSTRUCTION: u32 = 24;
+
+#[test]
+fn run_cycles_runs_at_least_as_many_cycles_as_asked() {
+    let mut emulator = common::emulator(test_rom(&LOOP, 0x8000));
+
+    for cycles in [1, 5, 100, 70224] {
+        let start = emulator.cycles();
+        let ran = emulator.run_cycles(cycles);
+
+        assert!(ran >= cycles && ran < cycles + LONGEST_INSTRUCTION, "asked for {} and ran {}", cycles, ran);
+        assert_eq!(emulator.cycles() - start, ran as u64);
+    }
+}
+
+#[test]
+fn run_cycles_never_splits_an_instruction() {
+    let mut emulator = common::emulator(test_rom(&LOOP, 0x8000));
+
+    // nop and jp $0150 take 4 and 16 cycles
+    assert_eq!(emulator.run_cycles(1), 4);
+    assert_eq!(emulator.run_cycles(5), 16);
+    assert_eq!(emulator.registers().pc, 0x0150);
+
+    // jr -2 takes 12
+    assert_eq!(emulator.run_cycles(1), 12);
+    assert_eq!(emulator.run_cycles(13), 24);
+}
+
+#[test]
+fn run_until_stops_once_the_predicate_holds() {
+    let mut emulator = common::emulator(test_rom(&LOOP, 0x8000));
+
+    // it is asked before the first instruction
+    assert_eq!(emulator.run_until(|_| true), 0);
+
+    assert_eq!(emulator.run_until(|emulator| emulator.registers().pc == 0x0150), 20);
+    assert_eq!(emulator.registers().pc, 0x0150);
+
+    let mut asked = 0;
+    let ran = emulator.run_until(|_| {
+        asked += 1;
+        asked == 4
+    });
+    assert_eq!(ran, 3 * 12);
+}
+
+#[test]
+fn run_frame_ends_as_vblank_starts() {
+    let mut by_frame = common::emulator(test_rom(&LOOP, 0x8000));
+    let mut by_instruction = common::emulator(test_rom(&LOOP, 0x8000));
+
+    for frame in 1..=3 {
+        by_frame.run_frame();
+        // an instruction at a time until ly turns from 143 to 144
+        by_instruction.run_until(|emulator| emulator.peek(0xFF44) == 143);
+        by_instruction.run_until(|emulator| emulator.peek(0xFF44) == 144);
+
+        // ly is 144 and stat is in mode 1 right after the instruction that entered vblank
+        assert_eq!(by_frame.frame_count(), frame);
+        assert_eq!(by_frame.peek(0xFF44), 144);
+        assert_eq!(by_frame.peek(0xFF41) & 0x3, 1);
+        assert_eq!(by_frame.cycles(), by_instruction.cycles());
+    }
+}
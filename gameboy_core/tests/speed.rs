@@ -0,0 +1,135 @@
+// speed multipliers, turbo and the sleeping that keeps a paced host at the set speed
+
+mod common;
+
+use common::{test_rom, LOOP};
+use gameboy_core::emulator::{Emulator, EmulatorConfig, EmulatorError, Speed, MAX_SPEED, MIN_SPEED};
+use gameboy_core::hardware::{Hardware, Key};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// a frame is 70224 t cycles of 238ns
+const FRAME: Duration = Duration::from_nanos(70224 * 238);
+
+// counts how often the emulator looks at the clock, which it only does to pace a frame
+struct ClockHardware {
+    paced: bool,
+    started: Instant,
+    clock_reads: Arc<AtomicUsize>,
+}
+
+impl Hardware for ClockHardware {
+    fn draw_line(&mut self, _line: usize, _buffer: &[u32]) {}
+
+    fn joypad_pressed(&mut self, _key: Key) -> bool {
+        false
+    }
+
+    fn clock(&mut self) -> Duration {
+        self.clock_reads.fetch_add(1, Ordering::Relaxed);
+        self.started.elapsed()
+    }
+
+    fn paced(&mut self) -> bool {
+        self.paced
+    }
+
+    fn pcm_sample_rate(&mut self) -> u32 {
+        44100
+    }
+
+    fn next_pcm_amplitude(&mut self, _amplitude: f32) {}
+
+    fn run(&mut self) -> bool {
+        true
+    }
+}
+
+// starts at the real speed
+fn start(paced: bool) -> (Emulator, Arc<AtomicUsize>) {
+    let clock_reads = Arc::new(AtomicUsize::new(0));
+    let hardware = ClockHardware {
+        paced,
+        started: Instant::now(),
+        clock_reads: clock_reads.clone(),
+    };
+    let config = EmulatorConfig { native_speed: false, ..common::config() };
+
+    let emulator = Emulator::new(test_rom(&LOOP, 0x8000), Box::new(hardware), config).unwrap();
+    (emulator, clock_reads)
+}
+
+#[test]
+fn multipliers_are_kept_in_range() {
+    let (mut emulator, _) = start(false);
+    assert_eq!(emulator.speed(), Speed::Multiplier(1.0));
+
+    emulator.set_speed(Speed::Multiplier(100.0)).unwrap();
+    assert_eq!(emulator.speed(), Speed::Multiplier(MAX_SPEED));
+
+    emulator.set_speed(Speed::Multiplier(0.0)).unwrap();
+    assert_eq!(emulator.speed(), Speed::Multiplier(MIN_SPEED));
+
+    emulator.set_speed(Speed::Multiplier(2.0)).unwrap();
+    assert_eq!(emulator.speed(), Speed::Multiplier(2.0));
+}
+
+#[test]
+fn multipliers_that_are_not_numbers_are_refused() {
+    let (mut emulator, _) = start(false);
+    emulator.set_speed(Speed::Multiplier(2.0)).unwrap();
+
+    for multiplier in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+        assert!(matches!(emulator.set_speed(Speed::Multiplier(multiplier)), Err(EmulatorError::InvalidSpeed(_))));
+        assert_eq!(emulator.speed(), Speed::Multiplier(2.0));
+    }
+}
+
+#[test]
+fn only_a_paced_host_is_slowed_down() {
+    let (mut emulator, clock_reads) = start(false);
+    emulator.set_speed(Speed::Multiplier(MIN_SPEED)).unwrap();
+    emulator.run_frame();
+    emulator.run_cycles(100000);
+    assert_eq!(clock_reads.load(Ordering::Relaxed), 0);
+
+    let (mut emulator, clock_reads) = start(true);
+    emulator.run_frame();
+    emulator.run_frame();
+    assert_eq!(clock_reads.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn a_paced_host_waits_for_the_emulated_time() {
+    let (mut emulator, _) = start(true);
+    emulator.set_speed(Speed::Multiplier(2.0)).unwrap();
+
+    let start = Instant::now();
+    for _ in 0..4 {
+        emulator.run_frame();
+    }
+
+    // the first frame is cut short by the boot, the 3 full ones behind it are waited for
+    assert!(start.elapsed() >= FRAME * 3 / 2, "4 frames at twice the speed took {:?}", start.elapsed());
+}
+
+#[test]
+fn turbo_runs_uncapped_and_owes_nothing_afterwards() {
+    let (mut emulator, clock_reads) = start(true);
+
+    emulator.set_speed(Speed::Uncapped).unwrap();
+    for _ in 0..30 {
+        emulator.run_frame();
+    }
+    assert_eq!(clock_reads.load(Ordering::Relaxed), 0);
+
+    // back at the real speed the frames run in turbo aren't waited for
+    emulator.set_speed(Speed::Multiplier(1.0)).unwrap();
+    let start = Instant::now();
+    emulator.run_frame();
+
+    assert_eq!(emulator.speed(), Speed::Multiplier(1.0));
+    assert_eq!(clock_reads.load(Ordering::Relaxed), 1);
+    assert!(start.elapsed() < FRAME * 10, "the frame after turbo took {:?}", start.elapsed());
+}
@@ -0,0 +1,69 @@
+// save states that are damaged in a way the length check can't see
+
+mod common;
+
+use common::test_rom;
+use gameboy_core::emulator::{Emulator, EmulatorError};
+
+// an emulator that wrote value to the io register $ff00 + register and loops, with its snapshot
+fn after_writing(register: u8, value: u8) -> (Emulator, Vec<u8>) {
+    // ld a, value; ldh (register), a; xor a; jr -2
+    let program = [0x3E, value, 0xE0, register, 0xAF, 0x18, 0xFE];
+    let mut emulator = common::emulator(test_rom(&program, 0x8000));
+    emulator.run_frame();
+
+    let state = emulator.save_state();
+    (emulator, state)
+}
+
+// where the snapshots of two machines that only wrote a different value to a register differ
+fn register_byte(register: u8, first: u8, second: u8) -> usize {
+    let (_, first) = after_writing(register, first);
+    let (_, second) = after_writing(register, second);
+
+    let differences: Vec<usize> = (0..first.len()).filter(|index| first[*index] != second[*index]).collect();
+    assert_eq!(differences.len(), 1);
+    differences[0]
+}
+
+fn assert_refused(emulator: &mut Emulator, damaged: &[u8]) {
+    let before = emulator.save_state();
+
+    match emulator.load_state(damaged) {
+        Err(EmulatorError::InvalidState(_)) => {}
+        other => panic!("expected an invalid state, got {:?}", other.err()),
+    }
+
+    // the refused state left the machine alone
+    assert_eq!(emulator.save_state(), before);
+    emulator.run_frame();
+}
+
+#[test]
+fn out_of_range_bank_is_refused() {
+    let index = register_byte(0x70, 2, 3);
+    let (mut emulator, mut damaged) = after_writing(0x70, 3);
+
+    damaged[index] = 0x40;
+    assert_refused(&mut emulator, &damaged);
+}
+
+// a timer speed outside the four tac selects would never let the timer tick
+#[test]
+fn out_of_range_timer_speed_is_refused() {
+    let index = register_byte(0x07, 0x01, 0x02);
+    let (mut emulator, mut damaged) = after_writing(0x07, 0x02);
+
+    damaged[index] = 0x04;
+    assert_refused(&mut emulator, &damaged);
+}
+
+#[test]
+fn joypad_select_is_masked() {
+    let index = register_byte(0x00, 0x10, 0x20);
+    let (mut emulator, mut damaged) = after_writing(0x00, 0x20);
+
+    damaged[index] = 0xFF;
+    emulator.load_state(&damaged).unwrap();
+    assert_eq!(emulator.save_state()[index], 0x30);
+}
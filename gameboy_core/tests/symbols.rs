@@ -0,0 +1,137 @@
+// symbol files as rgbds and wla-dx write them and the labels found for an address
+
+use gameboy_core::emulator::EmulatorError;
+use gameboy_core::symbols::{Symbol, SymbolTable};
+
+const RGBDS: &str = "\
+; File generated by rgblink
+00:0150 Start
+00:0160 Start.loop
+00:3ff0 EndOfBankZero
+01:4000 BankOne
+01:4010 BankOne.inner
+02:4000 BankTwo
+00:c000 wPlayerLives
+00:c100 .anonymous
+";
+
+const WLA_DX: &str = "\
+; wla symbolic information file
+
+[information]
+version 2
+
+[labels]
+0000:0150 Start ; the entry point
+0001:4000 BankOne
+
+[definitions]
+00000040 _sizeof_Start
+";
+
+fn invalid(text: &str) -> &'static str {
+    match SymbolTable::parse(text) {
+        Err(EmulatorError::InvalidSymbols(reason)) => reason,
+        Err(other) => panic!("{:?} isn't a symbol error", other),
+        Ok(table) => panic!("{:?} was taken with {} symbols", text, table.len()),
+    }
+}
+
+fn name(table: &SymbolTable, bank: usize, adder: u16) -> Option<&str> {
+    table.containing(bank, adder).map(|symbol| symbol.name.as_str())
+}
+
+#[test]
+fn rgbds_files() {
+    let table = SymbolTable::parse(RGBDS).unwrap();
+
+    assert_eq!(table.len(), 8);
+    assert_eq!(
+        table.lookup("BankOne.inner"),
+        Some(&Symbol { bank: 1, adder: 0x4010, name: "BankOne.inner".to_string() })
+    );
+    assert_eq!(table.lookup("wPlayerLives").map(|symbol| symbol.adder), Some(0xC000));
+    assert_eq!(table.lookup("Missing"), None);
+}
+
+#[test]
+fn wla_dx_files_only_take_the_labels_section() {
+    let table = SymbolTable::parse(WLA_DX).unwrap();
+
+    // the information and definitions sections aren't symbols
+    assert_eq!(table.len(), 2);
+    assert_eq!(table.lookup("Start").map(|symbol| (symbol.bank, symbol.adder)), Some((0, 0x150)));
+    assert_eq!(table.lookup("BankOne").map(|symbol| (symbol.bank, symbol.adder)), Some((1, 0x4000)));
+    assert_eq!(table.lookup("_sizeof_Start"), None);
+}
+
+#[test]
+fn comments_and_blank_lines() {
+    let table = SymbolTable::parse("; only a comment\n\n   \n00:0150 Start ; after a label\n").unwrap();
+
+    assert_eq!(table.len(), 1);
+    assert_eq!(table.lookup("Start").map(|symbol| symbol.adder), Some(0x150));
+}
+
+#[test]
+fn bad_lines() {
+    assert_eq!(invalid("00:0150"), "expected a bank:adder and a label on every line");
+    assert_eq!(invalid("00:0150 Start Extra"), "expected a bank:adder and a label on every line");
+    assert_eq!(invalid("0150 Start"), "a symbol location is missing its bank");
+    assert_eq!(invalid("zz:0150 Start"), "bad bank number");
+    assert_eq!(invalid("00:10000 Start"), "bad symbol address");
+}
+
+#[test]
+fn the_closest_label_at_or_before_an_address() {
+    let table = SymbolTable::parse(RGBDS).unwrap();
+
+    assert_eq!(name(&table, 0, 0x0150), Some("Start"));
+    assert_eq!(name(&table, 0, 0x015F), Some("Start"));
+    assert_eq!(name(&table, 0, 0x0160), Some("Start.loop"));
+    assert_eq!(table.symbolize(0, 0x0163), Some("Start.loop+3".to_string()));
+
+    // nothing comes before the first label
+    assert_eq!(name(&table, 0, 0x014F), None);
+
+    // the same address in another bank has its own labels
+    assert_eq!(name(&table, 1, 0x4008), Some("BankOne"));
+    assert_eq!(name(&table, 2, 0x4008), Some("BankTwo"));
+    assert_eq!(name(&table, 3, 0x4008), None);
+}
+
+#[test]
+fn labels_stop_at_the_end_of_their_region() {
+    let table = SymbolTable::parse(RGBDS).unwrap();
+
+    // the last label of bank 0 doesn't run on into vram, wram or io
+    assert_eq!(name(&table, 0, 0x3FFF), Some("EndOfBankZero"));
+    assert_eq!(name(&table, 0, 0x8000), None);
+    assert_eq!(name(&table, 0, 0xBFFF), None);
+
+    // wram bank 0 ends at $cfff, $d000 is the switchable bank
+    assert_eq!(name(&table, 0, 0xCFFF), Some(".anonymous"));
+    assert_eq!(name(&table, 0, 0xD000), None);
+    assert_eq!(name(&table, 0, 0xFF80), None);
+}
+
+#[test]
+fn local_labels_fold_into_their_parent() {
+    let table = SymbolTable::parse(RGBDS).unwrap();
+
+    assert_eq!(table.function(0, 0x0155), Some("Start"));
+    assert_eq!(table.function(0, 0x0160), Some("Start"));
+    assert_eq!(table.function(1, 0x4020), Some("BankOne"));
+
+    // a label that starts with a dot has no parent to fold into
+    assert_eq!(table.function(0, 0xC100), Some(".anonymous"));
+    assert_eq!(table.function(0, 0x0100), None);
+}
+
+#[test]
+fn the_first_of_two_labels_with_one_name_is_kept() {
+    let table = SymbolTable::parse("01:4000 Twice\n00:0150 Twice\n").unwrap();
+
+    // sorted by bank first, so bank 0 is the first one
+    assert_eq!(table.lookup("Twice").map(|symbol| (symbol.bank, symbol.adder)), Some((0, 0x150)));
+}
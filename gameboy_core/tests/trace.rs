@@ -0,0 +1,101 @@
+// instruction traces in the gameboy doctor format
+
+mod common;
+
+use common::test_rom;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+// ld a, $42; ld b, a; jr -2
+const PROGRAM: [u8; 5] = [0x3E, 0x42, 0x47, 0x18, 0xFE];
+
+// halt with every interrupt disabled, it never wakes up
+const HALT: [u8; 1] = [0x76];
+
+// lines the gameboy doctor reference logs hold for the same state, the first one is the first line of
+// every one of them
+const GOLDEN: [&str; 6] = [
+    "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,50,01",
+    "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0101 PCMEM:C3,50,01,CE",
+    "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0150 PCMEM:3E,42,47,18",
+    "A:42 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0152 PCMEM:47,18,FE,00",
+    "A:42 F:B0 B:42 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0153 PCMEM:18,FE,00,00",
+    "A:42 F:B0 B:42 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0153 PCMEM:18,FE,00,00",
+];
+
+// a writer the test can still read after the emulator took it
+#[derive(Clone, Default)]
+struct Shared(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Shared {
+    fn lines(&self) -> Vec<String> {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap().lines().map(str::to_string).collect()
+    }
+}
+
+struct Broken;
+
+impl Write for Broken {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::other("disk full"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn a_line_before_every_instruction() {
+    let out = Shared::default();
+    let mut emulator = common::emulator(test_rom(&PROGRAM, 0x8000));
+
+    emulator.set_trace(Some(Box::new(out.clone()))).unwrap();
+    for _ in 0..GOLDEN.len() {
+        emulator.run_cycles(1);
+    }
+    emulator.set_trace(None).unwrap();
+
+    assert_eq!(out.lines(), GOLDEN);
+
+    // with the trace stopped nothing more is logged
+    emulator.run_cycles(100);
+    assert_eq!(out.lines().len(), GOLDEN.len());
+}
+
+#[test]
+fn nothing_is_logged_while_halted() {
+    let out = Shared::default();
+    let mut emulator = common::emulator(test_rom(&HALT, 0x8000));
+
+    emulator.set_trace(Some(Box::new(out.clone()))).unwrap();
+    emulator.run_frame();
+    emulator.set_trace(None).unwrap();
+
+    let lines = out.lines();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[2].ends_with("PC:0150 PCMEM:76,00,00,00"));
+}
+
+#[test]
+fn the_first_write_error_is_returned() {
+    let mut emulator = common::emulator(test_rom(&PROGRAM, 0x8000));
+
+    emulator.set_trace(Some(Box::new(Broken))).unwrap();
+    emulator.run_cycles(100);
+
+    // the buffer only reaches the writer when it is flushed
+    let error = emulator.set_trace(None).unwrap_err();
+    assert_eq!(error.to_string(), "disk full");
+}